@@ -0,0 +1,92 @@
+//! Pluggable time source
+//!
+//! Parsers read time through the active [`Clock`] instead of calling browser APIs,
+//! so the same code runs under wasm-bindgen and in native binaries.
+
+use std::sync::{Arc, RwLock};
+
+/// Source of timings (for `parse_time`) and timestamps (for generated ids)
+pub trait Clock: Send + Sync {
+    /// Monotonic time in milliseconds
+    fn now(&self) -> f64;
+
+    /// Wall-clock time in milliseconds since the Unix epoch
+    fn timestamp(&self) -> u64;
+}
+
+/// Default clock: `performance.now()` / `Date.now()` in the browser, `std::time` elsewhere
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        web_sys::window()
+            .and_then(|w| w.performance())
+            .map(|p| p.now())
+            .unwrap_or(0.0)
+    }
+
+    fn timestamp(&self) -> u64 {
+        js_sys::Date::now() as u64
+    }
+}
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        START.elapsed().as_secs_f64() * 1000.0
+    }
+
+    fn timestamp(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+lazy_static::lazy_static! {
+    static ref START: std::time::Instant = std::time::Instant::now();
+}
+
+lazy_static::lazy_static! {
+    static ref CLOCK: RwLock<Arc<dyn Clock>> = RwLock::new(Arc::new(SystemClock));
+}
+
+/// Replace the clock used by all parsers
+pub fn set_clock(clock: Arc<dyn Clock>) {
+    if let Ok(mut current) = CLOCK.write() {
+        *current = clock;
+    }
+}
+
+/// Restore the default [`SystemClock`]
+pub fn reset_clock() {
+    set_clock(Arc::new(SystemClock));
+}
+
+/// Current time in milliseconds from the active clock
+pub fn now() -> f64 {
+    CLOCK.read().map(|c| c.now()).unwrap_or(0.0)
+}
+
+/// Current timestamp in milliseconds from the active clock
+pub fn timestamp() -> u64 {
+    CLOCK.read().map(|c| c.timestamp()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_clock_is_monotonic() {
+        let clock = SystemClock;
+        let first = clock.now();
+        let second = clock.now();
+        assert!(second >= first);
+        assert!(clock.timestamp() > 0);
+    }
+}
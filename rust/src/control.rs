@@ -0,0 +1,90 @@
+//! Cancellation and pause control for in-progress parses
+//!
+//! Rust counterpart of the TypeScript `ParseController`. Clones share state, so one
+//! handle can be given to the parser and another kept by the caller.
+//!
+//! Pausing only works in native builds, where another thread can call `resume`.
+//! It is out of scope on wasm32: the JS bindings export cancellation only.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared cancel/pause flags checked by parsers between records
+#[derive(Debug, Clone, Default)]
+pub struct ParseController {
+    cancelled: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+}
+
+impl ParseController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation; the parser stops at the next record boundary
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Pause the parser at the next record boundary
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Called between records: waits while paused, returns false once cancelled
+    ///
+    /// Blocking only happens in native builds; in the browser nothing else can run
+    /// while the parser holds the thread, so a pause is ignored there.
+    pub fn checkpoint(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        while self.is_paused() && !self.is_cancelled() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        !self.is_cancelled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_is_shared_between_clones() {
+        let controller = ParseController::new();
+        let handle = controller.clone();
+        assert!(controller.checkpoint());
+
+        handle.cancel();
+        assert!(controller.is_cancelled());
+        assert!(!controller.checkpoint());
+    }
+
+    #[test]
+    fn test_resume_unblocks_checkpoint() {
+        let controller = ParseController::new();
+        controller.pause();
+
+        let handle = controller.clone();
+        let resumer = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            handle.resume();
+        });
+
+        assert!(controller.checkpoint());
+        assert!(!controller.is_paused());
+        resumer.join().unwrap();
+    }
+}
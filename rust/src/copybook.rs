@@ -0,0 +1,605 @@
+//! COBOL Copybook Import
+//!
+//! Turns copybook record descriptions (`PIC`, `USAGE`, `OCCURS`, `REDEFINES`, `SIGN`)
+//! into fixed-width `FieldDefinition`s so mainframe layouts don't have to be typed by hand.
+//!
+//! Each elementary item becomes one definition. `OCCURS` items are expanded with a
+//! `(n)` suffix, `REDEFINES` items overlap the storage of the item they redefine, and
+//! every level-01 record starts again at offset 0. Numeric-edited pictures (`ZZ9.99`,
+//! `-9(5)`, `$$,$$9.99CR`) become `edited` fields that are read against their PIC.
+
+use crate::types::*;
+
+/// Parse copybook text into fixed-width field definitions
+pub fn parse_copybook(text: &str) -> Result<Vec<FieldDefinition>, ParseError> {
+    let entries = parse_entries(text)?;
+    let tree = build_tree(entries)?;
+
+    let mut definitions = Vec::new();
+    let mut offsets = std::collections::HashMap::new();
+    for record in &tree {
+        layout_item(record, 0, "", None, &mut offsets, &mut definitions)?;
+    }
+
+    if definitions.is_empty() {
+        return Err(ParseError::CopybookError(
+            "no elementary items with a PIC clause found".to_string(),
+        ));
+    }
+
+    Ok(definitions)
+}
+
+/// Build a fixed-width parser configuration from copybook text
+pub fn copybook_to_config(text: &str, name: &str) -> Result<ParserConfig, ParseError> {
+    Ok(ParserConfig {
+        parser_type: "fixed-width".to_string(),
+        name: name.to_string(),
+        description: Some("Generated from COBOL copybook".to_string()),
+        field_definitions: Some(parse_copybook(text)?),
+        ..Default::default()
+    })
+}
+
+/// One data description entry (a period-terminated statement)
+#[derive(Debug, Clone, Default)]
+struct Entry {
+    level: u8,
+    name: String,
+    picture: Option<String>,
+    usage: Option<Usage>,
+    occurs: usize,
+    redefines: Option<String>,
+    sign_separate: bool,
+}
+
+/// Storage format of a numeric item
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Usage {
+    Display,
+    Packed,
+    Binary,
+}
+
+/// Entry with its subordinate items
+#[derive(Debug)]
+struct Item {
+    entry: Entry,
+    children: Vec<Item>,
+}
+
+/// Split copybook text into data description entries
+fn parse_entries(text: &str) -> Result<Vec<Entry>, ParseError> {
+    let fixed = is_fixed_format(text);
+    let mut source = String::new();
+    for line in text.lines() {
+        let line = if fixed { strip_sequence_area(line) } else { line };
+        if line.trim_start().starts_with('*') {
+            continue;
+        }
+        source.push_str(line);
+        source.push(' ');
+    }
+
+    let mut entries = Vec::new();
+    for statement in split_statements(&source) {
+        let tokens: Vec<&str> = statement.split_whitespace().collect();
+        if tokens.is_empty() {
+            continue;
+        }
+        let level: u8 = tokens[0].parse().map_err(|_| {
+            ParseError::CopybookError(format!("expected a level number in \"{}\"", statement))
+        })?;
+
+        // 66 (RENAMES) and 88 (condition names) don't describe storage
+        if level == 66 || level == 88 {
+            continue;
+        }
+
+        entries.push(parse_entry(level, &tokens[1..], statement)?);
+    }
+
+    Ok(entries)
+}
+
+/// Whether `text` is fixed-format source: every non-blank line has an indicator
+/// (space, `*`, `/` or `-`) in column 7, whatever its sequence area holds
+fn is_fixed_format(text: &str) -> bool {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .all(|line| matches!(line.chars().nth(6), Some(' ' | '*' | '/' | '-')))
+}
+
+/// Program text of a fixed-format line: columns 8-72, dropping the sequence area
+/// (columns 1-6), comment lines and the identification area (columns 73-80)
+fn strip_sequence_area(line: &str) -> &str {
+    let at = |column: usize| line.char_indices().nth(column).map_or(line.len(), |(i, _)| i);
+    if matches!(line.chars().nth(6), Some('*' | '/')) {
+        return "";
+    }
+    &line[at(7)..at(72)]
+}
+
+/// Split source into statements on periods followed by whitespace or end of input
+fn split_statements(source: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let bytes = source.as_bytes();
+
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'.' && bytes.get(i + 1).is_none_or(|n| n.is_ascii_whitespace()) {
+            statements.push(source[start..i].trim());
+            start = i + 1;
+        }
+    }
+    if !source[start..].trim().is_empty() {
+        statements.push(source[start..].trim());
+    }
+
+    statements
+}
+
+/// Parse the clauses following a level number
+fn parse_entry(level: u8, tokens: &[&str], statement: &str) -> Result<Entry, ParseError> {
+    let mut entry = Entry {
+        level,
+        name: "FILLER".to_string(),
+        occurs: 1,
+        ..Default::default()
+    };
+
+    let mut i = 0;
+    if let Some(first) = tokens.first() {
+        if !is_keyword(first) {
+            entry.name = first.to_uppercase();
+            i = 1;
+        }
+    }
+
+    let missing = |clause: &str| {
+        ParseError::CopybookError(format!(
+            "{} clause without a value in \"{}\"",
+            clause, statement
+        ))
+    };
+
+    while i < tokens.len() {
+        let token = tokens[i].to_uppercase();
+        match token.as_str() {
+            "PIC" | "PICTURE" => {
+                i += 1;
+                if tokens.get(i).map(|t| t.eq_ignore_ascii_case("IS")) == Some(true) {
+                    i += 1;
+                }
+                let picture = tokens.get(i).ok_or_else(|| missing("PIC"))?;
+                entry.picture = Some(picture.to_uppercase());
+            }
+            "USAGE" | "IS" => {}
+            "COMP-3" | "COMPUTATIONAL-3" | "PACKED-DECIMAL" => entry.usage = Some(Usage::Packed),
+            "COMP" | "COMPUTATIONAL" | "COMP-4" | "COMPUTATIONAL-4" | "COMP-5"
+            | "COMPUTATIONAL-5" | "BINARY" => entry.usage = Some(Usage::Binary),
+            "DISPLAY" => entry.usage = Some(Usage::Display),
+            "COMP-1" | "COMP-2" | "COMPUTATIONAL-1" | "COMPUTATIONAL-2" => {
+                return Err(ParseError::CopybookError(format!(
+                    "floating-point usage {} is not supported for {}",
+                    token, entry.name
+                )));
+            }
+            "OCCURS" => {
+                i += 1;
+                let count = tokens.get(i).ok_or_else(|| missing("OCCURS"))?;
+                entry.occurs = count.parse().map_err(|_| {
+                    ParseError::CopybookError(format!(
+                        "invalid OCCURS count \"{}\" for {}",
+                        count, entry.name
+                    ))
+                })?;
+                // OCCURS n TO m: storage is laid out for the maximum
+                if tokens.get(i + 1).map(|t| t.eq_ignore_ascii_case("TO")) == Some(true) {
+                    i += 2;
+                    if let Some(max) = tokens.get(i).and_then(|t| t.parse().ok()) {
+                        entry.occurs = max;
+                    }
+                }
+            }
+            "REDEFINES" => {
+                i += 1;
+                let target = tokens.get(i).ok_or_else(|| missing("REDEFINES"))?;
+                entry.redefines = Some(target.to_uppercase());
+            }
+            "SEPARATE" => entry.sign_separate = true,
+            "VALUE" | "VALUES" => break,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    Ok(entry)
+}
+
+/// Check whether a token starts a clause rather than naming the item
+fn is_keyword(token: &str) -> bool {
+    let token = token.to_uppercase();
+    let usage = token
+        .strip_prefix("COMPUTATIONAL")
+        .or_else(|| token.strip_prefix("COMP"))
+        .is_some_and(|rest| matches!(rest, "" | "-1" | "-2" | "-3" | "-4" | "-5"));
+    usage
+        || matches!(
+            token.as_str(),
+            "PIC"
+                | "PICTURE"
+                | "USAGE"
+                | "BINARY"
+                | "PACKED-DECIMAL"
+                | "DISPLAY"
+                | "SIGN"
+                | "LEADING"
+                | "TRAILING"
+                | "SEPARATE"
+                | "OCCURS"
+                | "REDEFINES"
+                | "VALUE"
+                | "VALUES"
+                | "JUST"
+                | "JUSTIFIED"
+                | "BLANK"
+                | "SYNC"
+                | "SYNCHRONIZED"
+        )
+}
+
+/// Nest entries by level number
+fn build_tree(mut entries: Vec<Entry>) -> Result<Vec<Item>, ParseError> {
+    // Copybook fragments often start below level 01: wrap them in a record
+    if entries.first().map(|e| e.level != 1 && e.level != 77) == Some(true) {
+        entries.insert(
+            0,
+            Entry {
+                level: 1,
+                name: "RECORD".to_string(),
+                occurs: 1,
+                ..Default::default()
+            },
+        );
+    }
+
+    let mut stack: Vec<Item> = Vec::new();
+    let mut roots = Vec::new();
+
+    let close = |stack: &mut Vec<Item>, roots: &mut Vec<Item>| {
+        if let Some(item) = stack.pop() {
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(item),
+                None => roots.push(item),
+            }
+        }
+    };
+
+    for entry in entries {
+        if entry.level == 0 || (entry.level > 49 && entry.level != 77) {
+            return Err(ParseError::CopybookError(format!(
+                "invalid level number {} for {}",
+                entry.level, entry.name
+            )));
+        }
+        while stack.last().map(|top| top.entry.level >= entry.level) == Some(true) {
+            close(&mut stack, &mut roots);
+        }
+        stack.push(Item {
+            entry,
+            children: Vec::new(),
+        });
+    }
+    while !stack.is_empty() {
+        close(&mut stack, &mut roots);
+    }
+
+    Ok(roots)
+}
+
+/// Lay out an item at `offset`, appending definitions and returning the bytes it occupies
+fn layout_item(
+    item: &Item,
+    offset: usize,
+    suffix: &str,
+    inherited_usage: Option<Usage>,
+    offsets: &mut std::collections::HashMap<String, usize>,
+    definitions: &mut Vec<FieldDefinition>,
+) -> Result<usize, ParseError> {
+    let entry = &item.entry;
+    let usage = entry.usage.or(inherited_usage);
+    let mut size = 0;
+
+    for occurrence in 0..entry.occurs {
+        let suffix = if entry.occurs > 1 {
+            format!("{}({})", suffix, occurrence + 1)
+        } else {
+            suffix.to_string()
+        };
+        let start = offset + size;
+        offsets.insert(entry.name.clone(), start);
+
+        let occupied = if let Some(picture) = &entry.picture {
+            let definition = elementary_definition(entry, picture, usage, start, &suffix)?;
+            let length = definition.length;
+            if entry.name != "FILLER" {
+                definitions.push(definition);
+            }
+            length
+        } else {
+            layout_group(item, start, &suffix, usage, offsets, definitions)?
+        };
+        size += occupied;
+    }
+
+    Ok(size)
+}
+
+/// Lay out the children of a group item, honouring REDEFINES
+fn layout_group(
+    item: &Item,
+    start: usize,
+    suffix: &str,
+    usage: Option<Usage>,
+    offsets: &mut std::collections::HashMap<String, usize>,
+    definitions: &mut Vec<FieldDefinition>,
+) -> Result<usize, ParseError> {
+    let mut cursor = start;
+    let mut end = start;
+
+    for child in &item.children {
+        if let Some(target) = &child.entry.redefines {
+            let target_start = *offsets.get(target).ok_or_else(|| {
+                ParseError::CopybookError(format!(
+                    "{} redefines unknown item {}",
+                    child.entry.name, target
+                ))
+            })?;
+            let size = layout_item(child, target_start, suffix, usage, offsets, definitions)?;
+            end = end.max(target_start + size);
+        } else {
+            cursor += layout_item(child, cursor, suffix, usage, offsets, definitions)?;
+            end = end.max(cursor);
+        }
+    }
+
+    Ok(end - start)
+}
+
+/// Build the definition of an elementary item from its PIC string
+fn elementary_definition(
+    entry: &Entry,
+    picture: &str,
+    usage: Option<Usage>,
+    start: usize,
+    suffix: &str,
+) -> Result<FieldDefinition, ParseError> {
+    let expanded = expand_picture(picture).ok_or_else(|| {
+        ParseError::CopybookError(format!("invalid PIC {} for {}", picture, entry.name))
+    })?;
+
+    let signed = expanded.starts_with('S');
+    let is_numeric = expanded.chars().all(|c| matches!(c, '9' | 'S' | 'V' | 'P'));
+    let digits = expanded.chars().filter(|&c| c == '9').count();
+    let scale = expanded
+        .find('V')
+        .map(|v| expanded[v..].chars().filter(|&c| c == '9').count() as u32);
+
+    let usage = if is_numeric {
+        usage.unwrap_or(Usage::Display)
+    } else {
+        Usage::Display
+    };
+
+    let length = match usage {
+        Usage::Packed => digits / 2 + 1,
+        Usage::Binary => match digits {
+            0..=4 => 2,
+            5..=9 => 4,
+            _ => 8,
+        },
+        Usage::Display => {
+            let chars = expanded
+                .chars()
+                .filter(|c| !matches!(c, 'S' | 'V' | 'P'))
+                .count();
+            chars + usize::from(signed && entry.sign_separate)
+        }
+    };
+
+    let is_edited_numeric = !is_numeric && is_numeric_edited(&expanded);
+    let field_type = if is_numeric || is_edited_numeric {
+        "number"
+    } else {
+        "string"
+    };
+
+    let usage_name = match usage {
+        Usage::Packed => Some("packed"),
+        Usage::Binary => Some("binary"),
+        Usage::Display if is_numeric && (signed || scale.is_some()) => Some("zoned"),
+        Usage::Display if is_edited_numeric => Some("edited"),
+        Usage::Display => None,
+    };
+
+    let name = format!("{}{}", entry.name, suffix);
+    let clause = match usage {
+        Usage::Packed => " COMP-3",
+        Usage::Binary => " COMP",
+        Usage::Display => "",
+    };
+
+    Ok(FieldDefinition {
+        id: name
+            .to_lowercase()
+            .replace(['(', ')'], "-")
+            .trim_end_matches('-')
+            .to_string(),
+        name,
+        start,
+        length,
+        field_type: field_type.to_string(),
+        format: Some(picture.to_string()),
+        required: false,
+        description: Some(format!("PIC {}{}", picture, clause)),
+        usage: usage_name.map(str::to_string),
+        scale: if is_numeric { scale } else { None },
+        signed: is_numeric && signed,
+    })
+}
+
+/// Whether an expanded picture is numeric-edited: digit positions with insertion,
+/// suppression, sign or currency symbols and an optional `CR`/`DB` suffix
+fn is_numeric_edited(expanded: &str) -> bool {
+    let body = expanded
+        .strip_suffix("CR")
+        .or_else(|| expanded.strip_suffix("DB"))
+        .unwrap_or(expanded);
+    body.chars().any(|c| matches!(c, '9' | 'Z' | '*' | '+' | '-' | '$'))
+        && body.chars().all(|c| "9Z*+-$,./B0VP".contains(c))
+}
+
+/// Expand repeat counts, e.g. `S9(3)V99` -> `S999V99`
+pub(crate) fn expand_picture(picture: &str) -> Option<String> {
+    let mut expanded = String::new();
+    let mut chars = picture.chars().peekable();
+    let mut last = None;
+
+    while let Some(c) = chars.next() {
+        if c == '(' {
+            let mut count = String::new();
+            for d in chars.by_ref() {
+                if d == ')' {
+                    break;
+                }
+                count.push(d);
+            }
+            let repeat: usize = count.parse().ok()?;
+            let symbol = last?;
+            for _ in 1..repeat {
+                expanded.push(symbol);
+            }
+        } else {
+            let upper = c.to_ascii_uppercase();
+            expanded.push(upper);
+            last = Some(upper);
+        }
+    }
+
+    Some(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COPYBOOK: &str = "
+      * Customer account record
+       01  ACCOUNT-REC.
+           05  ACCT-ID            PIC X(10).
+           05  BALANCE            PIC S9(7)V99 COMP-3.
+           05  RATE               PIC S9(3)V99.
+           05  HISTORY OCCURS 2 TIMES.
+               10  HIST-AMT       PIC 9(5).
+           05  ALT-ID REDEFINES ACCT-ID PIC 9(10).
+           05  FILLER             PIC X(3).
+           05  STATUS-CODE        PIC X.
+               88  ACTIVE         VALUE 'A'.
+    ";
+
+    #[test]
+    fn test_parse_copybook_offsets() {
+        let defs = parse_copybook(COPYBOOK).unwrap();
+        let layout: Vec<(&str, usize, usize)> = defs
+            .iter()
+            .map(|d| (d.name.as_str(), d.start, d.length))
+            .collect();
+
+        assert_eq!(
+            layout,
+            [
+                ("ACCT-ID", 0, 10),
+                ("BALANCE", 10, 5),
+                ("RATE", 15, 5),
+                ("HIST-AMT(1)", 20, 5),
+                ("HIST-AMT(2)", 25, 5),
+                ("ALT-ID", 0, 10),
+                ("STATUS-CODE", 33, 1),
+            ]
+        );
+        assert_eq!(defs[1].usage.as_deref(), Some("packed"));
+        assert_eq!(defs[1].scale, Some(2));
+        assert!(defs[1].signed);
+        assert_eq!(defs[2].usage.as_deref(), Some("zoned"));
+    }
+
+    #[test]
+    fn test_sequence_area_and_config() {
+        let text = format!(
+            "000100 01 REC.\n000200*COMMENT\n{:<72}SEQ00001",
+            "000300     05 CODE PIC XX."
+        );
+        let config = copybook_to_config(&text, "Mainframe").unwrap();
+
+        assert_eq!(config.parser_type, "fixed-width");
+        let defs = config.field_definitions.unwrap();
+        assert_eq!(defs.len(), 1);
+        assert_eq!(defs[0].name, "CODE");
+        assert_eq!(defs[0].length, 2);
+
+        // Blank sequence numbers still leave the identification area out
+        let text = format!(
+            "{:<72}ACCTREC1\n{:<72}ACCTREC2\n      * {:<64}ACCTREC3",
+            "       01 REC.", "           05 CODE PIC XX.", "NOTE"
+        );
+        let defs = parse_copybook(&text).unwrap();
+        assert_eq!(defs.len(), 1);
+        assert_eq!((defs[0].name.as_str(), defs[0].length), ("CODE", 2));
+
+        // Free-format source is read as is
+        let defs = parse_copybook("01 REC.\n  05 NAME PIC X(20).").unwrap();
+        assert_eq!(defs[0].length, 20);
+    }
+
+    #[test]
+    fn test_usage_and_sign_clauses() {
+        let defs = parse_copybook(
+            "01 REC.
+               05 BINARY PIC 9(4).
+               05 CNT PIC S9(4) USAGE IS BINARY.
+               05 AMT PIC S9(5)V99 PACKED-DECIMAL.
+               05 DISPLAY PIC X(2).
+               05 BAL PIC S9(5) SIGN IS LEADING SEPARATE CHARACTER.
+               05 TOTAL PIC $$,$$9.99CR.
+               05 QTY PIC -9(5).",
+        )
+        .unwrap();
+        let layout: Vec<(&str, usize, usize, Option<&str>)> = defs
+            .iter()
+            .map(|d| (d.name.as_str(), d.start, d.length, d.usage.as_deref()))
+            .collect();
+
+        // Unnamed items start with a clause and are FILLER
+        assert_eq!(
+            layout,
+            [
+                ("CNT", 2, 2, Some("binary")),
+                ("AMT", 4, 4, Some("packed")),
+                ("BAL", 10, 6, Some("zoned")),
+                ("TOTAL", 16, 11, Some("edited")),
+                ("QTY", 27, 6, Some("edited")),
+            ]
+        );
+        assert_eq!(defs[3].field_type, "number");
+        assert_eq!(defs[3].format.as_deref(), Some("$$,$$9.99CR"));
+    }
+
+    #[test]
+    fn test_invalid_copybook() {
+        assert!(matches!(
+            parse_copybook("01 REC. 05 BAD PIC 9(X)."),
+            Err(ParseError::CopybookError(_))
+        ));
+    }
+}
@@ -2,88 +2,54 @@
 //!
 //! Uses the `csv` crate for zero-copy, streaming CSV parsing.
 //! 10-50x faster than JavaScript implementations for large files.
+//! Multi-character and pattern delimiters are split by [`crate::delimited`].
+//!
+//! Dialect options: `escape_char` (escapes the next character inside quotes),
+//! `comment_char` (skips lines starting with it), `skip_rows` (preamble lines
+//! before the header) and `trim` (`none`, `headers`, `fields` or `all`).
+//!
+//! Header/detail/trailer files type their rows with `row_rules`; each kind of row
+//! has its own column names and inferred column types.
 
+use crate::clock;
+use crate::control::ParseController;
+use crate::dates;
+use crate::delimited::{self, Delimiter};
+use crate::numbers::NumberFormat;
+use crate::progress::ProgressTracker;
+use crate::totals;
 use crate::types::*;
+use crate::utils::{generate_id, LineIndex};
 use csv::{ReaderBuilder, StringRecord};
+use regex::Regex;
+use std::collections::HashSet;
 use std::io::Cursor;
 
 /// Parse CSV data
 pub fn parse_csv(data: &str, config: &ParserConfig) -> Result<ParsedData, ParseError> {
-    let start_time = get_time();
+    let start_time = clock::now();
     let total_bytes = data.len();
 
-    let delimiter = config.delimiter.as_bytes().first().copied().unwrap_or(b',');
-    let quote = config.quote_char.as_bytes().first().copied().unwrap_or(b'"');
-
-    let mut reader = ReaderBuilder::new()
-        .delimiter(delimiter)
-        .quote(quote)
-        .has_headers(config.has_header)
-        .flexible(true) // Allow variable number of fields
-        .from_reader(Cursor::new(data));
-
-    let headers: Vec<String> = if config.has_header {
-        reader
-            .headers()
-            .map(|h| h.iter().map(|s| s.trim().to_string()).collect())
-            .unwrap_or_default()
-    } else {
-        // Generate column names for headerless CSV
-        let first_record = reader.records().next();
-        if let Some(Ok(record)) = first_record {
-            (0..record.len())
-                .map(|i| format!("Column {}", i + 1))
-                .collect()
-        } else {
-            vec![]
-        }
-    };
-
-    // Re-create reader if we consumed it for headers
-    let mut reader = ReaderBuilder::new()
-        .delimiter(delimiter)
-        .quote(quote)
-        .has_headers(config.has_header)
-        .flexible(true)
-        .from_reader(Cursor::new(data));
+    let rules = RowRules::from_config(config)?;
+    let lines = LineIndex::new(data);
+    let (headers, rows) = read_rows(data, config, &lines)?;
+    let rows: Vec<Row> = rows.collect();
 
-    let mut records = Vec::new();
-    let mut valid_count = 0usize;
-    let mut invalid_count = 0usize;
-
-    for (index, result) in reader.records().enumerate() {
-        match result {
-            Ok(record) => {
-                let parsed_record = create_record(index, &record, &headers, config);
-                if parsed_record.is_valid {
-                    valid_count += 1;
-                } else {
-                    invalid_count += 1;
-                }
-                records.push(parsed_record);
-            }
-            Err(e) => {
-                invalid_count += 1;
-                records.push(ParsedRecord {
-                    id: format!("record-{}", index),
-                    index,
-                    fields: vec![],
-                    raw: String::new(),
-                    record_type: "data".to_string(),
-                    is_valid: false,
-                    errors: Some(vec![e.to_string()]),
-                });
-            }
-        }
-    }
+    // Second pass: type each column from all of its values
+    let (mut records, types) = build_records(rows, &headers, rules, config);
+    let diagnostics = totals::check_records(&mut records, config)?;
+    let invalid_count = records.iter().filter(|r| !r.is_valid).count();
+    let valid_count = records.len() - invalid_count;
 
-    let end_time = get_time();
+    lines.locate(&mut records);
+    let end_time = clock::now();
 
     Ok(ParsedData {
-        id: format!("parsed-{}", js_sys::Date::now() as u64),
+        id: generate_id("parsed"),
         config: config.clone(),
         records,
         headers: Some(headers),
+        columns: Some(types.columns()),
         metadata: ParseMetadata {
             total_records: valid_count + invalid_count,
             valid_records: valid_count,
@@ -91,6 +57,7 @@ pub fn parse_csv(data: &str, config: &ParserConfig) -> Result<ParsedData, ParseE
             parse_time: end_time - start_time,
             file_size: Some(total_bytes),
             parser_engine: "wasm".to_string(),
+            diagnostics,
             ..Default::default()
         },
     })
@@ -105,96 +72,93 @@ pub fn parse_csv_with_progress<F>(
 where
     F: Fn(ParseProgress),
 {
-    let start_time = get_time();
+    parse_csv_with_control(data, config, progress_fn, &ParseController::new())
+}
+
+/// Parse CSV with progress callback, stopping early when `control` is cancelled
+pub fn parse_csv_with_control<F>(
+    data: &str,
+    config: &ParserConfig,
+    progress_fn: F,
+    control: &ParseController,
+) -> Result<ParsedData, ParseError>
+where
+    F: Fn(ParseProgress),
+{
+    let start_time = clock::now();
     let total_bytes = data.len();
 
     progress_fn(ParseProgress::new("initializing", 0, total_bytes, 0).with_message("Starting CSV parse..."));
 
-    let delimiter = config.delimiter.as_bytes().first().copied().unwrap_or(b',');
-    let quote = config.quote_char.as_bytes().first().copied().unwrap_or(b'"');
+    let rules = RowRules::from_config(config)?;
+    let lines = LineIndex::new(data);
+    let (headers, records) = read_rows(data, config, &lines)?;
 
-    let mut reader = ReaderBuilder::new()
-        .delimiter(delimiter)
-        .quote(quote)
-        .has_headers(config.has_header)
-        .flexible(true)
-        .from_reader(Cursor::new(data));
+    let mut tracker = ProgressTracker::started_at(start_time, total_bytes);
+    progress_fn(tracker.progress("parsing", 0, 0).with_message("Parsing records..."));
 
-    let headers: Vec<String> = if config.has_header {
-        reader
-            .headers()
-            .map(|h| h.iter().map(|s| s.trim().to_string()).collect())
-            .unwrap_or_default()
-    } else {
-        vec![]
-    };
-
-    progress_fn(ParseProgress::new("parsing", 0, total_bytes, 0).with_message("Parsing records..."));
-
-    // Re-create reader
-    let mut reader = ReaderBuilder::new()
-        .delimiter(delimiter)
-        .quote(quote)
-        .has_headers(config.has_header)
-        .flexible(true)
-        .from_reader(Cursor::new(data));
-
-    let mut records = Vec::new();
-    let mut valid_count = 0usize;
+    let mut rows: Vec<Row> = Vec::new();
     let mut invalid_count = 0usize;
-    let mut last_progress_update = 0usize;
-    let progress_interval = total_bytes / 100; // Update every 1%
-
-    for (index, result) in reader.records().enumerate() {
-        match result {
-            Ok(record) => {
-                let parsed_record = create_record(index, &record, &headers, config);
-                if parsed_record.is_valid {
-                    valid_count += 1;
-                } else {
-                    invalid_count += 1;
-                }
+    let mut bytes_processed = 0usize;
 
-                // Estimate bytes processed based on record position
-                let bytes_processed = ((index + 1) * total_bytes) / (records.len().max(index + 1) + 1);
+    for row in records {
+        if !control.checkpoint() {
+            progress_fn(
+                tracker
+                    .progress("cancelled", bytes_processed, rows.len())
+                    .with_message("Parsing cancelled"),
+            );
+            return Err(ParseError::Cancelled(Box::new(ParseMetadata {
+                total_records: rows.len(),
+                valid_records: rows.len() - invalid_count,
+                invalid_records: invalid_count,
+                parse_time: clock::now() - start_time,
+                file_size: Some(total_bytes),
+                parser_engine: "wasm".to_string(),
+                ..Default::default()
+            })));
+        }
 
-                if bytes_processed - last_progress_update > progress_interval {
-                    last_progress_update = bytes_processed;
-                    progress_fn(
-                        ParseProgress::new("parsing", bytes_processed, total_bytes, index + 1)
-                            .with_message(&format!("Parsed {} records...", index + 1)),
-                    );
-                }
+        if row.is_err() {
+            invalid_count += 1;
+        }
+        bytes_processed = row_end(&row).unwrap_or(bytes_processed);
+        rows.push(row);
 
-                records.push(parsed_record);
-            }
-            Err(e) => {
-                invalid_count += 1;
-                records.push(ParsedRecord {
-                    id: format!("record-{}", index),
-                    index,
-                    fields: vec![],
-                    raw: String::new(),
-                    record_type: "data".to_string(),
-                    is_valid: false,
-                    errors: Some(vec![e.to_string()]),
-                });
-            }
+        if tracker.due() {
+            progress_fn(
+                tracker
+                    .progress("parsing", bytes_processed, rows.len())
+                    .with_message(&format!("Parsed {} records...", rows.len())),
+            );
         }
     }
 
-    let end_time = get_time();
+    progress_fn(
+        tracker
+            .progress("finalizing", total_bytes, rows.len())
+            .with_message("Building records..."),
+    );
+    let (mut records, types) = build_records(rows, &headers, rules, config);
+    let diagnostics = totals::check_records(&mut records, config)?;
+    let invalid_count = records.iter().filter(|r| !r.is_valid).count();
+    let valid_count = records.len() - invalid_count;
+
+    lines.locate(&mut records);
+    let end_time = clock::now();
 
     progress_fn(
-        ParseProgress::new("complete", total_bytes, total_bytes, records.len())
+        tracker
+            .progress("complete", total_bytes, records.len())
             .with_message("Parsing complete"),
     );
 
     Ok(ParsedData {
-        id: format!("parsed-{}", js_sys::Date::now() as u64),
+        id: generate_id("parsed"),
         config: config.clone(),
         records,
         headers: Some(headers),
+        columns: Some(types.columns()),
         metadata: ParseMetadata {
             total_records: valid_count + invalid_count,
             valid_records: valid_count,
@@ -202,91 +166,849 @@ where
             parse_time: end_time - start_time,
             file_size: Some(total_bytes),
             parser_engine: "wasm".to_string(),
+            diagnostics,
             ..Default::default()
         },
     })
 }
 
-/// Create a parsed record from a CSV record
+/// CSV syntax options resolved from `ParserConfig`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Dialect {
+    pub(crate) delimiter: u8,
+    pub(crate) quote: u8,
+    pub(crate) escape: Option<u8>,
+    pub(crate) comment: Option<u8>,
+}
+
+impl Dialect {
+    pub(crate) fn from_config(config: &ParserConfig) -> Self {
+        let first_byte = |s: &str| s.as_bytes().first().copied();
+        let quote = first_byte(&config.quote_char).unwrap_or(b'"');
+        Self {
+            delimiter: first_byte(&config.delimiter).unwrap_or(b','),
+            quote,
+            // Escaping with the quote character itself is plain quote doubling
+            escape: first_byte(&config.escape_char).filter(|&escape| escape != quote),
+            comment: config.comment_char.as_deref().and_then(first_byte),
+        }
+    }
+
+    fn reader<'a>(&self, data: &'a str, has_headers: bool) -> csv::Reader<Cursor<&'a str>> {
+        ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .comment(self.comment)
+            .has_headers(has_headers)
+            .flexible(true) // Allow variable number of fields
+            .from_reader(Cursor::new(data))
+    }
+}
+
+/// Byte length of the first `rows` lines of `data`
+fn preamble_len(data: &[u8], rows: usize) -> usize {
+    if rows == 0 {
+        return 0;
+    }
+    memchr::memchr_iter(b'\n', data)
+        .nth(rows - 1)
+        .map_or(data.len(), |newline| newline + 1)
+}
+
+/// Whether header names are trimmed (`trim` is `headers` or `all`)
+pub(crate) fn trims_headers(config: &ParserConfig) -> bool {
+    matches!(config.trim.as_str(), "headers" | "all")
+}
+
+/// Whether text cell values are trimmed (`trim` is `fields` or `all`)
+fn trims_fields(config: &ParserConfig) -> bool {
+    matches!(config.trim.as_str(), "fields" | "all")
+}
+
+/// Column names from the fields of a header row
+///
+/// Empty names become `Column N` and repeated names get a `_1`, `_2`, ... suffix,
+/// as Papa Parse does, skipping names used by any real header; in `strict` mode either
+/// is an error.
+pub(crate) fn header_names<'a>(
+    fields: impl IntoIterator<Item = &'a str>,
+    config: &ParserConfig,
+) -> Result<Vec<String>, ParseError> {
+    let trim = trims_headers(config);
+    let fields: Vec<&str> = fields
+        .into_iter()
+        .map(|field| if trim { field.trim() } else { field })
+        .collect();
+    let real: HashSet<&str> = fields.iter().copied().filter(|n| !n.trim().is_empty()).collect();
+    let mut names: Vec<String> = Vec::new();
+    for (i, &name) in fields.iter().enumerate() {
+        let taken = names.iter().any(|n| n == name);
+        if config.strict && (name.trim().is_empty() || taken) {
+            let problem = if taken { "duplicate" } else { "empty" };
+            return Err(ParseError::CsvError(format!(
+                "{} header \"{}\" in column {}",
+                problem,
+                name,
+                i + 1
+            )));
+        }
+
+        let used = |candidate: &str| {
+            names.iter().any(|n| n == candidate) || real.contains(candidate) && candidate != name
+        };
+        let unique = if name.trim().is_empty() {
+            unused_name(&format!("Column {}", i + 1), used)
+        } else {
+            unused_name(name, used)
+        };
+        names.push(unique);
+    }
+    Ok(names)
+}
+
+/// `base`, or the first of `base_1`, `base_2`, ... for which `used` is false
+fn unused_name(base: &str, used: impl Fn(&str) -> bool) -> String {
+    std::iter::once(base.to_string())
+        .chain((1..).map(|n| format!("{}_{}", base, n)))
+        .find(|candidate| !used(candidate))
+        .unwrap_or_default()
+}
+
+/// `Column N` names for a headerless file whose first record has `count` fields
+pub(crate) fn numbered_headers(count: usize) -> Vec<String> {
+    (0..count).map(|i| format!("Column {}", i + 1)).collect()
+}
+
+/// First-pass rows in input order
+type Rows<'a> = Box<dyn Iterator<Item = Row> + 'a>;
+
+/// Column names and first-pass rows of the records after the `skip_rows` preamble
+fn read_rows<'a>(
+    data: &'a str,
+    config: &ParserConfig,
+    lines: &'a LineIndex,
+) -> Result<(Vec<String>, Rows<'a>), ParseError> {
+    let dialect = Dialect::from_config(config);
+    let body_start = preamble_len(data.as_bytes(), config.skip_rows);
+    let body = &data[body_start..];
+
+    let Some(delimiter) = Delimiter::from_config(config)? else {
+        let mut reader = dialect.reader(body, config.has_header);
+        let headers = if config.has_header {
+            match reader.headers() {
+                Ok(header) => header_names(header, config)?,
+                Err(_) => vec![],
+            }
+        } else {
+            match reader.records().next() {
+                Some(Ok(record)) => numbered_headers(record.len()),
+                _ => vec![],
+            }
+        };
+        let rows = dialect
+            .reader(body, config.has_header)
+            .into_records()
+            .enumerate()
+            .map(move |(index, result)| read_row(index, result, data, body_start, &dialect, lines));
+        return Ok((headers, Box::new(rows)));
+    };
+
+    let mut pos = body_start;
+    let mut records = std::iter::from_fn(move || {
+        let record = delimited::split_record(data, pos, true, &delimiter, &dialect)?;
+        pos = record.end;
+        Some(record)
+    })
+    .peekable();
+    let headers = if config.has_header {
+        match records.next() {
+            Some(record) => header_names(record.fields.iter().map(String::as_str), config)?,
+            None => vec![],
+        }
+    } else {
+        records
+            .peek()
+            .map(|record| numbered_headers(record.fields.len()))
+            .unwrap_or_default()
+    };
+    let rows = records.map(|record| {
+        let raw = source_text(data, &record.spans).to_string();
+        Ok((StringRecord::from(record.fields), record.spans, raw))
+    });
+    Ok((headers, Box::new(rows)))
+}
+
+/// A record read in the first pass: fields, their spans and the record's source text,
+/// or an invalid record
+pub(crate) type Row = Result<(StringRecord, Vec<(usize, usize)>, String), Box<ParsedRecord>>;
+
+/// First-pass row of a reader over `data[offset..]`
+fn read_row(
+    index: usize,
+    result: Result<StringRecord, csv::Error>,
+    data: &str,
+    offset: usize,
+    dialect: &Dialect,
+    lines: &LineIndex,
+) -> Row {
+    match result {
+        Ok(record) => {
+            let spans = match record.position() {
+                Some(position) => field_spans(
+                    data.as_bytes(),
+                    offset + position.byte() as usize,
+                    dialect,
+                    record.len(),
+                ),
+                None => Vec::new(),
+            };
+            let raw = source_text(data, &spans).to_string();
+            Ok((record, spans, raw))
+        }
+        Err(e) => Err(Box::new(error_record(index, &e, offset, lines))),
+    }
+}
+
+/// Byte offset in the input just past the last field of `row`
+fn row_end(row: &Row) -> Option<usize> {
+    match row {
+        Ok((_, spans, _)) => spans.last().map(|&(_, end)| end),
+        Err(record) => record.position.as_ref().map(|position| position.end),
+    }
+}
+
+/// Infer the column types of `rows` and build their records
+fn build_records(
+    rows: Vec<Row>,
+    headers: &[String],
+    rules: RowRules,
+    config: &ParserConfig,
+) -> (Vec<ParsedRecord>, RowTypes) {
+    let types = rules.infer(
+        rows.iter()
+            .filter_map(|row| row.as_ref().ok())
+            .map(|(record, _, raw)| (record, raw.as_str())),
+        headers,
+        config,
+    );
+    let records = rows
+        .into_iter()
+        .enumerate()
+        .map(|(index, row)| types.record(index, row, config))
+        .collect();
+    (records, types)
+}
+
+/// Compiled `row_rules`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RowRules {
+    rules: Vec<(CsvRowRule, Option<Regex>)>,
+}
+
+impl RowRules {
+    pub(crate) fn from_config(config: &ParserConfig) -> Result<Self, ParseError> {
+        let rules = config
+            .row_rules
+            .iter()
+            .flatten()
+            .map(|rule| {
+                if rule.value.is_none() && rule.pattern.is_none() {
+                    return Err(ParseError::ConfigError(format!(
+                        "row rule for \"{}\" rows needs a value or a pattern",
+                        rule.record_type
+                    )));
+                }
+                let pattern = rule.pattern.as_deref().map(Regex::new).transpose();
+                let pattern = pattern.map_err(|e| {
+                    ParseError::ConfigError(format!("invalid row rule pattern: {}", e))
+                })?;
+                Ok((rule.clone(), pattern))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Group of `record`, whose source text is `raw`: the index of the first matching
+    /// rule, or the number of rules for unmatched rows
+    fn group(&self, record: &StringRecord, raw: &str) -> usize {
+        if self.rules.is_empty() {
+            return 0;
+        }
+        self.rules
+            .iter()
+            .position(|(rule, pattern)| {
+                let value_matches = rule.value.as_deref().is_none_or(|value| {
+                    record.get(rule.column).map(str::trim) == Some(value.trim())
+                });
+                value_matches && pattern.as_ref().is_none_or(|p| p.is_match(raw))
+            })
+            .unwrap_or(self.rules.len())
+    }
+
+    /// Infer the column types of each kind of row from sample `records` and their
+    /// source text
+    pub(crate) fn infer<'a>(
+        self,
+        records: impl Iterator<Item = (&'a StringRecord, &'a str)> + Clone,
+        headers: &[String],
+        config: &ParserConfig,
+    ) -> RowTypes {
+        let groups: Vec<usize> = records
+            .clone()
+            .map(|(record, raw)| self.group(record, raw))
+            .collect();
+        let records = records.map(|(record, _)| record);
+        let groups = (0..=self.rules.len())
+            .map(|group| {
+                let rule = self.rules.get(group).map(|(rule, _)| rule);
+                let headers = rule
+                    .and_then(|rule| rule.columns.clone())
+                    .unwrap_or_else(|| headers.to_vec());
+                let rows = records
+                    .clone()
+                    .zip(&groups)
+                    .filter(|&(_, &g)| g == group)
+                    .map(|(record, _)| record);
+                RowGroup {
+                    record_type: rule.map_or("data", |rule| &rule.record_type).to_string(),
+                    count: rows.clone().count(),
+                    types: ColumnTypes::infer(rows, &headers, config),
+                    headers,
+                }
+            })
+            .collect();
+        RowTypes {
+            rules: self,
+            groups,
+        }
+    }
+}
+
+/// Record type, column names and column types of one kind of row
+#[derive(Debug, Clone)]
+struct RowGroup {
+    record_type: String,
+    headers: Vec<String>,
+    types: ColumnTypes,
+    /// Sample rows of this kind
+    count: usize,
+}
+
+/// Column types of each kind of row, from [`RowRules::infer`]
+#[derive(Debug, Clone)]
+pub(crate) struct RowTypes {
+    rules: RowRules,
+    /// One group per rule, then the unmatched rows
+    groups: Vec<RowGroup>,
+}
+
+impl RowTypes {
+    /// Record for a first-pass row
+    pub(crate) fn record(&self, index: usize, row: Row, config: &ParserConfig) -> ParsedRecord {
+        match row {
+            Ok((record, spans, raw)) => {
+                let group = &self.groups[self.rules.group(&record, &raw)];
+                create_record(index, &record, &spans, raw, group, config)
+            }
+            Err(record) => *record,
+        }
+    }
+
+    /// Column schema of the most common kind of row
+    pub(crate) fn columns(&self) -> Vec<ColumnSchema> {
+        self.groups
+            .iter()
+            .max_by_key(|group| group.count)
+            .map(|group| group.types.columns.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Source text of a record from the first to the last of its field `spans`
+pub(crate) fn source_text<'a>(data: &'a str, spans: &[(usize, usize)]) -> &'a str {
+    match (spans.first(), spans.last()) {
+        (Some(&(start, _)), Some(&(_, end))) => &data[start..end],
+        _ => "",
+    }
+}
+
+/// Name of column `index`
+fn column_name(headers: &[String], index: usize) -> String {
+    headers
+        .get(index)
+        .cloned()
+        .unwrap_or_else(|| {
+            let base = format!("Column {}", index + 1);
+            unused_name(&base, |candidate| headers.iter().any(|h| h == candidate))
+        })
+}
+
+/// Date format hint configured for column `name`
+fn column_format<'a>(config: &'a ParserConfig, name: &str) -> Option<&'a str> {
+    config
+        .column_formats
+        .as_ref()
+        .and_then(|formats| formats.get(name))
+        .map(String::as_str)
+}
+
+/// Create a parsed record from a CSV record, typing each cell by its column
+///
+/// `spans` are the source byte ranges of the fields (see [`field_spans`]).
 fn create_record(
     index: usize,
     record: &StringRecord,
-    headers: &[String],
+    spans: &[(usize, usize)],
+    raw: String,
+    group: &RowGroup,
     config: &ParserConfig,
 ) -> ParsedRecord {
+    let RowGroup { headers, types, .. } = group;
+    let mut diagnostics = Vec::new();
     let fields: Vec<ParsedField> = record
         .iter()
         .enumerate()
         .map(|(field_index, value)| {
-            let name = headers
-                .get(field_index)
-                .cloned()
-                .unwrap_or_else(|| format!("Column {}", field_index + 1));
+            let name = column_name(headers, field_index);
+            let id = format!("field-{}-{}", index, field_index);
+            let format = column_format(config, &name);
 
-            let (field_value, field_type) = infer_type(value);
+            let (field_value, field_type) = if !config.infer_types {
+                (FieldValue::String(value.trim().to_string()), "string".to_string())
+            } else if let Some(&kind) = types.kinds.get(field_index) {
+                let numbers = &types.numbers[field_index];
+                let field_value = convert(value, kind, format, numbers).unwrap_or_else(|| {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            DiagnosticCode::FieldConversion,
+                            format!(
+                                "Value \"{}\" in column \"{}\" is not a {}",
+                                value.trim(),
+                                name,
+                                kind.field_type()
+                            ),
+                        )
+                        .with_field(id.clone()),
+                    );
+                    FieldValue::String(value.trim().to_string())
+                });
+                (field_value, kind.field_type().to_string())
+            } else {
+                infer_type(value, format, NumberFormat::from_config(config))
+            };
+
+            // Numbers and dates are always read from trimmed text
+            let field_value = match field_value {
+                FieldValue::String(_) if !trims_fields(config) => {
+                    FieldValue::String(value.to_string())
+                }
+                field_value => field_value,
+            };
 
             ParsedField {
-                id: format!("field-{}-{}", index, field_index),
+                id,
                 name,
                 value: field_value,
                 field_type,
                 original_value: value.to_string(),
-                position: None,
+                position: spans
+                    .get(field_index)
+                    .map(|&(start, end)| Position::new(start, end)),
             }
         })
         .collect();
 
-    let raw = record.iter().collect::<Vec<_>>().join(&config.delimiter);
-    let record_type = if index == 0 && config.has_header {
-        "header"
-    } else {
-        "data"
+    let position = match (spans.first(), spans.last()) {
+        (Some(first), Some(last)) => Some(Position::new(first.0, last.1)),
+        _ => None,
     };
+    if config.strict && record.len() != headers.len() {
+        let mut diagnostic = Diagnostic::error(
+            DiagnosticCode::CsvFieldCount,
+            format!(
+                "Record has {} fields, expected {}",
+                record.len(),
+                headers.len()
+            ),
+        );
+        diagnostic.span = position.clone();
+        diagnostics.push(diagnostic);
+    }
 
-    ParsedRecord {
+    let mut parsed = ParsedRecord {
         id: format!("record-{}", index),
         index,
         fields,
         raw,
-        record_type: record_type.to_string(),
+        record_type: group.record_type.clone(),
         is_valid: true,
         errors: None,
-    }
+        position,
+        diagnostics: None,
+    };
+    parsed.add_diagnostics(diagnostics);
+    parsed
 }
 
-/// Infer type from string value
-fn infer_type(value: &str) -> (FieldValue, String) {
-    let trimmed = value.trim();
+/// Byte ranges of the `count` raw fields (quotes included) of the record starting at `start`
+///
+/// Blank and comment lines before the record are skipped.
+pub(crate) fn field_spans(
+    data: &[u8],
+    start: usize,
+    dialect: &Dialect,
+    count: usize,
+) -> Vec<(usize, usize)> {
+    let Dialect {
+        delimiter,
+        quote,
+        escape,
+        comment,
+    } = *dialect;
+    let mut spans = Vec::with_capacity(count);
+    let mut pos = start;
+    loop {
+        while pos < data.len() && (data[pos] == b'\r' || data[pos] == b'\n') {
+            pos += 1;
+        }
+        if comment.is_none() || data.get(pos).copied() != comment {
+            break;
+        }
+        pos = memchr::memchr(b'\n', &data[pos..]).map_or(data.len(), |newline| pos + newline);
+    }
 
-    if trimmed.is_empty() {
-        return (FieldValue::Null, "null".to_string());
+    while spans.len() < count {
+        let field_start = pos;
+        let mut in_quotes = false;
+        while pos < data.len() {
+            let byte = data[pos];
+            if in_quotes {
+                if Some(byte) == escape {
+                    pos += 1;
+                } else if byte == quote {
+                    if data.get(pos + 1) == Some(&quote) {
+                        pos += 1;
+                    } else {
+                        in_quotes = false;
+                    }
+                }
+            } else if byte == quote && pos == field_start {
+                in_quotes = true;
+            } else if byte == delimiter || byte == b'\n' || byte == b'\r' {
+                break;
+            }
+            pos += 1;
+        }
+        spans.push((field_start, pos));
+
+        if data.get(pos) == Some(&delimiter) {
+            pos += 1;
+        } else {
+            break;
+        }
+    }
+
+    // Fields the reader produced past the end of the line are empty
+    while spans.len() < count {
+        spans.push((pos, pos));
+    }
+    spans
+}
+
+/// Invalid record for a row the CSV reader rejected, `offset` bytes into the input
+fn error_record(
+    index: usize,
+    error: &csv::Error,
+    offset: usize,
+    lines: &LineIndex,
+) -> ParsedRecord {
+    let code = match error.kind() {
+        csv::ErrorKind::Utf8 { .. } => DiagnosticCode::CsvUtf8,
+        _ => DiagnosticCode::CsvSyntax,
+    };
+    let mut diagnostic = Diagnostic::error(code, error.to_string());
+    if let Some(position) = error.position() {
+        let offset = offset + position.byte() as usize;
+        let (line, column) = lines.line_column(offset);
+        diagnostic = diagnostic.at(line, column).with_span(offset, offset);
     }
 
-    // Check boolean
-    let lower = trimmed.to_lowercase();
-    if lower == "true" || lower == "yes" || lower == "1" {
-        return (FieldValue::Boolean(true), "boolean".to_string());
+    let mut record = ParsedRecord {
+        id: format!("record-{}", index),
+        index,
+        fields: vec![],
+        raw: String::new(),
+        record_type: "data".to_string(),
+        is_valid: false,
+        errors: None,
+        position: None,
+        diagnostics: None,
+    };
+    record.add_diagnostics(vec![diagnostic]);
+    record
+}
+
+/// Storage type of a cell or a whole column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColumnKind {
+    Null,
+    Boolean,
+    Integer,
+    Decimal,
+    Float,
+    Date,
+    /// Date-like text without a format to parse it (`01/02/2024`)
+    DateText,
+    Text,
+}
+
+impl ColumnKind {
+    fn field_type(self) -> &'static str {
+        match self {
+            ColumnKind::Null => "null",
+            ColumnKind::Boolean => "boolean",
+            ColumnKind::Integer | ColumnKind::Decimal | ColumnKind::Float => "number",
+            ColumnKind::Date | ColumnKind::DateText => "date",
+            ColumnKind::Text => "string",
+        }
     }
-    if lower == "false" || lower == "no" || lower == "0" {
-        return (FieldValue::Boolean(false), "boolean".to_string());
+}
+
+/// Minimum share of non-empty cells a type must match to be used for the column
+const MIN_CONFIDENCE: f64 = 0.9;
+
+/// Column types inferred from a set of rows
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ColumnTypes {
+    kinds: Vec<ColumnKind>,
+    numbers: Vec<NumberFormat>,
+    pub(crate) columns: Vec<ColumnSchema>,
+}
+
+impl ColumnTypes {
+    /// Pick one type per column: the most common cell type, widened across
+    /// integer/decimal/float, falling back to text below [`MIN_CONFIDENCE`]
+    ///
+    /// Number columns use the configured separators or, when unset, the decimal
+    /// mark their values reveal.
+    pub(crate) fn infer<'a>(
+        records: impl Iterator<Item = &'a StringRecord> + Clone,
+        headers: &[String],
+        config: &ParserConfig,
+    ) -> Self {
+        let width = records
+            .clone()
+            .map(|record| record.len())
+            .max()
+            .unwrap_or(0)
+            .max(headers.len());
+        let configured = NumberFormat::from_config(config);
+        let numbers: Vec<NumberFormat> = (0..width)
+            .map(|i| {
+                configured.unwrap_or_else(|| {
+                    NumberFormat::detect(records.clone().filter_map(|record| record.get(i)))
+                })
+            })
+            .collect();
+        let formats: Vec<Option<&str>> = (0..width)
+            .map(|i| column_format(config, &column_name(headers, i)))
+            .collect();
+
+        let mut counts: Vec<[usize; 8]> = vec![[0; 8]; width];
+        for record in records {
+            for (i, value) in record.iter().enumerate() {
+                counts[i][classify(value, formats[i], &numbers[i]) as usize] += 1;
+            }
+        }
+
+        let (kinds, columns) = counts
+            .iter()
+            .enumerate()
+            .map(|(i, counts)| {
+                let (kind, confidence) = if config.infer_types {
+                    column_kind(counts)
+                } else {
+                    (ColumnKind::Text, 1.0)
+                };
+                let total: usize = counts.iter().sum();
+                let nulls = counts[ColumnKind::Null as usize];
+                let schema = ColumnSchema {
+                    name: column_name(headers, i),
+                    field_type: kind.field_type().to_string(),
+                    confidence,
+                    null_ratio: if total > 0 {
+                        nulls as f64 / total as f64
+                    } else {
+                        0.0
+                    },
+                    decimal_separator: matches!(
+                        kind,
+                        ColumnKind::Integer | ColumnKind::Decimal | ColumnKind::Float
+                    )
+                    .then(|| numbers[i].decimal_separator.to_string()),
+                };
+                (kind, schema)
+            })
+            .unzip();
+        Self {
+            kinds,
+            numbers,
+            columns,
+        }
     }
+}
 
-    // Check integer
-    if let Ok(n) = trimmed.parse::<i64>() {
-        return (FieldValue::Integer(n), "number".to_string());
+/// Column type and confidence from the per-kind cell counts
+///
+/// Every value reads as text, so a column falling back to text has confidence 1.0.
+fn column_kind(counts: &[usize; 8]) -> (ColumnKind, f64) {
+    use ColumnKind::*;
+    let count = |kind: ColumnKind| counts[kind as usize];
+    let non_null: usize = counts.iter().sum::<usize>() - count(Null);
+    if non_null == 0 {
+        return (Null, 1.0);
     }
 
-    // Check float
-    if let Ok(n) = trimmed.parse::<f64>() {
-        return (FieldValue::Number(n), "number".to_string());
+    let number = if count(Float) > 0 {
+        Float
+    } else if count(Decimal) > 0 {
+        Decimal
+    } else {
+        Integer
+    };
+    let candidates = [
+        (number, count(Integer) + count(Decimal) + count(Float)),
+        (Date, count(Date)),
+        (DateText, count(Date) + count(DateText)),
+        (Boolean, count(Boolean)),
+    ];
+    let (kind, matched) = candidates.into_iter().fold((Text, 0), |best, candidate| {
+        if candidate.1 > best.1 {
+            candidate
+        } else {
+            best
+        }
+    });
+
+    let confidence = matched as f64 / non_null as f64;
+    if confidence >= MIN_CONFIDENCE {
+        (kind, confidence)
+    } else {
+        (Text, 1.0)
     }
+}
 
-    // Check date patterns
+/// Type of a single cell
+///
+/// `0`/`1` are numbers and zero-padded numbers (`007`) are text, so flag and
+/// identifier columns keep their meaning. Localized numbers (`1.234,56 €`) are
+/// read with the column's `numbers` format.
+fn classify(value: &str, format: Option<&str>, numbers: &NumberFormat) -> ColumnKind {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return ColumnKind::Null;
+    }
+    if format.is_some_and(|format| dates::parse_with_format(trimmed, format).is_some()) {
+        return ColumnKind::Date;
+    }
+    if matches!(
+        trimmed.to_lowercase().as_str(),
+        "true" | "false" | "yes" | "no"
+    ) {
+        return ColumnKind::Boolean;
+    }
+    if !has_leading_zeros(trimmed) {
+        if trimmed.parse::<i64>().is_ok() {
+            return ColumnKind::Integer;
+        }
+        if let Some(number) = numbers.parse(trimmed) {
+            return match number.to_i64() {
+                Some(_) => ColumnKind::Integer,
+                None => ColumnKind::Decimal,
+            };
+        }
+        if is_float(trimmed) {
+            return ColumnKind::Float;
+        }
+    }
+    if dates::parse_iso(trimmed).is_some() {
+        return ColumnKind::Date;
+    }
     if is_date_like(trimmed) {
-        return (FieldValue::String(trimmed.to_string()), "date".to_string());
+        return ColumnKind::DateText;
+    }
+    ColumnKind::Text
+}
+
+/// Value of a cell in a column of type `kind`, `None` when it does not fit
+fn convert(
+    value: &str,
+    kind: ColumnKind,
+    format: Option<&str>,
+    numbers: &NumberFormat,
+) -> Option<FieldValue> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Some(FieldValue::Null);
     }
+    let numeric = !has_leading_zeros(trimmed);
 
-    (FieldValue::String(trimmed.to_string()), "string".to_string())
+    match kind {
+        ColumnKind::Null | ColumnKind::DateText | ColumnKind::Text => {
+            Some(FieldValue::String(trimmed.to_string()))
+        }
+        ColumnKind::Boolean => match trimmed.to_lowercase().as_str() {
+            "true" | "yes" => Some(FieldValue::Boolean(true)),
+            "false" | "no" => Some(FieldValue::Boolean(false)),
+            _ => None,
+        },
+        _ if !numeric => None,
+        ColumnKind::Integer => trimmed
+            .parse()
+            .ok()
+            .or_else(|| numbers.parse(trimmed).and_then(|n| n.to_i64()))
+            .map(FieldValue::Integer),
+        ColumnKind::Decimal => numbers.parse(trimmed).map(FieldValue::Decimal),
+        ColumnKind::Float => Some(trimmed)
+            .filter(|t| is_float(t))
+            .and_then(|t| t.parse().ok())
+            .or_else(|| numbers.parse(trimmed).map(|n| n.to_f64()))
+            .map(FieldValue::Number),
+        ColumnKind::Date => format
+            .and_then(|format| dates::parse_with_format(trimmed, format))
+            .or_else(|| dates::parse_iso(trimmed)),
+    }
+}
+
+/// Infer type from a single string value, parsing dates with the `format` hint if any
+///
+/// Used for cells beyond the inferred columns (ragged rows); without configured
+/// `numbers`, the decimal mark is guessed from the value itself.
+fn infer_type(
+    value: &str,
+    format: Option<&str>,
+    numbers: Option<NumberFormat>,
+) -> (FieldValue, String) {
+    let numbers = numbers.unwrap_or_else(|| NumberFormat::detect([value]));
+    let kind = classify(value, format, &numbers);
+    let field_value = convert(value, kind, format, &numbers)
+        .unwrap_or_else(|| FieldValue::String(value.trim().to_string()));
+    (field_value, kind.field_type().to_string())
+}
+
+/// `007`, `-0012.5`: zero-padded numbers are identifiers, not quantities
+fn has_leading_zeros(value: &str) -> bool {
+    let unsigned = value.trim_start_matches(['+', '-']);
+    let int_part = unsigned.split(['.', ',']).next().unwrap_or("");
+    int_part.len() > 1 && int_part.starts_with('0') && int_part.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Scientific notation (`1.5e3`); plain decimals are read as exact decimals instead
+fn is_float(value: &str) -> bool {
+    value.contains(['e', 'E'])
+        && value.bytes().any(|b| b.is_ascii_digit())
+        && value.parse::<f64>().is_ok()
 }
 
 /// Check if value looks like a date
@@ -322,14 +1044,6 @@ fn is_date_like(value: &str) -> bool {
     false
 }
 
-/// Get current time in milliseconds
-fn get_time() -> f64 {
-    web_sys::window()
-        .and_then(|w| w.performance())
-        .map(|p| p.now())
-        .unwrap_or(0.0)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -344,12 +1058,137 @@ mod tests {
         assert_eq!(result.headers.unwrap().len(), 3);
     }
 
+    #[test]
+    fn test_progress_follows_reader_position() {
+        let data = "name,age\nAlice,30\nBob,25\n";
+        let updates = std::cell::RefCell::new(Vec::new());
+        parse_csv_with_progress(data, &ParserConfig::default(), |p| updates.borrow_mut().push(p))
+            .unwrap();
+        let updates = updates.into_inner();
+
+        // The first row is reported at once, later ones are throttled
+        let first_row = updates.iter().find(|p| p.records_processed == 1).unwrap();
+        assert_eq!(first_row.phase, "parsing");
+        assert_eq!(first_row.bytes_processed, "name,age\nAlice,30".len());
+        assert_eq!((first_row.current_chunk, first_row.total_chunks), (Some(1), Some(1)));
+
+        let last = updates.last().unwrap();
+        assert_eq!((last.phase.as_str(), last.percentage), ("complete", 100));
+        assert!(updates.windows(2).all(|w| w[0].bytes_processed <= w[1].bytes_processed));
+    }
+
     #[test]
     fn test_infer_types() {
-        assert!(matches!(infer_type("42").0, FieldValue::Integer(42)));
-        assert!(matches!(infer_type("3.14").0, FieldValue::Number(_)));
-        assert!(matches!(infer_type("true").0, FieldValue::Boolean(true)));
-        assert!(matches!(infer_type("").0, FieldValue::Null));
+        assert!(matches!(infer_type("42", None, None).0, FieldValue::Integer(42)));
+        assert!(matches!(
+            infer_type("1234567.89", None, None).0,
+            FieldValue::Decimal(d) if d.to_string() == "1234567.89"
+        ));
+        assert!(matches!(infer_type("1.5e3", None, None).0, FieldValue::Number(_)));
+        assert!(matches!(infer_type("true", None, None).0, FieldValue::Boolean(true)));
+        assert!(matches!(infer_type("", None, None).0, FieldValue::Null));
+    }
+
+    #[test]
+    fn test_column_types_are_stable() {
+        let data = "id,flag,amount,note\n007,1,10,x\n012,0,,y\n100,1,2.50,z";
+        let result = parse_csv(data, &ParserConfig::default()).unwrap();
+
+        let columns = result.columns.unwrap();
+        let types: Vec<&str> = columns.iter().map(|c| c.field_type.as_str()).collect();
+        assert_eq!(types, ["string", "number", "number", "string"]);
+        assert!((columns[2].null_ratio - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(columns[2].confidence, 1.0);
+
+        let json = serde_json::to_value(&result.records).unwrap();
+        assert_eq!(json[0]["fields"][0]["value"], "007");
+        assert_eq!(json[1]["fields"][1]["value"], 0);
+        // Whole amounts in a decimal column are decimals too
+        assert_eq!(json[0]["fields"][2]["value"], "10");
+        assert_eq!(json[1]["fields"][2]["value"], serde_json::Value::Null);
+        assert_eq!(json[2]["fields"][2]["value"], "2.50");
+    }
+
+    #[test]
+    fn test_localized_number_columns() {
+        let data = "Montant;Solde\n1.234,56;-1 234,56\n12,5;(7,00)\n1.000;3.000,00-";
+        let config = ParserConfig {
+            delimiter: ";".to_string(),
+            ..Default::default()
+        };
+        let result = parse_csv(data, &config).unwrap();
+
+        let columns = result.columns.unwrap();
+        assert_eq!(columns[0].decimal_separator.as_deref(), Some(","));
+        let json = serde_json::to_value(&result.records).unwrap();
+        assert_eq!(json[0]["fields"][0]["value"], "1234.56");
+        assert_eq!(json[0]["fields"][0]["originalValue"], "1.234,56");
+        // `1.000` is a thousand in a comma-decimal column
+        assert_eq!(json[2]["fields"][0]["value"], "1000");
+        assert_eq!(json[0]["fields"][1]["value"], "-1234.56");
+        assert_eq!(json[1]["fields"][1]["value"], "-7.00");
+        assert_eq!(json[2]["fields"][1]["value"], "-3000.00");
+
+        // Identifiers with letters are not amounts in a foreign currency
+        let result = parse_csv("ref\nTXN123\nABC456\nID-999", &ParserConfig::default()).unwrap();
+        assert_eq!(result.columns.unwrap()[0].field_type, "string");
+        let json = serde_json::to_value(&result.records).unwrap();
+        assert_eq!(json[1]["fields"][0]["value"], "ABC456");
+    }
+
+    #[test]
+    fn test_text_mode_and_outliers() {
+        let mut data = String::from("amount\n");
+        for i in 0..19 {
+            data.push_str(&format!("{}\n", i));
+        }
+        data.push_str("n/a\n");
+
+        let result = parse_csv(&data, &ParserConfig::default()).unwrap();
+        assert_eq!(result.columns.as_ref().unwrap()[0].field_type, "number");
+        let outlier = &result.records[19];
+        assert!(outlier.is_valid);
+        assert_eq!(outlier.fields[0].field_type, "number");
+        assert_eq!(
+            outlier.diagnostics.as_ref().unwrap()[0].severity,
+            Severity::Warning
+        );
+
+        let config = ParserConfig {
+            infer_types: false,
+            ..Default::default()
+        };
+        let result = parse_csv(&data, &config).unwrap();
+        assert!(matches!(&result.records[1].fields[0].value, FieldValue::String(s) if s == "1"));
+        assert_eq!(result.columns.unwrap()[0].field_type, "string");
+
+        // Neither numbers nor dates dominate a mixed column: it is text, all of it
+        let data = "mixed\n1\n2\n3\n2024-01-15\n2024-02-01\n2024-03-01\ntrue\n";
+        let result = parse_csv(data, &ParserConfig::default()).unwrap();
+        let column = &result.columns.unwrap()[0];
+        assert_eq!((column.field_type.as_str(), column.confidence), ("string", 1.0));
+        assert!(matches!(&result.records[0].fields[0].value, FieldValue::String(s) if s == "1"));
+    }
+
+    #[test]
+    fn test_dates_with_column_formats() {
+        assert!(matches!(infer_type("2024-01-15", None, None).0, FieldValue::Date(_)));
+        assert!(matches!(infer_type("15/01/2024", None, None).0, FieldValue::String(_)));
+
+        let data = "Booking Date,Created\n15/01/2024,2024-01-15T09:00:00+01:00";
+        let config = ParserConfig {
+            column_formats: Some(
+                [("Booking Date".to_string(), "DD/MM/YYYY".to_string())]
+                    .into_iter()
+                    .collect(),
+            ),
+            ..Default::default()
+        };
+        let result = parse_csv(data, &config).unwrap();
+        let json = serde_json::to_value(&result.records[0].fields).unwrap();
+        assert_eq!(json[0]["value"], "2024-01-15");
+        assert_eq!(json[0]["type"], "date");
+        assert_eq!(json[1]["value"], "2024-01-15T09:00:00+01:00");
     }
 
     #[test]
@@ -359,4 +1198,193 @@ mod tests {
         assert!(is_date_like("15-01-2024"));
         assert!(!is_date_like("not a date"));
     }
+
+    #[test]
+    fn test_field_positions() {
+        let data = "name,amount\r\n\"Smith, J\",42\r\nDoe,7";
+        let result = parse_csv(data, &ParserConfig::default()).unwrap();
+
+        let fields = &result.records[0].fields;
+        let name = fields[0].position.as_ref().unwrap();
+        assert_eq!(&data[name.start..name.end], "\"Smith, J\"");
+        let amount = fields[1].position.as_ref().unwrap();
+        assert_eq!(&data[amount.start..amount.end], "42");
+        assert_eq!((amount.line, amount.column), (Some(2), Some(12)));
+
+        let record = result.records[1].position.as_ref().unwrap();
+        assert_eq!(&data[record.start..record.end], "Doe,7");
+    }
+
+    #[test]
+    fn test_dialect_options() {
+        let data = "Export 2024-01-15\nname ,note\n# comment\n\"O\\\"Brien\", a b \n";
+        let config = ParserConfig {
+            comment_char: Some("#".to_string()),
+            skip_rows: 1,
+            trim: "headers".to_string(),
+            ..Default::default()
+        };
+        let result = parse_csv(data, &config).unwrap();
+
+        assert_eq!(result.headers.unwrap(), ["name", "note"]);
+        assert_eq!(result.records.len(), 1);
+        let fields = &result.records[0].fields;
+        assert!(matches!(&fields[0].value, FieldValue::String(s) if s == "O\"Brien"));
+        assert!(matches!(&fields[1].value, FieldValue::String(s) if s == " a b "));
+        let name = fields[0].position.as_ref().unwrap();
+        assert_eq!(&data[name.start..name.end], "\"O\\\"Brien\"");
+        assert_eq!(name.line, Some(4));
+
+        let untrimmed = ParserConfig {
+            trim: "none".to_string(),
+            ..config
+        };
+        let result = parse_csv(data, &untrimmed).unwrap();
+        assert_eq!(result.headers.unwrap()[0], "name ");
+    }
+
+    #[test]
+    fn test_multi_character_delimiter() {
+        let data = "id|~|name|~|amount\n1|~|\"Smith|~|J\"|~|10.50\n2|~|Doe|~|7\n";
+        let config = ParserConfig {
+            delimiter: "|~|".to_string(),
+            ..Default::default()
+        };
+        let result = parse_csv(data, &config).unwrap();
+
+        assert_eq!(result.headers.unwrap(), ["id", "name", "amount"]);
+        assert_eq!(result.records.len(), 2);
+        let name = &result.records[0].fields[1];
+        assert!(matches!(&name.value, FieldValue::String(s) if s == "Smith|~|J"));
+        let span = name.position.as_ref().unwrap();
+        assert_eq!(&data[span.start..span.end], "\"Smith|~|J\"");
+        assert_eq!((span.line, span.column), (Some(2), Some(5)));
+        assert_eq!(result.columns.unwrap()[2].field_type, "number");
+
+        let invalid = ParserConfig {
+            delimiter_pattern: Some("(".to_string()),
+            ..config
+        };
+        assert!(matches!(parse_csv(data, &invalid), Err(ParseError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_strict_field_counts_and_headers() {
+        let data = "id,name,amount\n1,Smith,10\n2,Doe\n3,Roe,7,extra\n";
+        let lenient = parse_csv(data, &ParserConfig::default()).unwrap();
+        assert_eq!(lenient.metadata.invalid_records, 0);
+
+        let strict = ParserConfig {
+            strict: true,
+            ..Default::default()
+        };
+        let result = parse_csv(data, &strict).unwrap();
+        assert_eq!(
+            (result.metadata.valid_records, result.metadata.invalid_records),
+            (1, 2)
+        );
+        let short = &result.records[1];
+        assert!(!short.is_valid);
+        assert_eq!(
+            short.errors.as_deref(),
+            Some(&["Record has 2 fields, expected 3".to_string()][..])
+        );
+        let diagnostic = &short.diagnostics.as_ref().unwrap()[0];
+        assert_eq!(diagnostic.code, DiagnosticCode::CsvFieldCount);
+
+        let data = "id,amount,,amount\n1,2,3,4\n";
+        let result = parse_csv(data, &ParserConfig::default()).unwrap();
+        assert_eq!(result.headers.unwrap(), ["id", "amount", "Column 3", "amount_1"]);
+        match parse_csv(data, &strict) {
+            Err(ParseError::CsvError(message)) => {
+                assert_eq!(message, "empty header \"\" in column 3")
+            }
+            other => panic!("expected a header error, got {:?}", other.map(|d| d.headers)),
+        }
+
+        // Generated names never shadow a real header, earlier or later
+        let data = "amount,,amount,Column 2,amount_1\n1,2,3,4,5\n";
+        let result = parse_csv(data, &ParserConfig::default()).unwrap();
+        assert_eq!(
+            result.headers.unwrap(),
+            ["amount", "Column 2_1", "amount_2", "Column 2", "amount_1"]
+        );
+        let names: Vec<&str> = result.records[0].fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["amount", "Column 2_1", "amount_2", "Column 2", "amount_1"]);
+    }
+
+    #[test]
+    fn test_header_detail_trailer_rules() {
+        let data = "H,20240115,ACME\nD,Smith,10.50\nD,Doe,7.25\nT,2,17.75\n";
+        let rule = |value: &str, record_type: &str, columns: &[&str]| CsvRowRule {
+            column: 0,
+            value: Some(value.to_string()),
+            pattern: None,
+            record_type: record_type.to_string(),
+            columns: Some(columns.iter().map(|c| c.to_string()).collect()),
+        };
+        let config = ParserConfig {
+            has_header: false,
+            row_rules: Some(vec![
+                rule("H", "header", &["Kind", "Date", "Sender"]),
+                rule("D", "transaction", &["Kind", "Name", "Amount"]),
+                CsvRowRule {
+                    value: None,
+                    pattern: Some("^T,".to_string()),
+                    ..rule("", "footer", &["Kind", "Count", "Total"])
+                },
+            ]),
+            ..Default::default()
+        };
+        let result = parse_csv(data, &config).unwrap();
+
+        let types: Vec<&str> = result.records.iter().map(|r| r.record_type.as_str()).collect();
+        assert_eq!(types, ["header", "transaction", "transaction", "footer"]);
+        assert_eq!(result.records[1].fields[2].name, "Amount");
+        assert_eq!(result.records[3].fields[1].name, "Count");
+        assert!(matches!(result.records[3].fields[1].value, FieldValue::Integer(2)));
+        let columns = result.columns.unwrap();
+        assert_eq!(columns[1].name, "Name");
+        assert_eq!(columns[2].field_type, "number");
+
+        let plain = parse_csv("name\nSmith", &ParserConfig::default()).unwrap();
+        assert_eq!(plain.records[0].record_type, "data");
+
+        // Patterns see the source line, quotes and all
+        let config = ParserConfig {
+            has_header: false,
+            row_rules: Some(vec![CsvRowRule {
+                value: None,
+                pattern: Some(r#"^"T","#.to_string()),
+                ..rule("", "footer", &["Kind", "Count"])
+            }]),
+            ..Default::default()
+        };
+        let result = parse_csv("D,\"Doe, J\"\nT,1\n\"T\",\"2\"\n", &config).unwrap();
+        let types: Vec<&str> = result.records.iter().map(|r| r.record_type.as_str()).collect();
+        assert_eq!(types, ["data", "data", "footer"]);
+        assert_eq!(result.records[0].raw, "D,\"Doe, J\"");
+        assert_eq!(result.records[2].raw, "\"T\",\"2\"");
+    }
+
+    #[test]
+    fn test_cancel_returns_partial_metadata() {
+        let data = "a,b\n1,2\n3,4\n5,6";
+        let control = ParseController::new();
+        let result = parse_csv_with_control(
+            data,
+            &ParserConfig::default(),
+            |progress| {
+                if progress.records_processed >= 1 {
+                    control.cancel();
+                }
+            },
+            &control,
+        );
+
+        match result {
+            Err(ParseError::Cancelled(metadata)) => assert!(metadata.total_records < 3),
+            other => panic!("expected cancellation, got {:?}", other.map(|d| d.records.len())),
+        }
+    }
 }
@@ -0,0 +1,219 @@
+//! Date and Datetime Values
+//!
+//! Dates are parsed into `chrono` values and serialized as ISO 8601 strings
+//! (`2024-01-15`, `2024-01-15T10:30:00+01:00`). Formats are either chrono
+//! `strftime` patterns (`%d.%m.%Y`) or the token patterns used in field
+//! definitions (`DD/MM/YYYY`, `YYMMDD`, `YYYY-MM-DDTHH:mm:ss`).
+
+use crate::types::FieldValue;
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Date and time of day, with the UTC offset when the source carried one
+///
+/// ISO 20022 `ISODateTime` values may be local (`2024-01-15T10:30:00`) or
+/// offset (`2024-01-15T10:30:00+01:00`); both are kept as written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub local: NaiveDateTime,
+    pub offset: Option<FixedOffset>,
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.offset {
+            Some(offset) => match offset.from_local_datetime(&self.local).single() {
+                Some(dt) => write!(f, "{}", dt.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+                None => write!(f, "{}", self.local.format("%Y-%m-%dT%H:%M:%S%.f")),
+            },
+            None => write!(f, "{}", self.local.format("%Y-%m-%dT%H:%M:%S%.f")),
+        }
+    }
+}
+
+impl FromStr for DateTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_iso(s) {
+            Some(FieldValue::DateTime(dt)) => Ok(dt),
+            _ => Err(format!("invalid ISO 8601 datetime \"{}\"", s)),
+        }
+    }
+}
+
+impl Serialize for DateTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DateTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// `serde(with)` helpers serializing a `NaiveDate` as `YYYY-MM-DD`
+pub mod iso_date {
+    use chrono::NaiveDate;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&date.format("%Y-%m-%d"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        NaiveDate::parse_from_str(&text, "%Y-%m-%d").map_err(serde::de::Error::custom)
+    }
+}
+
+/// Translate a token pattern (`DD/MM/YYYY`) into a chrono format; `%` patterns pass through
+pub fn to_strftime(format: &str) -> String {
+    if format.contains('%') {
+        return format.to_string();
+    }
+
+    // Longest tokens first so `YYYY` is not read as two `YY`
+    const TOKENS: &[(&str, &str)] = &[
+        ("YYYY", "%Y"),
+        ("SSS", "%3f"),
+        ("MMM", "%b"),
+        ("XXX", "%:z"),
+        ("YY", "%y"),
+        ("MM", "%m"),
+        ("DD", "%d"),
+        ("HH", "%H"),
+        ("mm", "%M"),
+        ("ss", "%S"),
+    ];
+
+    let mut result = String::with_capacity(format.len() * 2);
+    let mut rest = format;
+    'outer: while let Some(c) = rest.chars().next() {
+        for (token, spec) in TOKENS {
+            if let Some(after) = rest.strip_prefix(token) {
+                result.push_str(spec);
+                rest = after;
+                continue 'outer;
+            }
+        }
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    result
+}
+
+/// Parse `value` with a date or datetime `format`
+///
+/// Formats with a time of day yield `DateTime` (with the offset when the format
+/// has one), others yield `Date`.
+pub fn parse_with_format(value: &str, format: &str) -> Option<FieldValue> {
+    let value = value.trim();
+    let format = to_strftime(format);
+
+    if let Ok(dt) = chrono::DateTime::parse_from_str(value, &format) {
+        return Some(FieldValue::DateTime(DateTime {
+            local: dt.naive_local(),
+            offset: Some(*dt.offset()),
+        }));
+    }
+    if let Ok(local) = NaiveDateTime::parse_from_str(value, &format) {
+        return Some(FieldValue::DateTime(DateTime {
+            local,
+            offset: None,
+        }));
+    }
+    NaiveDate::parse_from_str(value, &format)
+        .ok()
+        .map(FieldValue::Date)
+}
+
+/// Parse an ISO 8601 date (`2024-01-15`) or datetime (`2024-01-15T10:30:00.5+01:00`)
+///
+/// A space is accepted in place of `T`, and `Z` as a zero offset.
+pub fn parse_iso(value: &str) -> Option<FieldValue> {
+    let value = value.trim();
+    if value.len() < 10 {
+        return None;
+    }
+    if value.len() == 10 {
+        return NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .map(FieldValue::Date);
+    }
+
+    let normalized = value.replacen(' ', "T", 1);
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(&normalized) {
+        return Some(FieldValue::DateTime(DateTime {
+            local: dt.naive_local(),
+            offset: Some(*dt.offset()),
+        }));
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(&normalized, format).ok())
+        .map(|local| {
+            FieldValue::DateTime(DateTime {
+                local,
+                offset: None,
+            })
+        })
+}
+
+/// Parse a SWIFT `YYMMDD` date (years 00-69 are 20xx, 70-99 are 19xx)
+pub fn parse_yymmdd(value: &str) -> Option<NaiveDate> {
+    if value.len() != 6 || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    NaiveDate::parse_from_str(value, "%y%m%d").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(value: &FieldValue) -> String {
+        serde_json::to_string(value).unwrap()
+    }
+
+    #[test]
+    fn test_formats_and_iso_serialization() {
+        assert_eq!(to_strftime("DD/MM/YYYY"), "%d/%m/%Y");
+        assert_eq!(to_strftime("YYMMDD"), "%y%m%d");
+        assert_eq!(to_strftime("%d.%m.%Y"), "%d.%m.%Y");
+
+        let date = parse_with_format("15/01/2024", "DD/MM/YYYY").unwrap();
+        assert_eq!(json(&date), "\"2024-01-15\"");
+        let local = parse_with_format("20240115 103000", "YYYYMMDD HHmmss").unwrap();
+        assert_eq!(json(&local), "\"2024-01-15T10:30:00\"");
+        assert!(parse_with_format("31/02/2024", "DD/MM/YYYY").is_none());
+    }
+
+    #[test]
+    fn test_iso_datetime_keeps_offset() {
+        let value = parse_iso("2024-01-15T10:30:00.123+01:00").unwrap();
+        assert_eq!(json(&value), "\"2024-01-15T10:30:00.123+01:00\"");
+        assert_eq!(
+            json(&parse_iso("2024-01-15T10:30:00Z").unwrap()),
+            "\"2024-01-15T10:30:00Z\""
+        );
+        assert_eq!(
+            json(&parse_iso("2024-01-15 10:30:00").unwrap()),
+            "\"2024-01-15T10:30:00\""
+        );
+        assert!(matches!(parse_iso("2024-01-15"), Some(FieldValue::Date(_))));
+        assert!(parse_iso("REF-2024-01").is_none());
+
+        assert_eq!(parse_yymmdd("240115"), NaiveDate::from_ymd_opt(2024, 1, 15));
+        assert_eq!(
+            parse_yymmdd("991231"),
+            NaiveDate::from_ymd_opt(1999, 12, 31)
+        );
+        assert_eq!(parse_yymmdd("241301"), None);
+    }
+}
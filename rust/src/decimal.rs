@@ -0,0 +1,322 @@
+//! Exact Decimal Numbers
+//!
+//! Monetary amounts are kept as decimal digits with a scale instead of `f64`, so
+//! `1234567.89` or an ISO 20022 `CtrlSum` survive parsing, summing and serialization
+//! unchanged. Precision is unbounded; trailing zeros written in the source are kept
+//! (`10.00` stays `10.00`) but do not affect comparisons.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Arbitrary-precision decimal: `coefficient * 10^-scale`
+#[derive(Debug, Clone)]
+pub struct Decimal {
+    negative: bool,
+    /// Coefficient digits (0-9), most significant first, without leading zeros
+    digits: Vec<u8>,
+    scale: u32,
+}
+
+impl Decimal {
+    /// Parse `[+-]digits[.digits]`, with `separator` as the decimal mark
+    ///
+    /// FIN amounts use `,` and may end with it (`1234,` means 1234).
+    pub fn parse_with_separator(text: &str, separator: char) -> Option<Self> {
+        let text = text.trim();
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+
+        let (int_part, frac_part) = match unsigned.split_once(separator) {
+            Some((int_part, frac_part)) => (int_part, frac_part),
+            None => (unsigned, ""),
+        };
+        if int_part.is_empty() && frac_part.is_empty() {
+            return None;
+        }
+        if !int_part
+            .bytes()
+            .chain(frac_part.bytes())
+            .all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+
+        let digits = int_part
+            .bytes()
+            .chain(frac_part.bytes())
+            .map(|b| b - b'0')
+            .collect();
+        Some(Self::from_parts(negative, digits, frac_part.len() as u32))
+    }
+
+    /// Build from unsigned decimal digits, a sign and implied decimal places
+    pub fn from_scaled(digits: &str, negative: bool, scale: u32) -> Option<Self> {
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Some(Self::from_parts(
+            negative,
+            digits.bytes().map(|b| b - b'0').collect(),
+            scale,
+        ))
+    }
+
+    fn from_parts(negative: bool, mut digits: Vec<u8>, scale: u32) -> Self {
+        let leading = digits.iter().take_while(|&&d| d == 0).count();
+        digits.drain(..leading);
+        Self {
+            negative: negative && !digits.is_empty(),
+            digits,
+            scale,
+        }
+    }
+
+    pub fn zero() -> Self {
+        Self::from_parts(false, Vec::new(), 0)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// Number of digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            negative: false,
+            ..self.clone()
+        }
+    }
+
+    /// Exact `i64` value of a decimal without decimal places
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.scale != 0 {
+            return None;
+        }
+        self.to_string().parse().ok()
+    }
+
+    /// Nearest `f64`, for display or charting only
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    /// Coefficient digits padded with trailing zeros to `scale`
+    fn rescaled(&self, scale: u32) -> Vec<u8> {
+        let mut digits = self.digits.clone();
+        digits.extend(std::iter::repeat_n(0, (scale - self.scale) as usize));
+        digits
+    }
+
+    /// Add two decimals of any sign
+    fn add_signed(&self, other: &Self, other_negative: bool) -> Self {
+        let scale = self.scale.max(other.scale);
+        let a = self.rescaled(scale);
+        let b = other.rescaled(scale);
+
+        if self.negative == other_negative {
+            return Self::from_parts(self.negative, add_digits(&a, &b), scale);
+        }
+        match compare_digits(&a, &b) {
+            Ordering::Less => Self::from_parts(other_negative, sub_digits(&b, &a), scale),
+            _ => Self::from_parts(self.negative, sub_digits(&a, &b), scale),
+        }
+    }
+}
+
+/// Sum of two digit strings (most significant first)
+fn add_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    let mut a = a.iter().rev();
+    let mut b = b.iter().rev();
+    loop {
+        let (x, y) = (a.next(), b.next());
+        if x.is_none() && y.is_none() {
+            break;
+        }
+        let sum = x.unwrap_or(&0) + y.unwrap_or(&0) + carry;
+        result.push(sum % 10);
+        carry = sum / 10;
+    }
+    if carry > 0 {
+        result.push(carry);
+    }
+    result.reverse();
+    result
+}
+
+/// Difference of two digit strings, `a >= b`
+fn sub_digits(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    let mut b = b.iter().rev();
+    for &x in a.iter().rev() {
+        let y = b.next().copied().unwrap_or(0) + borrow;
+        if x >= y {
+            result.push(x - y);
+            borrow = 0;
+        } else {
+            result.push(x + 10 - y);
+            borrow = 1;
+        }
+    }
+    result.reverse();
+    result
+}
+
+/// Compare digit strings of equal scale
+fn compare_digits(a: &[u8], b: &[u8]) -> Ordering {
+    let strip = |d: &[u8]| d.iter().skip_while(|&&x| x == 0).count();
+    strip(a)
+        .cmp(&strip(b))
+        .then_with(|| a[a.len() - strip(a)..].cmp(&b[b.len() - strip(b)..]))
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scale = self.scale as usize;
+        let mut digits: String = self.digits.iter().map(|d| char::from(b'0' + d)).collect();
+        if digits.len() <= scale {
+            digits = format!("{:0>width$}", digits, width = scale + 1);
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+        if scale == 0 {
+            write!(f, "{}", int_part)
+        } else {
+            write!(f, "{}.{}", int_part, frac_part)
+        }
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with_separator(s, '.').ok_or_else(|| format!("invalid decimal \"{}\"", s))
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (negative, _) => {
+                let scale = self.scale.max(other.scale);
+                let ordering = compare_digits(&self.rescaled(scale), &other.rescaled(scale));
+                if negative {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+        }
+    }
+}
+
+impl std::ops::Add for &Decimal {
+    type Output = Decimal;
+
+    fn add(self, other: &Decimal) -> Decimal {
+        self.add_signed(other, other.negative)
+    }
+}
+
+impl std::ops::Sub for &Decimal {
+    type Output = Decimal;
+
+    fn sub(self, other: &Decimal) -> Decimal {
+        self.add_signed(other, !other.negative && !other.is_zero())
+    }
+}
+
+impl std::iter::Sum for Decimal {
+    fn sum<I: Iterator<Item = Decimal>>(iter: I) -> Self {
+        iter.fold(Decimal::zero(), |total, value| &total + &value)
+    }
+}
+
+/// Serialized as a string so JS never sees a rounded number
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display_preserve_digits() {
+        assert_eq!(dec("1234567.89").to_string(), "1234567.89");
+        assert_eq!(dec("-0012.340").to_string(), "-12.340");
+        assert_eq!(dec("0.05").to_string(), "0.05");
+        assert_eq!(dec("-0.00").to_string(), "0.00");
+        assert_eq!(
+            Decimal::parse_with_separator("1234,56", ',')
+                .unwrap()
+                .to_string(),
+            "1234.56"
+        );
+        assert_eq!(
+            Decimal::parse_with_separator("1234,", ',')
+                .unwrap()
+                .to_string(),
+            "1234"
+        );
+        assert!("1e5".parse::<Decimal>().is_err());
+        assert!(".".parse::<Decimal>().is_err());
+    }
+
+    #[test]
+    fn test_exact_arithmetic() {
+        let total: Decimal = ["0.1", "0.2", "1234567.89"].iter().map(|s| dec(s)).sum();
+        assert_eq!(total.to_string(), "1234568.19");
+        assert_eq!((&dec("10.00") - &dec("25.5")).to_string(), "-15.50");
+        assert_eq!(&dec("-3") + &dec("3"), Decimal::zero());
+        assert_eq!(dec("1.10"), dec("1.1"));
+        assert!(dec("-2") < dec("-1.5"));
+    }
+}
@@ -0,0 +1,226 @@
+//! Multi-Character and Pattern Delimiters
+//!
+//! The `csv` reader only splits on a single byte. Feeds delimited by `||`, `|~|`,
+//! `; `, a non-ASCII character like `¦`, or runs of whitespace (`delimiterPattern:
+//! "\\s+"`) are split here instead, with the same quoting rules: a field starting
+//! with the quote character runs to the closing quote (doubled quotes and the
+//! escape character are unescaped), so delimiters and line breaks inside quotes
+//! are kept as data.
+
+use crate::csv_parser::Dialect;
+use crate::types::{ParseError, ParserConfig};
+use regex::Regex;
+
+/// Field separator the `csv` reader cannot handle
+#[derive(Debug, Clone)]
+pub(crate) enum Delimiter {
+    /// Literal text of more than one byte
+    Literal(String),
+    /// Regular expression, matched within a line
+    Pattern(Regex),
+}
+
+impl Delimiter {
+    /// Delimiter for `delimiter_pattern`, or a `delimiter` longer than one byte
+    pub(crate) fn from_config(config: &ParserConfig) -> Result<Option<Self>, ParseError> {
+        if let Some(pattern) = config.delimiter_pattern.as_deref() {
+            let regex = Regex::new(pattern).map_err(|e| {
+                ParseError::ConfigError(format!("invalid delimiterPattern \"{}\": {}", pattern, e))
+            })?;
+            if regex.is_match("") {
+                return Err(ParseError::ConfigError(format!(
+                    "delimiterPattern \"{}\" matches empty text",
+                    pattern
+                )));
+            }
+            return Ok(Some(Delimiter::Pattern(regex)));
+        }
+        Ok((config.delimiter.len() > 1).then(|| Delimiter::Literal(config.delimiter.clone())))
+    }
+
+    /// Byte range of the first delimiter in `line` at or after `from`
+    fn find(&self, line: &str, from: usize) -> Option<(usize, usize)> {
+        match self {
+            Delimiter::Literal(text) => line[from..]
+                .find(text.as_str())
+                .map(|start| (from + start, from + start + text.len())),
+            Delimiter::Pattern(regex) => regex.find_at(line, from).map(|m| (m.start(), m.end())),
+        }
+    }
+}
+
+/// A record split from the input
+#[derive(Debug)]
+pub(crate) struct SplitRecord {
+    pub(crate) fields: Vec<String>,
+    /// Byte ranges of the raw fields (quotes included)
+    pub(crate) spans: Vec<(usize, usize)>,
+    /// Offset just past the record's line break
+    pub(crate) end: usize,
+}
+
+/// Split the next record of `text` starting at `pos`, skipping blank and comment lines
+///
+/// Returns `None` at the end of the input, or when the record may continue past
+/// the end of `text` and `eof` is false.
+pub(crate) fn split_record(
+    text: &str,
+    mut pos: usize,
+    eof: bool,
+    delimiter: &Delimiter,
+    dialect: &Dialect,
+) -> Option<SplitRecord> {
+    let bytes = text.as_bytes();
+    loop {
+        match bytes.get(pos) {
+            None => return None,
+            Some(b'\r' | b'\n') => pos += 1,
+            Some(&byte) if Some(byte) == dialect.comment => {
+                pos += memchr::memchr(b'\n', &bytes[pos..])? + 1;
+            }
+            Some(_) => break,
+        }
+    }
+
+    let mut fields = Vec::new();
+    let mut spans = Vec::new();
+    loop {
+        let field_start = pos;
+        let mut value = Vec::new();
+        if bytes.get(pos) == Some(&dialect.quote) {
+            pos = read_quoted(bytes, pos + 1, eof, dialect, &mut value)?;
+        }
+
+        // Unquoted field, or text between a closing quote and the delimiter
+        let line_end = match memchr::memchr(b'\n', &bytes[pos..]) {
+            Some(newline) => pos + newline,
+            None if eof => bytes.len(),
+            None => return None,
+        };
+        let content_end = if line_end > pos && bytes[line_end - 1] == b'\r' {
+            line_end - 1
+        } else {
+            line_end
+        };
+        match delimiter.find(&text[..content_end], pos) {
+            Some((start, end)) => {
+                value.extend_from_slice(&bytes[pos..start]);
+                spans.push((field_start, start));
+                fields.push(String::from_utf8_lossy(&value).into_owned());
+                pos = end;
+            }
+            None => {
+                value.extend_from_slice(&bytes[pos..content_end]);
+                spans.push((field_start, content_end));
+                fields.push(String::from_utf8_lossy(&value).into_owned());
+                return Some(SplitRecord {
+                    fields,
+                    spans,
+                    end: (line_end + 1).min(bytes.len()),
+                });
+            }
+        }
+    }
+}
+
+/// Unescape a quoted field from just after its opening quote into `value`
+///
+/// Returns the offset after the closing quote; an unterminated field runs to the
+/// end of the input.
+fn read_quoted(
+    bytes: &[u8],
+    mut pos: usize,
+    eof: bool,
+    dialect: &Dialect,
+    value: &mut Vec<u8>,
+) -> Option<usize> {
+    loop {
+        let Some(&byte) = bytes.get(pos) else {
+            return eof.then_some(pos);
+        };
+        // The byte after an escape or a quote decides what it means
+        let next = bytes.get(pos + 1).copied();
+        if next.is_none() && !eof && (Some(byte) == dialect.escape || byte == dialect.quote) {
+            return None;
+        }
+        if Some(byte) == dialect.escape {
+            value.extend(next);
+            pos += 2;
+        } else if byte == dialect.quote {
+            if next != Some(dialect.quote) {
+                return Some(pos + 1);
+            }
+            value.push(byte);
+            pos += 2;
+        } else {
+            value.push(byte);
+            pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_all(text: &str, config: &ParserConfig) -> Vec<Vec<String>> {
+        let delimiter = Delimiter::from_config(config).unwrap().unwrap();
+        let dialect = Dialect::from_config(config);
+        let mut records = Vec::new();
+        let mut pos = 0;
+        while let Some(record) = split_record(text, pos, true, &delimiter, &dialect) {
+            pos = record.end;
+            records.push(record.fields);
+        }
+        records
+    }
+
+    #[test]
+    fn test_literal_delimiter_with_quotes() {
+        let config = ParserConfig {
+            delimiter: "|~|".to_string(),
+            ..Default::default()
+        };
+        let text = "a|~|b\r\n\"x|~|y\"|~|\"line\nbreak \"\"q\"\"\"|~|\n¦|~|";
+        assert_eq!(
+            split_all(text, &config),
+            vec![
+                vec!["a", "b"],
+                vec!["x|~|y", "line\nbreak \"q\"", ""],
+                vec!["¦", ""],
+            ]
+        );
+
+        let config = ParserConfig {
+            delimiter: "¦".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(split_all("1¦2¦3", &config), vec![vec!["1", "2", "3"]]);
+    }
+
+    #[test]
+    fn test_pattern_delimiter_and_partial_input() {
+        let config = ParserConfig {
+            delimiter_pattern: Some(r"[ \t]+".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            split_all("id   name\t\"J Smith\"\n2 Doe", &config),
+            vec![vec!["id", "name", "J Smith"], vec!["2", "Doe"]]
+        );
+
+        let delimiter = Delimiter::from_config(&config).unwrap().unwrap();
+        let dialect = Dialect::from_config(&config);
+        assert!(split_record("1 \"open", 0, false, &delimiter, &dialect).is_none());
+        assert!(split_record("1 2", 0, false, &delimiter, &dialect).is_none());
+
+        let empty = ParserConfig {
+            delimiter_pattern: Some(r"\s*".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Delimiter::from_config(&empty),
+            Err(ParseError::ConfigError(_))
+        ));
+    }
+}
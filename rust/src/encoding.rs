@@ -0,0 +1,531 @@
+//! Character Set Decoding
+//!
+//! Turns raw input bytes into text before parsing: detects and strips byte order marks,
+//! honours `ParserConfig.encoding`, and falls back to detection when no encoding is given.
+//! Supports UTF-8, UTF-16LE/BE, ISO-8859-1/15, Windows-1252 and EBCDIC (CP037/CP1047).
+//!
+//! Streams are detected from their first chunk; when that guessed UTF-8 and a later
+//! byte is not valid UTF-8, decoding carries on as Windows-1252 (an ASCII header
+//! followed by accented names is typical of Windows exports). UTF-8 declared by a
+//! byte order mark is never re-decoded; an invalid byte there is an error.
+
+use crate::types::ParseError;
+use std::collections::HashMap;
+
+/// Single-byte encodings, whose bytes can be recovered from decoded text
+const SINGLE_BYTE: [Encoding; 5] = [
+    Encoding::Iso8859_1,
+    Encoding::Iso8859_15,
+    Encoding::Windows1252,
+    Encoding::Cp037,
+    Encoding::Cp1047,
+];
+
+lazy_static::lazy_static! {
+    /// Byte each character of a single-byte encoding decodes from
+    static ref ENCODE_TABLE: HashMap<(Encoding, char), u8> = SINGLE_BYTE
+        .iter()
+        .flat_map(|&encoding| (0..=255u8).map(move |b| ((encoding, encoding.decode_byte(b)), b)))
+        .collect();
+}
+
+/// Supported character encodings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Iso8859_1,
+    Iso8859_15,
+    Windows1252,
+    Cp037,
+    Cp1047,
+}
+
+impl Encoding {
+    /// Resolve a charset label such as `"latin1"`, `"UTF-16BE"` or `"ibm-1047"`
+    pub fn from_label(label: &str) -> Option<Self> {
+        let normalized: String = label
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+
+        match normalized.as_str() {
+            "utf8" | "unicode11utf8" => Some(Encoding::Utf8),
+            "utf16" | "utf16le" | "unicode" => Some(Encoding::Utf16Le),
+            "utf16be" | "unicodefffe" => Some(Encoding::Utf16Be),
+            "iso88591" | "latin1" | "l1" | "ascii" | "usascii" => Some(Encoding::Iso8859_1),
+            "iso885915" | "latin9" | "l9" => Some(Encoding::Iso8859_15),
+            "windows1252" | "cp1252" | "win1252" => Some(Encoding::Windows1252),
+            "cp037" | "ibm037" | "ibm37" | "ebcdic" | "ebcdiccpus" => Some(Encoding::Cp037),
+            "cp1047" | "ibm1047" => Some(Encoding::Cp1047),
+            _ => None,
+        }
+    }
+
+    /// Canonical label recorded in `ParseMetadata.encoding`
+    pub fn label(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Iso8859_1 => "iso-8859-1",
+            Encoding::Iso8859_15 => "iso-8859-15",
+            Encoding::Windows1252 => "windows-1252",
+            Encoding::Cp037 => "cp037",
+            Encoding::Cp1047 => "cp1047",
+        }
+    }
+
+    /// Decode a single byte (single-byte encodings only)
+    fn decode_byte(self, byte: u8) -> char {
+        match self {
+            Encoding::Windows1252 if (0x80..0xA0).contains(&byte) => {
+                WINDOWS_1252_HIGH[(byte - 0x80) as usize]
+            }
+            Encoding::Iso8859_15 => match byte {
+                0xA4 => '\u{20AC}',
+                0xA6 => '\u{0160}',
+                0xA8 => '\u{0161}',
+                0xB4 => '\u{017D}',
+                0xB8 => '\u{017E}',
+                0xBC => '\u{0152}',
+                0xBD => '\u{0153}',
+                0xBE => '\u{0178}',
+                _ => byte as char,
+            },
+            Encoding::Cp037 | Encoding::Cp1047 => ebcdic_to_char(self, byte),
+            _ => byte as char,
+        }
+    }
+
+    /// Byte that decodes to `c`, for single-byte encodings
+    ///
+    /// Lets binary fields (e.g. COMP-3) be recovered from decoded text.
+    pub fn encode_char(self, c: char) -> Option<u8> {
+        ENCODE_TABLE.get(&(self, c)).copied()
+    }
+}
+
+/// Detect the encoding of raw input
+pub fn detect_encoding(bytes: &[u8]) -> Encoding {
+    if let Some((encoding, _)) = detect_bom(bytes) {
+        return encoding;
+    }
+
+    if let Some(encoding) = declared_xml_encoding(bytes) {
+        return encoding;
+    }
+
+    // UTF-16 without BOM: ASCII text leaves every other byte zero
+    let sample = &bytes[..bytes.len().min(4096)];
+    if sample.len() >= 4 {
+        let zeros_even = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+        let zeros_odd = sample
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count();
+        let half = sample.len() / 2;
+        if zeros_odd * 10 > half * 4 && zeros_even * 10 < half {
+            return Encoding::Utf16Le;
+        }
+        if zeros_even * 10 > half * 4 && zeros_odd * 10 < half {
+            return Encoding::Utf16Be;
+        }
+    }
+
+    if std::str::from_utf8(bytes).is_ok() || is_truncated_utf8(sample) {
+        return Encoding::Utf8;
+    }
+
+    // EBCDIC text uses 0x40 for spaces and has no ASCII spaces or line feeds
+    let ebcdic_spaces = sample.iter().filter(|&&b| b == 0x40).count();
+    let ascii_spaces = sample.iter().filter(|&&b| b == 0x20 || b == 0x0A).count();
+    if ebcdic_spaces > ascii_spaces * 4 && ebcdic_spaces * 20 > sample.len() {
+        return Encoding::Cp037;
+    }
+
+    Encoding::Windows1252
+}
+
+/// Decode bytes with the configured encoding (or detection), stripping any BOM
+///
+/// Returns the text and the encoding actually used.
+pub fn decode(bytes: &[u8], label: Option<&str>) -> Result<(String, Encoding), ParseError> {
+    let mut decoder = StreamDecoder::new(label)?;
+    let mut text = decoder.decode_chunk(bytes)?;
+    text.push_str(&decoder.finish()?);
+    let encoding = decoder.encoding.unwrap_or(Encoding::Utf8);
+    Ok((text, encoding))
+}
+
+/// `text` with NEL (U+0085, the EBCDIC line end) turned into `\n`, for the parsers
+/// that only split lines on `\n`
+pub fn nel_as_newline(text: String) -> String {
+    if text.contains('\u{85}') {
+        text.replace('\u{85}', "\n")
+    } else {
+        text
+    }
+}
+
+/// Incremental decoder carrying partial multi-byte sequences between chunks
+pub struct StreamDecoder {
+    requested: Option<Encoding>,
+    encoding: Option<Encoding>,
+    /// The input declared its encoding with a byte order mark
+    has_bom: bool,
+    pending: Vec<u8>,
+    offset: usize,
+}
+
+impl StreamDecoder {
+    /// Create a decoder for `label`, or one that detects the encoding from the first chunk
+    pub fn new(label: Option<&str>) -> Result<Self, ParseError> {
+        let requested = match label.map(str::trim).filter(|l| !l.is_empty()) {
+            Some(label) => Some(Encoding::from_label(label).ok_or_else(|| {
+                ParseError::EncodingError(format!("unsupported encoding \"{}\"", label))
+            })?),
+            None => None,
+        };
+
+        Ok(Self {
+            requested,
+            encoding: None,
+            has_bom: false,
+            pending: Vec::new(),
+            offset: 0,
+        })
+    }
+
+    /// Encoding in use, once the first chunk has been seen
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding
+    }
+
+    /// Decode the next chunk; incomplete trailing sequences are kept for the next call
+    pub fn decode_chunk(&mut self, chunk: &[u8]) -> Result<String, ParseError> {
+        self.pending.extend_from_slice(chunk);
+
+        if self.encoding.is_none() {
+            // Wait for enough bytes to recognise a BOM
+            if self.pending.len() < 4 && !chunk.is_empty() {
+                return Ok(String::new());
+            }
+            self.start();
+        }
+
+        self.decode_pending(false)
+    }
+
+    /// Decode whatever is left; fails on a truncated multi-byte sequence
+    pub fn finish(&mut self) -> Result<String, ParseError> {
+        if self.encoding.is_none() {
+            self.start();
+        }
+        self.decode_pending(true)
+    }
+
+    /// Pick the encoding and strip the BOM
+    fn start(&mut self) {
+        let bom = detect_bom(&self.pending);
+        let encoding = match (self.requested, bom) {
+            // A UTF-16 BOM settles the byte order even when "utf-16" was requested
+            (Some(Encoding::Utf16Le | Encoding::Utf16Be), Some((bom_encoding, _)))
+                if bom_encoding != Encoding::Utf8 =>
+            {
+                bom_encoding
+            }
+            (Some(requested), _) => requested,
+            (None, _) => detect_encoding(&self.pending),
+        };
+
+        if let Some((bom_encoding, len)) = bom {
+            if bom_encoding == encoding {
+                self.pending.drain(..len);
+                self.offset += len;
+                self.has_bom = true;
+            }
+        }
+        self.encoding = Some(encoding);
+    }
+
+    fn decode_pending(&mut self, is_last: bool) -> Result<String, ParseError> {
+        let encoding = self.encoding.unwrap_or(Encoding::Utf8);
+        let (text, consumed) = match encoding {
+            Encoding::Utf8 => match decode_utf8(&self.pending, is_last, self.offset) {
+                // Detected from ASCII so far: the invalid byte reveals Windows-1252.
+                // A BOM declared UTF-8, so there the byte is an error.
+                Err(ParseError::EncodingError(message)) if self.has_bom => {
+                    return Err(ParseError::EncodingError(format!(
+                        "{} after a UTF-8 byte order mark",
+                        message
+                    )));
+                }
+                Err(_) if self.requested.is_none() => {
+                    self.encoding = Some(Encoding::Windows1252);
+                    return self.decode_pending(is_last);
+                }
+                result => result?,
+            },
+            Encoding::Utf16Le | Encoding::Utf16Be => decode_utf16(
+                &self.pending,
+                encoding == Encoding::Utf16Be,
+                is_last,
+                self.offset,
+            )?,
+            _ => (
+                self.pending
+                    .iter()
+                    .map(|&b| encoding.decode_byte(b))
+                    .collect(),
+                self.pending.len(),
+            ),
+        };
+
+        self.pending.drain(..consumed);
+        self.offset += consumed;
+        Ok(text)
+    }
+}
+
+/// Byte order mark at the start of input, with its length
+fn detect_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        Some((Encoding::Utf8, 3))
+    } else if bytes.starts_with(&[0xFF, 0xFE]) {
+        Some((Encoding::Utf16Le, 2))
+    } else if bytes.starts_with(&[0xFE, 0xFF]) {
+        Some((Encoding::Utf16Be, 2))
+    } else {
+        None
+    }
+}
+
+/// Encoding named in an `<?xml ... encoding="..."?>` declaration
+fn declared_xml_encoding(bytes: &[u8]) -> Option<Encoding> {
+    if !bytes.starts_with(b"<?xml") {
+        return None;
+    }
+    let end = memchr::memmem::find(&bytes[..bytes.len().min(256)], b"?>")?;
+    let declaration = std::str::from_utf8(&bytes[..end]).ok()?;
+    let value = declaration.split("encoding=").nth(1)?;
+    let quote = value.chars().next()?;
+    let label = value[1..].split(quote).next()?;
+    Encoding::from_label(label)
+}
+
+/// True if the only UTF-8 error is a sequence cut off at the end of the sample
+fn is_truncated_utf8(sample: &[u8]) -> bool {
+    matches!(std::str::from_utf8(sample), Err(e) if e.error_len().is_none())
+}
+
+/// Decode UTF-8, returning the text and the number of bytes consumed
+fn decode_utf8(bytes: &[u8], is_last: bool, offset: usize) -> Result<(String, usize), ParseError> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok((text.to_string(), bytes.len())),
+        Err(e) if e.error_len().is_none() && !is_last => {
+            let valid = e.valid_up_to();
+            // Safe: validated above
+            Ok((
+                std::str::from_utf8(&bytes[..valid])
+                    .unwrap_or_default()
+                    .to_string(),
+                valid,
+            ))
+        }
+        Err(e) => Err(ParseError::EncodingError(format!(
+            "invalid UTF-8 at byte {}",
+            offset + e.valid_up_to()
+        ))),
+    }
+}
+
+/// Decode UTF-16, returning the text and the number of bytes consumed
+fn decode_utf16(
+    bytes: &[u8],
+    big_endian: bool,
+    is_last: bool,
+    offset: usize,
+) -> Result<(String, usize), ParseError> {
+    let mut usable = bytes.len() & !1;
+    if is_last && usable != bytes.len() {
+        return Err(ParseError::EncodingError(
+            "UTF-16 input has an odd number of bytes".to_string(),
+        ));
+    }
+
+    let unit = |i: usize| {
+        let pair = [bytes[i], bytes[i + 1]];
+        if big_endian {
+            u16::from_be_bytes(pair)
+        } else {
+            u16::from_le_bytes(pair)
+        }
+    };
+
+    // Keep a trailing high surrogate for the next chunk
+    if !is_last && usable >= 2 && (0xD800..0xDC00).contains(&unit(usable - 2)) {
+        usable -= 2;
+    }
+
+    let units = (0..usable).step_by(2).map(unit);
+    let mut text = String::with_capacity(usable / 2);
+    for (i, decoded) in char::decode_utf16(units).enumerate() {
+        match decoded {
+            Ok(c) => text.push(c),
+            Err(_) => {
+                return Err(ParseError::EncodingError(format!(
+                    "unpaired UTF-16 surrogate at byte {}",
+                    offset + i * 2
+                )))
+            }
+        }
+    }
+
+    Ok((text, usable))
+}
+
+/// Map an EBCDIC byte through the CP037 table, adjusted for CP1047
+fn ebcdic_to_char(encoding: Encoding, byte: u8) -> char {
+    let latin1 = if encoding == Encoding::Cp1047 {
+        match byte {
+            0x5F => 0x5E,
+            0xAD => 0x5B,
+            0xB0 => 0xAC,
+            0xBA => 0xDD,
+            0xBB => 0xA8,
+            0xBD => 0x5D,
+            _ => CP037[byte as usize],
+        }
+    } else {
+        CP037[byte as usize]
+    };
+
+    // NEL (0x15) stays U+0085 rather than becoming '\n', so binary fields holding
+    // 0x15 or 0x25 can be told apart; the fixed-width parser splits lines on it and
+    // the other parsers get it as '\n' through `nel_as_newline`
+    latin1 as char
+}
+
+/// Windows-1252 characters for bytes 0x80-0x9F (undefined bytes map to C1 controls)
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// CP037 byte -> ISO-8859-1 code point
+const CP037: [u8; 256] = [
+    0x00, 0x01, 0x02, 0x03, 0x9C, 0x09, 0x86, 0x7F, 0x97, 0x8D, 0x8E, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+    0x10, 0x11, 0x12, 0x13, 0x9D, 0x85, 0x08, 0x87, 0x18, 0x19, 0x92, 0x8F, 0x1C, 0x1D, 0x1E, 0x1F,
+    0x80, 0x81, 0x82, 0x83, 0x84, 0x0A, 0x17, 0x1B, 0x88, 0x89, 0x8A, 0x8B, 0x8C, 0x05, 0x06, 0x07,
+    0x90, 0x91, 0x16, 0x93, 0x94, 0x95, 0x96, 0x04, 0x98, 0x99, 0x9A, 0x9B, 0x14, 0x15, 0x9E, 0x1A,
+    0x20, 0xA0, 0xE2, 0xE4, 0xE0, 0xE1, 0xE3, 0xE5, 0xE7, 0xF1, 0xA2, 0x2E, 0x3C, 0x28, 0x2B, 0x7C,
+    0x26, 0xE9, 0xEA, 0xEB, 0xE8, 0xED, 0xEE, 0xEF, 0xEC, 0xDF, 0x21, 0x24, 0x2A, 0x29, 0x3B, 0xAC,
+    0x2D, 0x2F, 0xC2, 0xC4, 0xC0, 0xC1, 0xC3, 0xC5, 0xC7, 0xD1, 0xA6, 0x2C, 0x25, 0x5F, 0x3E, 0x3F,
+    0xF8, 0xC9, 0xCA, 0xCB, 0xC8, 0xCD, 0xCE, 0xCF, 0xCC, 0x60, 0x3A, 0x23, 0x40, 0x27, 0x3D, 0x22,
+    0xD8, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0xAB, 0xBB, 0xF0, 0xFD, 0xFE, 0xB1,
+    0xB0, 0x6A, 0x6B, 0x6C, 0x6D, 0x6E, 0x6F, 0x70, 0x71, 0x72, 0xAA, 0xBA, 0xE6, 0xB8, 0xC6, 0xA4,
+    0xB5, 0x7E, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0xA1, 0xBF, 0xD0, 0xDD, 0xDE, 0xAE,
+    0x5E, 0xA3, 0xA5, 0xB7, 0xA9, 0xA7, 0xB6, 0xBC, 0xBD, 0xBE, 0x5B, 0x5D, 0xAF, 0xA8, 0xB4, 0xD7,
+    0x7B, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0xAD, 0xF4, 0xF6, 0xF2, 0xF3, 0xF5,
+    0x7D, 0x4A, 0x4B, 0x4C, 0x4D, 0x4E, 0x4F, 0x50, 0x51, 0x52, 0xB9, 0xFB, 0xFC, 0xF9, 0xFA, 0xFF,
+    0x5C, 0xF7, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0xB2, 0xD4, 0xD6, 0xD2, 0xD3, 0xD5,
+    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0xB3, 0xDB, 0xDC, 0xD9, 0xDA, 0x9F,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bom_detection_and_stripping() {
+        let (text, encoding) = decode(b"\xEF\xBB\xBFa,b", None).unwrap();
+        assert_eq!(encoding, Encoding::Utf8);
+        assert_eq!(text, "a,b");
+
+        let (text, encoding) = decode(&[0xFF, 0xFE, b'h', 0, b'i', 0], Some("utf-16")).unwrap();
+        assert_eq!(encoding, Encoding::Utf16Le);
+        assert_eq!(text, "hi");
+    }
+
+    #[test]
+    fn test_single_byte_encodings() {
+        let bytes = b"Soci\xE9t\xE9 \x80 100";
+        assert_eq!(detect_encoding(bytes), Encoding::Windows1252);
+        assert_eq!(decode(bytes, None).unwrap().0, "Société € 100");
+        assert_eq!(decode(b"\xA4", Some("latin9")).unwrap().0, "€");
+        assert_eq!(decode(b"\xA4", Some("ISO-8859-1")).unwrap().0, "¤");
+    }
+
+    #[test]
+    fn test_ebcdic() {
+        // "HELLO WORLD" + NEL in CP037
+        let bytes = [
+            0xC8, 0xC5, 0xD3, 0xD3, 0xD6, 0x40, 0xE6, 0xD6, 0xD9, 0xD3, 0xC4, 0x15,
+        ];
+        let (text, encoding) = decode(&bytes, None).unwrap();
+        assert_eq!(encoding, Encoding::Cp037);
+        assert_eq!(text, "HELLO WORLD\u{85}");
+        assert_eq!(decode(&[0xAD, 0xBD], Some("cp1047")).unwrap().0, "[]");
+        assert_eq!(Encoding::Cp037.encode_char('{'), Some(0xC0));
+        assert_eq!(Encoding::Cp037.encode_char('\u{85}'), Some(0x15));
+        assert_eq!(Encoding::Utf8.encode_char('a'), None);
+    }
+
+    #[test]
+    fn test_stream_decoder_split_sequences() {
+        let text = "é€𝄞";
+        let mut utf16 = vec![0xFE, 0xFF];
+        utf16.extend(text.encode_utf16().flat_map(|u| u.to_be_bytes()));
+
+        for (bytes, label) in [(text.as_bytes().to_vec(), "utf-8"), (utf16, "utf-16")] {
+            let mut decoder = StreamDecoder::new(Some(label)).unwrap();
+            let mut decoded = String::new();
+            for chunk in bytes.chunks(1) {
+                decoded.push_str(&decoder.decode_chunk(chunk).unwrap());
+            }
+            decoded.push_str(&decoder.finish().unwrap());
+            assert_eq!(decoded, text, "{}", label);
+        }
+    }
+
+    #[test]
+    fn test_stream_detection_falls_back_to_windows_1252() {
+        let mut decoder = StreamDecoder::new(None).unwrap();
+        let mut decoded = decoder.decode_chunk(b"Name;Ville\n").unwrap();
+        assert_eq!(decoder.encoding(), Some(Encoding::Utf8));
+        decoded.push_str(&decoder.decode_chunk(b"Soci\xE9t\xE9;Orl\xE9ans\n").unwrap());
+        decoded.push_str(&decoder.finish().unwrap());
+        assert_eq!(decoded, "Name;Ville\nSociété;Orléans\n");
+        assert_eq!(decoder.encoding(), Some(Encoding::Windows1252));
+
+        // A configured UTF-8 stays strict
+        let mut decoder = StreamDecoder::new(Some("utf-8")).unwrap();
+        decoder.decode_chunk(b"Name;Ville\n").unwrap();
+        assert!(matches!(
+            decoder.decode_chunk(b"Soci\xE9t\xE9\n"),
+            Err(ParseError::EncodingError(_))
+        ));
+
+        // So does UTF-8 declared by a byte order mark
+        match decode(b"\xEF\xBB\xBFName\nSoci\xE9t\xE9\n", None) {
+            Err(ParseError::EncodingError(message)) => {
+                assert_eq!(message, "invalid UTF-8 at byte 12 after a UTF-8 byte order mark")
+            }
+            other => panic!("expected an encoding error, got {:?}", other),
+        }
+        let mut decoder = StreamDecoder::new(None).unwrap();
+        decoder.decode_chunk(b"\xEF\xBB\xBFName;Ville\n").unwrap();
+        assert!(matches!(
+            decoder.decode_chunk(b"Soci\xE9t\xE9\n"),
+            Err(ParseError::EncodingError(_))
+        ));
+    }
+}
@@ -1,13 +1,36 @@
 //! SWIFT FIN Message Parser
 //!
 //! High-performance parser for SWIFT MT messages (MT103, MT202, MT940, etc.)
+//!
+//! Block 4 is split by a tokenizer rather than a regex: a field starts only where
+//! a line begins with `:tag:`, so values may contain colons (times, URLs in fields
+//! 70/72) and span several lines, which are kept line by line.
+//!
+//! Files exported from SWIFT Alliance wrap their messages in an [`Envelope`]: RJE
+//! separates them with `$`, DOS-PCC frames each one with 0x01 ... 0x03 and pads it
+//! to 512-byte sectors. The envelope is detected and split off before the blocks of
+//! each message are scanned.
+//!
+//! Each block 4 field is checked against its SWIFT format (see [`swift_format`]);
+//! a mismatch invalidates the record with a `FIN_FORMAT` error.
+//!
+//! Composite fields such as 32A or 61 and the party fields 50-59 are followed by
+//! their named sub-fields (value date, amount, account, BIC, name, ...), each with
+//! its own type and position (see `fin_subfields`).
 
+use crate::clock;
+use crate::dates;
+use crate::decimal::Decimal;
+use crate::fin_subfields;
+use crate::swift_format;
 use crate::types::*;
+use crate::utils::{generate_id, LineIndex};
 use regex::Regex;
 use std::collections::HashMap;
+use std::ops::Range;
 
-/// SWIFT field tag mappings
 lazy_static::lazy_static! {
+    /// SWIFT field tag mappings
     static ref FIN_FIELD_NAMES: HashMap<&'static str, &'static str> = {
         let mut m = HashMap::new();
         m.insert("20", "Transaction Reference Number");
@@ -63,200 +86,412 @@ lazy_static::lazy_static! {
         m
     };
 
-    static ref BLOCK_REGEX: Regex = Regex::new(r"\{(\d):([^}]*)\}").unwrap();
-    static ref FIELD_REGEX: Regex = Regex::new(r":(\d{2}[A-Z]?):([^:]+?)(?=:\d{2}[A-Z]?:|$)").unwrap();
+    static ref FIN_AMOUNT_REGEX: Regex = Regex::new(r"(\d+,\d*)$").unwrap();
 }
 
-/// Parse SWIFT FIN message
+/// Parse SWIFT FIN messages; a file may hold any number of them back to back
 pub fn parse_fin(data: &str, config: &ParserConfig) -> Result<ParsedData, ParseError> {
-    let start_time = get_time();
+    let start_time = clock::now();
     let total_bytes = data.len();
 
-    let mut records = Vec::new();
-    let mut headers = std::collections::HashSet::new();
-    let mut record_index = 0usize;
-
-    // Parse SWIFT blocks
-    let blocks = parse_blocks(data);
+    let lines = LineIndex::new(data);
+    let mut messages = Vec::new();
+    for segment in Envelope::detect(data).segments(data) {
+        messages.extend(split_messages(data, segment, &lines)?);
+    }
+    if messages.is_empty() {
+        return Err(ParseError::Located(Box::new(
+            Diagnostic::error(DiagnosticCode::FinBlock, "No SWIFT blocks found").at(1, 1),
+        )));
+    }
 
-    // Block 1: Basic Header
-    if let Some(block1) = blocks.get("1") {
-        let fields = parse_block1(block1);
-        for field in &fields {
-            headers.insert(field.name.clone());
-        }
-        records.push(ParsedRecord {
-            id: format!("record-{}", record_index),
-            index: record_index,
-            fields,
-            raw: format!("{{1:{}}}", block1),
-            record_type: "header".to_string(),
-            is_valid: true,
-            errors: None,
+    let mut records = Vec::new();
+    let mut summaries = Vec::new();
+    for (message_index, message) in messages.iter().enumerate() {
+        let first_record = records.len();
+        records.extend(message_records(message, first_record, &lines));
+        summaries.push(MessageSummary {
+            index: message_index,
+            message_type: message.message_type(),
+            first_record,
+            record_count: records.len() - first_record,
+            position: Some(Position::new(message.span.0, message.span.1)),
         });
-        record_index += 1;
     }
 
-    // Block 2: Application Header
-    if let Some(block2) = blocks.get("2") {
-        let fields = parse_block2(block2);
-        for field in &fields {
-            headers.insert(field.name.clone());
+    let mut headers = std::collections::HashSet::new();
+    for field in records.iter().flat_map(|r| &r.fields) {
+        headers.insert(field.name.clone());
+    }
+    let invalid_count = records.iter().filter(|r| !r.is_valid).count();
+    let record_count = records.len();
+
+    lines.locate(&mut records);
+    for summary in &mut summaries {
+        if let Some(position) = &mut summary.position {
+            let (line, column) = lines.line_column(position.start);
+            position.line = Some(line);
+            position.column = Some(column);
         }
-        records.push(ParsedRecord {
-            id: format!("record-{}", record_index),
-            index: record_index,
-            fields,
-            raw: format!("{{2:{}}}", block2),
-            record_type: "header".to_string(),
-            is_valid: true,
-            errors: None,
-        });
-        record_index += 1;
     }
+    let end_time = clock::now();
+
+    Ok(ParsedData {
+        id: generate_id("parsed"),
+        config: config.clone(),
+        records,
+        headers: Some(headers.into_iter().collect()),
+        columns: None,
+        metadata: ParseMetadata {
+            total_records: record_count,
+            valid_records: record_count - invalid_count,
+            invalid_records: invalid_count,
+            parse_time: end_time - start_time,
+            file_size: Some(total_bytes),
+            parser_engine: "wasm".to_string(),
+            messages: Some(summaries),
+            ..Default::default()
+        },
+    })
+}
 
-    // Block 3: User Header (optional)
-    if let Some(block3) = blocks.get("3") {
-        let fields = parse_block3(block3);
-        for field in &fields {
-            headers.insert(field.name.clone());
+/// Header, transaction and footer records of one message, numbered from `first_index`
+fn message_records(message: &Message, first_index: usize, lines: &LineIndex) -> Vec<ParsedRecord> {
+    let mut records = Vec::new();
+    let mut record_index = first_index;
+
+    // Blocks 1-3: Basic, Application and (optional) User Header
+    for id in ["1", "2", "3"] {
+        if let Some(block) = message.block(id) {
+            let fields = match id {
+                "1" => parse_block1(block.content, block.offset),
+                "2" => parse_block2(block.content, block.offset),
+                _ => parse_block3(block.content, block.offset),
+            };
+            records.push(block_record(record_index, id, block, fields, "header"));
+            record_index += 1;
         }
-        records.push(ParsedRecord {
-            id: format!("record-{}", record_index),
-            index: record_index,
-            fields,
-            raw: format!("{{3:{}}}", block3),
-            record_type: "header".to_string(),
-            is_valid: true,
-            errors: None,
-        });
-        record_index += 1;
     }
 
     // Block 4: Text Block (Message Content)
-    if let Some(block4) = blocks.get("4") {
-        let message_fields = parse_block4(block4);
+    if let Some(block4) = message.block("4") {
+        let message_type = message.message_type();
+        let message_fields =
+            parse_block4(block4.content, block4.offset, message_type.as_deref(), lines);
+        let has_tags = message_fields.iter().any(|group| !group.fields.is_empty());
 
-        for field_group in message_fields {
-            for field in &field_group {
-                headers.insert(field.name.clone());
-            }
-            records.push(ParsedRecord {
+        for FieldGroup {
+            fields: field_group,
+            diagnostics,
+        } in message_fields
+        {
+            let position = match (field_group.first(), field_group.last()) {
+                (Some(first), Some(last)) => first
+                    .position
+                    .as_ref()
+                    .zip(last.position.as_ref())
+                    .map(|(first, last)| Position::new(first.start, last.end)),
+                _ => Some(block4.position()),
+            };
+            let mut record = ParsedRecord {
                 id: format!("record-{}", record_index),
                 index: record_index,
                 fields: field_group,
-                raw: block4.clone(),
+                raw: block4.content.to_string(),
                 record_type: "transaction".to_string(),
                 is_valid: true,
                 errors: None,
-            });
+                position,
+                diagnostics: None,
+            };
+            record.add_diagnostics(diagnostics);
+            if !has_tags {
+                let (start, _) = block4.span;
+                let (line, column) = lines.line_column(start);
+                record.add_diagnostics(vec![Diagnostic::error(
+                    DiagnosticCode::FinField,
+                    "Text block (block 4) contains no field tags",
+                )
+                .at(line, column)
+                .with_span(start, start + 3)]);
+            }
+            records.push(record);
             record_index += 1;
         }
     }
 
     // Block 5: Trailer
-    if let Some(block5) = blocks.get("5") {
-        let fields = parse_block5(block5);
-        for field in &fields {
-            headers.insert(field.name.clone());
-        }
-        records.push(ParsedRecord {
-            id: format!("record-{}", record_index),
-            index: record_index,
-            fields,
-            raw: format!("{{5:{}}}", block5),
-            record_type: "footer".to_string(),
-            is_valid: true,
-            errors: None,
-        });
+    if let Some(block5) = message.block("5") {
+        let fields = parse_block5(block5.content, block5.offset);
+        records.push(block_record(record_index, "5", block5, fields, "footer"));
     }
 
-    let end_time = get_time();
+    records
+}
 
-    Ok(ParsedData {
-        id: format!("parsed-{}", js_sys::Date::now() as u64),
-        config: config.clone(),
-        records,
-        headers: Some(headers.into_iter().collect()),
-        metadata: ParseMetadata {
-            total_records: record_index,
-            valid_records: record_index,
-            invalid_records: 0,
-            parse_time: end_time - start_time,
-            file_size: Some(total_bytes),
-            parser_engine: "wasm".to_string(),
-            ..Default::default()
-        },
-    })
+/// Record for a header or trailer block
+fn block_record(
+    index: usize,
+    id: &str,
+    block: &Block,
+    fields: Vec<ParsedField>,
+    record_type: &str,
+) -> ParsedRecord {
+    ParsedRecord {
+        id: format!("record-{}", index),
+        index,
+        fields,
+        raw: format!("{{{}:{}}}", id, block.content),
+        record_type: record_type.to_string(),
+        is_valid: true,
+        errors: None,
+        position: Some(block.position()),
+        diagnostics: None,
+    }
 }
 
-/// Parse SWIFT blocks from raw message
-fn parse_blocks(data: &str) -> HashMap<String, String> {
-    let mut blocks = HashMap::new();
+/// A `{n:...}` block located in the raw message
+struct Block<'a> {
+    content: &'a str,
+    /// Byte offset of `content` in the message
+    offset: usize,
+    /// Byte span of the whole block, braces included
+    span: (usize, usize),
+}
 
-    for cap in BLOCK_REGEX.captures_iter(data) {
-        let block_num = cap.get(1).map(|m| m.as_str()).unwrap_or("");
-        let block_content = cap.get(2).map(|m| m.as_str()).unwrap_or("");
-        blocks.insert(block_num.to_string(), block_content.to_string());
+impl Block<'_> {
+    fn position(&self) -> Position {
+        Position::new(self.span.0, self.span.1)
     }
+}
 
-    // Handle Block 4 which may span multiple lines with different format
-    if !blocks.contains_key("4") {
-        // Try alternate format: {4:\n...-}
-        let block4_regex = Regex::new(r"\{4:\s*\n([\s\S]*?)-\}").unwrap();
-        if let Some(cap) = block4_regex.captures(data) {
-            blocks.insert("4".to_string(), cap.get(1).map(|m| m.as_str()).unwrap_or("").to_string());
-        }
+/// The blocks of one message, in file order
+struct Message<'a> {
+    blocks: Vec<(&'a str, Block<'a>)>,
+    /// Byte span from the first block's `{` to the last block's `}`
+    span: (usize, usize),
+}
+
+impl Message<'_> {
+    fn block(&self, id: &str) -> Option<&Block<'_>> {
+        self.blocks
+            .iter()
+            .find(|(block_id, _)| *block_id == id)
+            .map(|(_, block)| block)
     }
 
-    blocks
+    /// `MT103` etc. from the application header
+    fn message_type(&self) -> Option<String> {
+        let content = self.block("2")?.content;
+        let digits = content.get(1..4)?;
+        digits
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| format!("MT{}", digits))
+    }
 }
 
-/// Parse Block 1: Basic Header
-fn parse_block1(content: &str) -> Vec<ParsedField> {
-    let mut fields = Vec::new();
-    let chars: Vec<char> = content.chars().collect();
+/// File framing around FIN messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Envelope {
+    /// Messages back to back, without separators
+    Bare,
+    /// SWIFT Alliance RJE: messages separated by `$`
+    Rje,
+    /// SWIFT Alliance DOS-PCC: each message framed by 0x01 and 0x03, padded to sectors
+    DosPcc,
+}
 
-    if chars.len() >= 1 {
-        fields.push(create_field(0, "Application ID", &chars[0..1].iter().collect::<String>()));
-    }
-    if chars.len() >= 3 {
-        fields.push(create_field(1, "Service ID", &chars[1..3].iter().collect::<String>()));
+impl Envelope {
+    /// Detect the envelope from the file content
+    ///
+    /// `$`, 0x01 and 0x03 cannot occur in the SWIFT character set, so any of them
+    /// marks an envelope.
+    pub fn detect(data: &str) -> Self {
+        let bytes = data.as_bytes();
+        if memchr::memchr2(0x01, 0x03, bytes).is_some() {
+            Envelope::DosPcc
+        } else if memchr::memchr(b'$', bytes).is_some() {
+            Envelope::Rje
+        } else {
+            Envelope::Bare
+        }
     }
-    if chars.len() >= 15 {
-        fields.push(create_field(2, "Logical Terminal", &chars[3..15].iter().collect::<String>()));
+
+    /// Byte ranges of `data` holding one message each (any number for `Bare`)
+    pub fn segments(self, data: &str) -> Vec<Range<usize>> {
+        let bytes = data.as_bytes();
+        match self {
+            Envelope::Bare => std::iter::once(0..data.len()).collect(),
+            Envelope::Rje => {
+                let mut start = 0;
+                let mut segments = Vec::new();
+                for end in memchr::memchr_iter(b'$', bytes).chain(std::iter::once(data.len())) {
+                    segments.push(start..end);
+                    start = end + 1;
+                }
+                segments
+            }
+            Envelope::DosPcc => {
+                // Sector padding between frames is skipped; an unterminated frame runs
+                // to the end of the file
+                let mut segments = Vec::new();
+                let mut pos = 0;
+                while let Some(found) = memchr::memchr(0x01, &bytes[pos..]) {
+                    let start = pos + found + 1;
+                    let end = memchr::memchr(0x03, &bytes[start..])
+                        .map_or(data.len(), |len| start + len);
+                    segments.push(start..end);
+                    pos = (end + 1).min(data.len());
+                }
+                segments
+            }
+        }
     }
-    if chars.len() >= 16 {
-        fields.push(create_field(3, "Session Number", &chars[15..19].iter().collect::<String>()));
+}
+
+/// Split `data[segment]` into messages of brace-balanced `{id:...}` blocks
+///
+/// Block contents may nest braces (the `{108:...}` fields of block 3, `{CHK:...}` of
+/// block 5). A block whose id does not follow the previous one's (`{1:` after `{5:`)
+/// starts the next message. Text between blocks is skipped.
+fn split_messages<'a>(
+    data: &'a str,
+    segment: Range<usize>,
+    lines: &LineIndex,
+) -> Result<Vec<Message<'a>>, ParseError> {
+    let data = &data[..segment.end];
+    let bytes = data.as_bytes();
+    let mut messages: Vec<Message> = Vec::new();
+    let mut pos = segment.start;
+
+    while let Some(found) = memchr::memchr(b'{', &bytes[pos..]) {
+        let start = pos + found;
+        let Some(colon) = data[start + 1..].find(':').map(|i| start + 1 + i) else {
+            break;
+        };
+        let id = &data[start + 1..colon];
+        if id.is_empty() || !id.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            pos = start + 1;
+            continue;
+        }
+
+        let mut depth = 1;
+        let mut end = None;
+        for (i, &byte) in bytes[colon + 1..].iter().enumerate() {
+            match byte {
+                b'{' => depth += 1,
+                b'}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = Some(colon + 1 + i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let Some(end) = end else {
+            let (line, column) = lines.line_column(start);
+            return Err(ParseError::Located(Box::new(
+                Diagnostic::error(
+                    DiagnosticCode::FinBlock,
+                    format!("Block {{{}: is not closed", id),
+                )
+                .at(line, column)
+                .with_span(start, data.len()),
+            )));
+        };
+
+        let mut content = &data[colon + 1..end];
+        if id == "4" {
+            // The text block ends with CRLF "-" before the closing brace
+            content = content.trim_end().strip_suffix('-').unwrap_or(content);
+        }
+        let block = Block {
+            content,
+            offset: colon + 1,
+            span: (start, end + 1),
+        };
+
+        let continues = messages.last().is_some_and(|message| {
+            message.blocks.last().is_some_and(|(last, _)| block_order(id) > block_order(last))
+        });
+        match messages.last_mut() {
+            Some(message) if continues => {
+                message.span.1 = end + 1;
+                message.blocks.push((id, block));
+            }
+            _ => messages.push(Message {
+                blocks: vec![(id, block)],
+                span: (start, end + 1),
+            }),
+        }
+        pos = end + 1;
     }
-    if chars.len() >= 25 {
-        fields.push(create_field(4, "Sequence Number", &chars[19..25].iter().collect::<String>()));
+
+    Ok(messages)
+}
+
+/// Position of a block id within a message; user-defined blocks (`{S:...}`) come last
+fn block_order(id: &str) -> usize {
+    id.parse().unwrap_or(usize::MAX)
+}
+
+/// Characters `start..end` of `content` with their absolute byte span, if present
+fn char_slice(content: &str, offset: usize, start: usize, end: usize) -> Option<(&str, (usize, usize))> {
+    let mut indices = content.char_indices().map(|(i, _)| i).chain(std::iter::once(content.len()));
+    let byte_start = indices.nth(start)?;
+    let byte_end = if end > start {
+        indices.nth(end - start - 1)?
+    } else {
+        byte_start
+    };
+    Some((&content[byte_start..byte_end], (offset + byte_start, offset + byte_end)))
+}
+
+/// Parse Block 1: Basic Header
+fn parse_block1(content: &str, offset: usize) -> Vec<ParsedField> {
+    let mut fields = Vec::new();
+    let layout = [
+        (0, 1, "Application ID"),
+        (1, 3, "Service ID"),
+        (3, 15, "Logical Terminal"),
+        (15, 19, "Session Number"),
+        (19, 25, "Sequence Number"),
+    ];
+
+    for (idx, (start, end, name)) in layout.into_iter().enumerate() {
+        match char_slice(content, offset, start, end) {
+            Some((value, span)) => fields.push(create_field(idx, name, value, span)),
+            None => break,
+        }
     }
 
     fields
 }
 
 /// Parse Block 2: Application Header
-fn parse_block2(content: &str) -> Vec<ParsedField> {
+fn parse_block2(content: &str, offset: usize) -> Vec<ParsedField> {
     let mut fields = Vec::new();
-    let chars: Vec<char> = content.chars().collect();
 
-    if chars.is_empty() {
+    let Some((direction, span)) = char_slice(content, offset, 0, 1) else {
         return fields;
-    }
-
-    let direction = chars[0];
-    fields.push(create_field(0, "Direction", if direction == 'I' { "Input" } else { "Output" }));
+    };
+    let direction_name = if direction == "I" { "Input" } else { "Output" };
+    fields.push(create_field(0, "Direction", direction_name, span));
 
-    if direction == 'I' && chars.len() >= 4 {
-        fields.push(create_field(1, "Message Type", &chars[1..4].iter().collect::<String>()));
-        if chars.len() >= 16 {
-            fields.push(create_field(2, "Destination", &chars[4..16].iter().collect::<String>()));
-        }
-    } else if direction == 'O' && chars.len() >= 4 {
-        fields.push(create_field(1, "Message Type", &chars[1..4].iter().collect::<String>()));
-        if chars.len() >= 8 {
-            fields.push(create_field(2, "Input Time", &chars[4..8].iter().collect::<String>()));
+    let details = match direction {
+        "I" => Some((16, "Destination")),
+        "O" => Some((8, "Input Time")),
+        _ => None,
+    };
+    if let (Some((details_end, details_name)), Some((message_type, span))) =
+        (details, char_slice(content, offset, 1, 4))
+    {
+        fields.push(create_field(1, "Message Type", message_type, span));
+        if let Some((value, span)) = char_slice(content, offset, 4, details_end) {
+            fields.push(create_field(2, details_name, value, span));
         }
     }
 
@@ -264,7 +499,7 @@ fn parse_block2(content: &str) -> Vec<ParsedField> {
 }
 
 /// Parse Block 3: User Header
-fn parse_block3(content: &str) -> Vec<ParsedField> {
+fn parse_block3(content: &str, offset: usize) -> Vec<ParsedField> {
     let mut fields = Vec::new();
     let field_regex = Regex::new(r"\{(\d{3}):([^}]*)\}").unwrap();
 
@@ -286,57 +521,235 @@ fn parse_block3(content: &str) -> Vec<ParsedField> {
             _ => tag,
         };
 
-        fields.push(create_field(idx, name, value));
+        let whole = cap.get(0).unwrap();
+        let span = (offset + whole.start(), offset + whole.end());
+        fields.push(create_field(idx, name, value, span));
     }
 
     fields
 }
 
-/// Parse Block 4: Message Content
-fn parse_block4(content: &str) -> Vec<Vec<ParsedField>> {
+/// A block 4 field as split from the text
+#[derive(Debug)]
+struct TextField<'a> {
+    tag: &'a str,
+    /// Value lines, without line breaks
+    lines: Vec<&'a str>,
+    /// Offset of each value line in the block content
+    line_starts: Vec<usize>,
+    /// Byte range in the block content, from the tag's leading colon to the value's end
+    span: (usize, usize),
+}
+
+/// Message and byte range of a malformed block 4 line
+type Problem = (String, (usize, usize));
+
+/// Fields of one block 4 record and the problems found while splitting them
+#[derive(Debug, Default)]
+struct FieldGroup {
+    fields: Vec<ParsedField>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Split block 4 into fields at each line starting with `:tag:`
+///
+/// Other lines continue the current field's value. A line starting with `:` without
+/// a valid tag (two digits and an optional option letter) is kept in the value and
+/// reported by its byte range, as is text before the first tag.
+fn tokenize_block4(content: &str) -> (Vec<TextField<'_>>, Vec<Problem>) {
+    let mut fields: Vec<TextField> = Vec::new();
+    let mut problems = Vec::new();
+
+    let mut line_start = 0;
+    for raw_line in content.split_inclusive('\n') {
+        let start = line_start;
+        line_start += raw_line.len();
+        let line = raw_line.trim_end_matches(['\r', '\n']);
+        let end = start + line.len();
+
+        if let Some(tag) = field_tag(line) {
+            let value = &line[tag.len() + 2..];
+            fields.push(TextField {
+                tag,
+                lines: vec![value],
+                line_starts: vec![start + tag.len() + 2],
+                span: (start, end),
+            });
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix(':') {
+            let tag = rest.find(':').map_or(line, |end| &line[..end + 2]);
+            let shown: String = tag.chars().take(10).collect();
+            problems.push((format!("Malformed field tag \"{}\"", shown), (start, end)));
+        }
+        match fields.last_mut() {
+            Some(field) => {
+                field.lines.push(line);
+                field.line_starts.push(start);
+                if !line.trim().is_empty() {
+                    field.span.1 = end;
+                }
+            }
+            None if !line.trim().is_empty() && !line.starts_with(':') => {
+                problems.push(("Text before the first field tag".to_string(), (start, end)));
+            }
+            None => {}
+        }
+    }
+
+    for field in &mut fields {
+        let blank = field.lines.iter().rev().take_while(|l| l.trim().is_empty()).count();
+        field.lines.truncate((field.lines.len() - blank).max(1));
+        field.line_starts.truncate(field.lines.len());
+    }
+    (fields, problems)
+}
+
+/// The tag of a line starting with `:tag:`, e.g. `20` or `32A`
+fn field_tag(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix(':')?;
+    let tag_len = rest.find(':')?;
+    let tag = &rest[..tag_len];
+    let bytes = tag.as_bytes();
+    let valid = matches!(bytes.len(), 2 | 3)
+        && bytes[..2].iter().all(u8::is_ascii_digit)
+        && bytes.get(2).is_none_or(u8::is_ascii_uppercase);
+    valid.then_some(tag)
+}
+
+/// Parse Block 4: Message Content, validating fields against the formats of `message_type`
+fn parse_block4(
+    content: &str,
+    offset: usize,
+    message_type: Option<&str>,
+    lines: &LineIndex,
+) -> Vec<FieldGroup> {
     let mut field_groups = Vec::new();
-    let mut current_group = Vec::new();
-    let mut field_idx = 0;
+    let mut current_group = FieldGroup::default();
+    let (tokens, problems) = tokenize_block4(content);
 
-    for cap in FIELD_REGEX.captures_iter(content) {
-        let tag = cap.get(1).map(|m| m.as_str()).unwrap_or("");
-        let value = cap.get(2).map(|m| m.as_str()).unwrap_or("").trim();
+    for (field_idx, token) in tokens.iter().enumerate() {
+        let tag = token.tag;
+        let value = token.lines.join("\n");
+        let value = value.trim();
 
+        let (field_value, field_type) = fin_value(tag, value);
         let name = FIN_FIELD_NAMES
             .get(tag)
             .map(|s| s.to_string())
             .unwrap_or_else(|| format!("Field {}", tag));
 
-        current_group.push(ParsedField {
-            id: format!("field-4-{}", field_idx),
+        let id = format!("field-4-{}", field_idx);
+        if let Some(format) = swift_format::field_format(message_type, tag) {
+            if let Err(problem) = format.validate(&token.lines.join("\n")) {
+                let start = offset + token.span.0;
+                let (line, column) = lines.line_column(start);
+                current_group.diagnostics.push(
+                    Diagnostic::error(
+                        DiagnosticCode::FinFormat,
+                        format!("Field {} does not match {}: {}", tag, format.spec(), problem),
+                    )
+                    .at(line, column)
+                    .with_span(start, offset + token.span.1)
+                    .with_field(id.clone()),
+                );
+            }
+        }
+
+        current_group.fields.push(ParsedField {
+            id: id.clone(),
             name: name.clone(),
-            value: FieldValue::String(value.to_string()),
-            field_type: infer_fin_type(tag, value),
+            value: field_value,
+            field_type,
             original_value: format!(":{}: {}", tag, value),
-            position: None,
+            position: Some(Position::new(offset + token.span.0, offset + token.span.1)),
         });
 
-        field_idx += 1;
+        // Composite and party fields are followed by their parts, keeping the original line
+        let decomposed = fin_subfields::decompose(tag, &token.lines);
+        for (message, line) in decomposed.problems {
+            let (start, end) = match line {
+                Some(line) => {
+                    let start = token.line_starts[line];
+                    (start, start + token.lines[line].len())
+                }
+                None => token.span,
+            };
+            let (line, column) = lines.line_column(offset + start);
+            current_group.diagnostics.push(
+                Diagnostic::error(DiagnosticCode::FinFormat, format!("Field {}: {}", tag, message))
+                    .at(line, column)
+                    .with_span(offset + start, offset + end)
+                    .with_field(id.clone()),
+            );
+        }
+        for (sub_idx, sub) in decomposed.parts.into_iter().enumerate() {
+            let line_start = offset + token.line_starts[sub.line];
+            let text = &token.lines[sub.line][sub.range.clone()];
+            current_group.fields.push(ParsedField {
+                id: format!("{}-{}", id, sub_idx),
+                name: format!("{} {}", tag, sub.name),
+                value: sub.value,
+                field_type: sub.field_type.to_string(),
+                original_value: text.to_string(),
+                position: Some(Position::new(
+                    line_start + sub.range.start,
+                    line_start + sub.range.end,
+                )),
+            });
+        }
+
+        // Malformed tags within this field's lines belong to its record
+        let (start, end) = token.span;
+        current_group.diagnostics.extend(
+            problems
+                .iter()
+                .filter(|(_, span)| span.0 >= start && span.0 <= end)
+                .map(|(message, span)| tag_diagnostic(message, *span, offset, lines)),
+        );
 
         // Group certain fields together
-        if is_group_boundary(tag) && !current_group.is_empty() {
+        if is_group_boundary(tag) && !current_group.fields.is_empty() {
             field_groups.push(std::mem::take(&mut current_group));
         }
     }
 
-    if !current_group.is_empty() {
+    if !current_group.fields.is_empty() {
         field_groups.push(current_group);
     }
 
     if field_groups.is_empty() {
-        field_groups.push(Vec::new());
+        field_groups.push(FieldGroup::default());
     }
 
+    // Text before the first tag goes to the first record; without any tags the
+    // whole block is reported instead
+    let first_start = tokens.first().map_or(0, |token| token.span.0);
+    let leading = problems
+        .iter()
+        .filter(|(_, span)| span.0 < first_start)
+        .map(|(message, span)| tag_diagnostic(message, *span, offset, lines));
+    field_groups[0].diagnostics.splice(0..0, leading);
+
     field_groups
 }
 
+/// Error for a malformed line of block 4 at `span` within the block content
+fn tag_diagnostic(
+    message: &str,
+    span: (usize, usize),
+    offset: usize,
+    lines: &LineIndex,
+) -> Diagnostic {
+    let (line, column) = lines.line_column(offset + span.0);
+    Diagnostic::error(DiagnosticCode::FinField, message)
+        .at(line, column)
+        .with_span(offset + span.0, offset + span.1)
+}
+
 /// Parse Block 5: Trailer
-fn parse_block5(content: &str) -> Vec<ParsedField> {
+fn parse_block5(content: &str, offset: usize) -> Vec<ParsedField> {
     let mut fields = Vec::new();
     let field_regex = Regex::new(r"\{([A-Z]{3}):([^}]*)\}").unwrap();
 
@@ -355,21 +768,23 @@ fn parse_block5(content: &str) -> Vec<ParsedField> {
             _ => tag,
         };
 
-        fields.push(create_field(idx, name, value));
+        let whole = cap.get(0).unwrap();
+        let span = (offset + whole.start(), offset + whole.end());
+        fields.push(create_field(idx, name, value, span));
     }
 
     fields
 }
 
-/// Create a parsed field
-fn create_field(idx: usize, name: &str, value: &str) -> ParsedField {
+/// Create a parsed field spanning `span` in the message
+fn create_field(idx: usize, name: &str, value: &str, span: (usize, usize)) -> ParsedField {
     ParsedField {
         id: format!("field-{}", idx),
         name: name.to_string(),
         value: FieldValue::String(value.to_string()),
         field_type: "string".to_string(),
         original_value: value.to_string(),
-        position: None,
+        position: Some(Position::new(span.0, span.1)),
     }
 }
 
@@ -378,27 +793,33 @@ fn is_group_boundary(tag: &str) -> bool {
     matches!(tag, "59" | "59A" | "59F" | "61" | "86")
 }
 
-/// Infer field type from tag and value
-fn infer_fin_type(tag: &str, value: &str) -> String {
-    match tag {
-        "32A" | "33B" | "60F" | "60M" | "62F" | "62M" | "64" | "65" => {
-            // Amount fields
-            if value.contains(|c: char| c.is_ascii_digit()) {
-                "number".to_string()
-            } else {
-                "string".to_string()
-            }
-        }
-        _ => "string".to_string(),
+/// Typed value for a block 4 field: amount fields yield their exact amount, date
+/// fields their date
+///
+/// FIN amounts use a decimal comma (`1234,56`); the full text stays in `original_value`.
+fn fin_value(tag: &str, value: &str) -> (FieldValue, String) {
+    // Tag 30 is YYMMDD; the lettered 30 options (trade, value, payment dates) YYYYMMDD
+    let date = match tag {
+        "30" => dates::parse_yymmdd(value).map(FieldValue::Date),
+        _ if tag.starts_with("30") && value.len() == 8 => dates::parse_with_format(value, "%Y%m%d"),
+        _ => None,
+    };
+    if let Some(date) = date {
+        return (date, "date".to_string());
     }
-}
 
-/// Get current time in milliseconds
-fn get_time() -> f64 {
-    web_sys::window()
-        .and_then(|w| w.performance())
-        .map(|p| p.now())
-        .unwrap_or(0.0)
+    let is_amount = matches!(
+        tag,
+        "19" | "32A" | "32B" | "33B" | "36" | "60F" | "60M" | "62F" | "62M" | "64" | "65" | "71F" | "71G"
+    );
+    let amount = FIN_AMOUNT_REGEX
+        .captures(value)
+        .and_then(|cap| Decimal::parse_with_separator(&cap[1], ','));
+
+    match amount {
+        Some(amount) if is_amount => (FieldValue::Decimal(amount), "number".to_string()),
+        _ => (FieldValue::String(value.to_string()), "string".to_string()),
+    }
 }
 
 #[cfg(test)]
@@ -407,17 +828,197 @@ mod tests {
 
     #[test]
     fn test_parse_blocks() {
-        let data = "{1:F01BANKUS33AXXX0000000000}{2:I103BANKGB2LXXXXN}";
-        let blocks = parse_blocks(data);
-        assert!(blocks.contains_key("1"));
-        assert!(blocks.contains_key("2"));
+        let data = "{1:F01BANKUS33AXXX0000000000}{2:I103BANKGB2LXXXXN}\
+                    {3:{108:REF}{121:180f1e65-90e0-44d5-a49a-92b55eb3025f}}{4:\n:20:X\n-}\
+                    {5:{CHK:123456789ABC}}";
+        let messages = split_messages(data, 0..data.len(), &LineIndex::new(data)).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            messages[0].block("3").unwrap().content,
+            "{108:REF}{121:180f1e65-90e0-44d5-a49a-92b55eb3025f}"
+        );
+        assert_eq!(messages[0].block("4").unwrap().content, "\n:20:X\n");
+        assert_eq!(messages[0].block("5").unwrap().content, "{CHK:123456789ABC}");
+        assert_eq!(messages[0].message_type().as_deref(), Some("MT103"));
+
+        assert!(matches!(
+            split_messages("{1:F01{3:", 0..9, &LineIndex::new("{1:F01{3:")),
+            Err(ParseError::Located(_))
+        ));
+    }
+
+    #[test]
+    fn test_rje_and_dos_pcc_envelopes() {
+        let message = "{1:F01BANKUS33AXXX0000000000}{2:I940BANKGB2LXXXXN}{4:\r\n:20:STMT\r\n-}";
+        let rje = format!("{}\r\n${}\r\n$", message, message);
+        assert_eq!(Envelope::detect(&rje), Envelope::Rje);
+
+        let mut pcc = String::new();
+        for _ in 0..2 {
+            pcc.push('\u{1}');
+            pcc.push_str(message);
+            pcc.push('\u{3}');
+            pcc.push_str(&" ".repeat(512 - message.len() - 2));
+        }
+        assert_eq!(Envelope::detect(&pcc), Envelope::DosPcc);
+        let segments = Envelope::DosPcc.segments(&pcc);
+        assert_eq!(segments, [1..message.len() + 1, 513..message.len() + 513]);
+
+        for data in [rje, pcc] {
+            let result = parse_fin(&data, &ParserConfig::default()).unwrap();
+            let messages = result.metadata.messages.unwrap();
+            assert_eq!(messages.len(), 2);
+            assert_eq!(messages[1].message_type.as_deref(), Some("MT940"));
+            assert_eq!(result.metadata.total_records, 6);
+            assert_eq!(result.metadata.invalid_records, 0);
+        }
+    }
+
+    #[test]
+    fn test_multiple_messages() {
+        let message = |reference: &str| {
+            format!(
+                "{{1:F01BANKUS33AXXX0000000000}}{{2:I103BANKGB2LXXXXN}}{{3:{{108:{}}}}}\
+                 {{4:\n:20:{}\n:32A:240115EUR1,00\n-}}{{5:{{CHK:ABC}}}}\n",
+                reference, reference
+            )
+        };
+        let data = format!("{}{}", message("A"), message("B"));
+        let result = parse_fin(&data, &ParserConfig::default()).unwrap();
+
+        let messages = result.metadata.messages.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!((messages[1].first_record, messages[1].record_count), (5, 5));
+        assert_eq!(messages[1].position.as_ref().unwrap().line, Some(5));
+        assert_eq!(result.metadata.total_records, 10);
+
+        let types: Vec<&str> = result.records[5..].iter().map(|r| r.record_type.as_str()).collect();
+        assert_eq!(types, ["header", "header", "header", "transaction", "footer"]);
+        assert_eq!(result.records[7].fields[0].original_value, "B");
+        assert_eq!(result.records[9].index, 9);
     }
 
     #[test]
     fn test_parse_block1() {
         let content = "F01BANKUS33AXXX0000000000";
-        let fields = parse_block1(content);
+        let fields = parse_block1(content, 0);
         assert!(!fields.is_empty());
         assert_eq!(fields[0].name, "Application ID");
     }
+
+    #[test]
+    fn test_parse_block4_fields() {
+        let block4 = |content| parse_block4(content, 0, None, &LineIndex::new(content));
+        let groups = block4("\n:20:REF123\n:32A:240115EUR1234,56\n");
+        assert_eq!(groups.len(), 1);
+        let names: Vec<&str> = groups[0].fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "Transaction Reference Number",
+                "Value Date/Currency/Amount",
+                "32A Value Date",
+                "32A Currency",
+                "32A Amount",
+            ]
+        );
+        let tag_32a = &groups[0].fields[1];
+        assert_eq!(tag_32a.original_value, ":32A: 240115EUR1234,56");
+        assert!(matches!(&tag_32a.value, FieldValue::Decimal(d) if d.to_string() == "1234.56"));
+        let currency = &groups[0].fields[3];
+        let currency_id = (currency.id.as_str(), currency.original_value.as_str());
+        assert_eq!(currency_id, ("field-4-1-1", "EUR"));
+        let position = currency.position.as_ref().unwrap();
+        assert_eq!((position.start, position.end), (23, 26));
+
+        let groups = block4("\n:30:240116\n:30V:20240117\n");
+        let dates: Vec<String> =
+            groups[0].fields.iter().map(|f| serde_json::to_string(&f.value).unwrap()).collect();
+        assert_eq!(dates, ["\"2024-01-16\"", "\"2024-01-17\""]);
+    }
+
+    #[test]
+    fn test_block4_tokenizer() {
+        let content =
+            "\r\n:20:REF:12:30\r\n:70:SEE https://example.com/inv\r\nLINE 2\r\n\r\n:2X:BAD\r\n:86:NOTE\r\n";
+        let groups = parse_block4(content, 0, None, &LineIndex::new(content));
+        let fields = &groups[0].fields;
+
+        let values: Vec<&str> = fields.iter().map(|f| f.original_value.as_str()).collect();
+        assert_eq!(
+            values,
+            [
+                ":20: REF:12:30",
+                ":70: SEE https://example.com/inv\nLINE 2\n\n:2X:BAD",
+                ":86: NOTE",
+            ]
+        );
+        let span = fields[1].position.as_ref().unwrap();
+        assert!(content[span.start..span.end].ends_with("LINE 2\r\n\r\n:2X:BAD"));
+
+        // The blank line also breaks field 70's 4*35x format
+        let diagnostics = &groups[0].diagnostics;
+        let codes: Vec<_> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, [DiagnosticCode::FinFormat, DiagnosticCode::FinField]);
+        assert_eq!(diagnostics[1].message, "Malformed field tag \":2X:\"");
+        assert_eq!(diagnostics[1].line, Some(6));
+    }
+
+    #[test]
+    fn test_field_format_validation() {
+        let data = "{1:F01BANKUS33AXXX0000000000}{2:I103BANKGB2LXXXXN}{4:\n\
+                    :20:REF123\n:23B:CRED\n:32A:240115EU1234,56\n:71A:SHA\n-}";
+        let result = parse_fin(data, &ParserConfig::default()).unwrap();
+        let record = &result.records[2];
+
+        assert!(!record.is_valid);
+        assert_eq!(
+            record.errors.as_ref().unwrap(),
+            &["Field 32A does not match 6!n3!a15d: expected 3!a (letters) at character 9"]
+        );
+        let diagnostic = &record.diagnostics.as_ref().unwrap()[0];
+        assert_eq!(diagnostic.code, DiagnosticCode::FinFormat);
+        assert_eq!(diagnostic.field_id.as_deref(), Some("field-4-2"));
+        assert_eq!(diagnostic.line, Some(4));
+        assert_eq!(result.metadata.invalid_records, 1);
+
+        // Option F rules are checked on top of the format, at the line concerned
+        let data = "{1:F01BANKUS33AXXX0000000000}{4:\n:20:REF123\n\
+                    :50F:/12345\n1/JOHN DOE\n2/MAIN STREET 1\n3/BERLIN\n-}";
+        let result = parse_fin(data, &ParserConfig::default()).unwrap();
+        let diagnostic = &result.records[1].diagnostics.as_ref().unwrap()[0];
+        assert_eq!(diagnostic.message, "Field 50F: 3/ must start with a country code");
+        assert_eq!(diagnostic.line, Some(6));
+    }
+
+    #[test]
+    fn test_field_positions() {
+        let data = "{1:F01BANKUS33AXXX0000000000}{4:\n:20:REF123\n:32A:240115EUR1234,56\n-}";
+        let result = parse_fin(data, &ParserConfig::default()).unwrap();
+        let span = |position: &Option<Position>| {
+            let position = position.as_ref().unwrap();
+            &data[position.start..position.end]
+        };
+
+        assert_eq!(span(&result.records[0].fields[2].position), "BANKUS33AXXX");
+        let tag_32a = &result.records[1].fields[1];
+        assert_eq!(span(&tag_32a.position), ":32A:240115EUR1234,56");
+        assert_eq!(tag_32a.position.as_ref().unwrap().line, Some(3));
+    }
+
+    #[test]
+    fn test_text_block_without_tags_is_diagnosed() {
+        let data = "{1:F01BANKUS33AXXX0000000000}\n{4:\nno tags here\n-}";
+        let result = parse_fin(data, &ParserConfig::default()).unwrap();
+        let record = result.records.last().unwrap();
+
+        assert!(!record.is_valid);
+        let diagnostic = &record.diagnostics.as_ref().unwrap()[0];
+        assert_eq!(diagnostic.code, DiagnosticCode::FinField);
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(2), Some(1)));
+        assert!(matches!(
+            parse_fin("plain text", &ParserConfig::default()),
+            Err(ParseError::Located(_))
+        ));
+    }
 }
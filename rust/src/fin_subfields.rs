@@ -0,0 +1,528 @@
+//! FIN Sub-Fields
+//!
+//! Composite block 4 fields pack several values into one line: 32A is a value date,
+//! a currency and an amount (`240115EUR1234,56`), a 61 statement line has up to nine
+//! parts. [`decompose`] splits them into named, typed parts with their byte ranges
+//! in the field's value lines; the parser adds each part as a field of its own next
+//! to the original line. Values that do not fit the layout yield no parts (the
+//! format check reports them).
+//!
+//! Party fields (50-59) are split by option: A is an optional `/account` line and a
+//! BIC, D and K (and 59 without a letter) an optional account line and up to four
+//! name and address lines, and F numbered lines (`1/NAME`, `2/ADDRESS`,
+//! `3/FR/PARIS`). Option F has rules beyond its format, e.g. an address needs a
+//! country and town line; breaking one is returned as a problem.
+
+use crate::dates;
+use crate::decimal::Decimal;
+use crate::types::FieldValue;
+use chrono::{Datelike, NaiveDate};
+use regex::Regex;
+use std::ops::Range;
+
+lazy_static::lazy_static! {
+    static ref DATE_CURRENCY_AMOUNT: Regex = Regex::new(r"^(\d{6})([A-Z]{3})(\d[\d,]*)$").unwrap();
+    static ref CURRENCY_AMOUNT: Regex = Regex::new(r"^([A-Z]{3})(\d[\d,]*)$").unwrap();
+    static ref BALANCE: Regex = Regex::new(r"^([CD])(\d{6})([A-Z]{3})(\d[\d,]*)$").unwrap();
+    static ref RATE: Regex = Regex::new(r"^(\d[\d,]*)$").unwrap();
+    /// 61: value date, entry date, D/C mark, funds code, amount, transaction type,
+    /// customer reference and bank reference; supplementary details follow on line 2
+    static ref STATEMENT_LINE: Regex = Regex::new(
+        r"^(\d{6})(\d{4})?(R?[CD]|E[CD])([A-Z])?(\d[\d,]{0,14})([A-Z][A-Z0-9]{3})(.{1,16}?)(?://(.{1,16}))?$"
+    )
+    .unwrap();
+    /// Letters between the dates and the amount of a 61: D/C mark and funds code
+    static ref STATEMENT_MARK: Regex = Regex::new(r"^\d{6}(?:\d{4})?([A-Z]{1,3})\d").unwrap();
+    static ref VALID_MARK: Regex = Regex::new(r"^(?:R?[CD]|E[CD])[A-Z]?$").unwrap();
+    /// `[/1!a][/34x]`: D/C mark and account of options A and D
+    static ref MARKED_ACCOUNT: Regex = Regex::new(r"^/([CD])(?:/(.+))?$").unwrap();
+    /// `4!a/2!a/27x`: identifier code, country and identifier of 50F
+    static ref PARTY_IDENTIFIER: Regex = Regex::new(r"^([A-Z]{4})/([A-Z]{2})/(.+)$").unwrap();
+    /// `2!a[/30x]`: country and town of lines 3/ and 5/ of option F
+    static ref COUNTRY_TOWN: Regex = Regex::new(r"^([A-Z]{2})(?:/(.+))?$").unwrap();
+    /// `2!a/`: country code of lines 6/ and 7/ of 50F
+    static ref COUNTRY_PREFIX: Regex = Regex::new(r"^[A-Z]{2}/").unwrap();
+}
+
+/// Identifier codes of the first line of 50F
+const IDENTIFIER_CODES: [&str; 8] =
+    ["ARNU", "CCPT", "CUST", "DRLC", "EMPL", "NIDN", "SOSE", "TXID"];
+
+/// One part of a composite field
+#[derive(Debug)]
+pub(crate) struct SubField {
+    pub(crate) name: &'static str,
+    /// Index of the value line holding the part
+    pub(crate) line: usize,
+    /// Byte range within that line
+    pub(crate) range: Range<usize>,
+    pub(crate) value: FieldValue,
+    pub(crate) field_type: &'static str,
+}
+
+/// Parts of a field and the option rules it breaks
+#[derive(Debug, Default)]
+pub(crate) struct Decomposed {
+    pub(crate) parts: Vec<SubField>,
+    /// Broken rules, with the value line they concern (`None` for the whole field)
+    pub(crate) problems: Vec<(String, Option<usize>)>,
+}
+
+impl Decomposed {
+    fn push(&mut self, name: &'static str, line: usize, range: Range<usize>, text: &str) {
+        self.parts.push(SubField {
+            name,
+            line,
+            range,
+            value: FieldValue::String(text.to_string()),
+            field_type: "string",
+        });
+    }
+
+    fn problem(&mut self, message: impl Into<String>, line: Option<usize>) {
+        self.problems.push((message.into(), line));
+    }
+}
+
+/// How a part's text is typed
+#[derive(Clone, Copy)]
+enum Kind {
+    Text,
+    /// `YYMMDD`
+    Date,
+    /// `MMDD` in the year of the preceding date
+    MonthDay,
+    /// Decimal with a comma
+    Amount,
+}
+
+/// Named, typed parts of field `tag`, or none when it is not composite
+pub(crate) fn decompose(tag: &str, lines: &[&str]) -> Decomposed {
+    match party_option(tag) {
+        Some('A') => bic_party(tag, lines),
+        Some('F') => structured_party(tag, lines),
+        Some(_) => name_address_party(tag, lines),
+        None => {
+            let mut decomposed = Decomposed {
+                parts: composite(tag, lines),
+                problems: Vec::new(),
+            };
+            // The format's 2a allows any letters; only these marks are defined
+            if tag == "61" && decomposed.parts.is_empty() {
+                let mark = lines.first().and_then(|line| STATEMENT_MARK.captures(line));
+                let mark = mark.and_then(|captures| captures.get(1));
+                if let Some(mark) = mark.filter(|mark| !VALID_MARK.is_match(mark.as_str())) {
+                    let message = format!(
+                        "D/C mark \"{}\" is not C, D, RC, RD, EC or ED",
+                        mark.as_str()
+                    );
+                    decomposed.problem(message, Some(0));
+                }
+            }
+            decomposed
+        }
+    }
+}
+
+/// Parts of the amount, balance and statement line fields
+fn composite(tag: &str, lines: &[&str]) -> Vec<SubField> {
+    use Kind::*;
+
+    let (regex, parts): (&Regex, &[(&'static str, Kind)]) = match tag {
+        "32A" => (
+            &DATE_CURRENCY_AMOUNT,
+            &[("Value Date", Date), ("Currency", Text), ("Amount", Amount)],
+        ),
+        "32B" | "33B" | "71F" | "71G" => {
+            (&CURRENCY_AMOUNT, &[("Currency", Text), ("Amount", Amount)])
+        }
+        "60F" | "60M" | "62F" | "62M" | "64" | "65" => (
+            &BALANCE,
+            &[
+                ("D/C Mark", Text),
+                ("Date", Date),
+                ("Currency", Text),
+                ("Amount", Amount),
+            ],
+        ),
+        "36" => (&RATE, &[("Exchange Rate", Amount)]),
+        "61" => (
+            &STATEMENT_LINE,
+            &[
+                ("Value Date", Date),
+                ("Entry Date", MonthDay),
+                ("D/C Mark", Text),
+                ("Funds Code", Text),
+                ("Amount", Amount),
+                ("Transaction Type", Text),
+                ("Customer Reference", Text),
+                ("Bank Reference", Text),
+            ],
+        ),
+        _ => return Vec::new(),
+    };
+    let Some(line) = lines.first() else {
+        return Vec::new();
+    };
+    let Some(captures) = regex.captures(line) else {
+        return Vec::new();
+    };
+
+    let mut subfields = Vec::new();
+    let mut last_date = None;
+    for (&(name, kind), capture) in parts.iter().zip(captures.iter().skip(1)) {
+        let Some(capture) = capture else { continue };
+        let text = capture.as_str();
+        let typed = match kind {
+            Text => None,
+            Date => dates::parse_yymmdd(text).inspect(|&date| last_date = Some(date)).map(date),
+            MonthDay => last_date.and_then(|base| month_day(base, text)).map(date),
+            Amount => Decimal::parse_with_separator(text, ',')
+                .map(|amount| (FieldValue::Decimal(amount), "number")),
+        };
+        let (value, field_type) =
+            typed.unwrap_or_else(|| (FieldValue::String(text.to_string()), "string"));
+        subfields.push(SubField {
+            name,
+            line: 0,
+            range: capture.range(),
+            value,
+            field_type,
+        });
+    }
+
+    if tag == "61" {
+        if let Some(details) = lines.get(1).filter(|l| !l.trim().is_empty()) {
+            subfields.push(SubField {
+                name: "Supplementary Details",
+                line: 1,
+                range: 0..details.len(),
+                value: FieldValue::String(details.trim_end().to_string()),
+                field_type: "string",
+            });
+        }
+    }
+    subfields
+}
+
+/// Option letter of a party field (50-59), `' '` for 59 without one
+fn party_option(tag: &str) -> Option<char> {
+    let number: u8 = tag.get(..2)?.parse().ok()?;
+    let option = tag[2..].chars().next().unwrap_or(' ');
+    let known = match option {
+        'A' => true,
+        'D' => number != 59,
+        'K' | 'F' => matches!(number, 50 | 59),
+        ' ' => number == 59,
+        _ => false,
+    };
+    ((50..=59).contains(&number) && known).then_some(option)
+}
+
+/// Splits an optional party identifier line starting with `/`, returning the
+/// index of the next line
+fn party_identifier(tag: &str, lines: &[&str], parts: &mut Decomposed) -> usize {
+    let Some(line) = lines.first().filter(|line| line.starts_with('/')) else {
+        return 0;
+    };
+    // Customer options only have an account; the institution options may add a mark
+    let marked = !matches!(tag, "50A" | "50F" | "50K" | "59" | "59A" | "59F");
+    if let Some(captures) = MARKED_ACCOUNT.captures(line).filter(|_| marked) {
+        for (name, capture) in [("D/C Mark", captures.get(1)), ("Account", captures.get(2))] {
+            if let Some(capture) = capture {
+                parts.push(name, 0, capture.range(), capture.as_str());
+            }
+        }
+    } else if let Some(code) = line.strip_prefix("//") {
+        parts.push("Clearing Code", 0, 2..line.len(), code);
+    } else {
+        parts.push("Account", 0, 1..line.len(), &line[1..]);
+    }
+    1
+}
+
+/// Option A: optional account line and a BIC
+fn bic_party(tag: &str, lines: &[&str]) -> Decomposed {
+    let mut parts = Decomposed::default();
+    let next = party_identifier(tag, lines, &mut parts);
+    if let Some(bic) = lines.get(next) {
+        parts.push("BIC", next, 0..bic.len(), bic.trim_end());
+    }
+    parts
+}
+
+/// Options D and K: optional account line, then a name and address lines
+fn name_address_party(tag: &str, lines: &[&str]) -> Decomposed {
+    let mut parts = Decomposed::default();
+    let next = party_identifier(tag, lines, &mut parts);
+    for (idx, line) in lines.iter().enumerate().skip(next) {
+        let name = if idx == next { "Name" } else { "Address" };
+        parts.push(name, idx, 0..line.len(), line.trim_end());
+    }
+    parts
+}
+
+/// Option F: party identifier and numbered name, address and identification lines
+fn structured_party(tag: &str, lines: &[&str]) -> Decomposed {
+    let mut parts = Decomposed::default();
+    // 59F has an optional account; 50F an account or a code/country/identifier
+    let mut next = party_identifier(tag, lines, &mut parts);
+    if next == 0 && tag == "50F" {
+        match lines.first().and_then(|line| PARTY_IDENTIFIER.captures(line)) {
+            Some(captures) => {
+                let code = &captures[1];
+                if !IDENTIFIER_CODES.contains(&code) {
+                    parts.problem(format!("unknown identifier code \"{}\"", code), Some(0));
+                }
+                let names = ["Identifier Code", "Identifier Country", "Identifier"];
+                for (name, capture) in names.into_iter().zip(captures.iter().skip(1)) {
+                    let capture = capture.expect("all groups participate");
+                    parts.push(name, 0, capture.range(), capture.as_str());
+                }
+            }
+            None => parts.problem(
+                "the first line must be /account or code/country/identifier",
+                Some(0),
+            ),
+        }
+        next = 1;
+    }
+
+    let max_number = if tag == "50F" { 8 } else { 3 };
+    let mut seen = [false; 9];
+    let mut last_number = 0;
+    for (idx, line) in lines.iter().enumerate().skip(next) {
+        let number = match line.as_bytes() {
+            [digit @ b'1'..=b'9', b'/', ..] => digit - b'0',
+            _ => {
+                parts.problem("lines must start with a line number, e.g. 1/", Some(idx));
+                continue;
+            }
+        };
+        if number > max_number {
+            parts.problem(format!("line number {} is not allowed", number), Some(idx));
+            continue;
+        }
+        if number < last_number {
+            parts.problem("line numbers must be in ascending order", Some(idx));
+        }
+        last_number = number;
+        seen[number as usize] = true;
+
+        let text = line[2..].trim_end();
+        let range = 2..2 + text.len();
+        match number {
+            1 => parts.push("Name", idx, range, text),
+            2 => parts.push("Address", idx, range, text),
+            3 | 5 => {
+                let names = if number == 3 {
+                    ["Country", "Town"]
+                } else {
+                    ["Birth Country", "Birth Town"]
+                };
+                let Some(captures) = COUNTRY_TOWN.captures(text) else {
+                    let message = format!("{}/ must start with a country code", number);
+                    parts.problem(message, Some(idx));
+                    continue;
+                };
+                for (name, capture) in names.into_iter().zip(captures.iter().skip(1)) {
+                    if let Some(capture) = capture {
+                        let range = 2 + capture.start()..2 + capture.end();
+                        parts.push(name, idx, range, capture.as_str());
+                    }
+                }
+            }
+            4 => match NaiveDate::parse_from_str(text, "%Y%m%d") {
+                Ok(birth) if text.len() == 8 => parts.parts.push(SubField {
+                    name: "Date of Birth",
+                    line: idx,
+                    range,
+                    value: FieldValue::Date(birth),
+                    field_type: "date",
+                }),
+                _ => parts.problem("4/ must be a date of birth (YYYYMMDD)", Some(idx)),
+            },
+            6 | 7 => {
+                if !COUNTRY_PREFIX.is_match(text) {
+                    let message = format!("{}/ must start with a country code", number);
+                    parts.problem(message, Some(idx));
+                }
+                let name = if number == 6 {
+                    "Customer Identification"
+                } else {
+                    "National Identity Number"
+                };
+                parts.push(name, idx, range, text);
+            }
+            _ => parts.push("Additional Information", idx, range, text),
+        }
+    }
+
+    if !seen[1] {
+        parts.problem("1/ (name) is required", None);
+    }
+    if seen[2] && !seen[3] {
+        parts.problem("2/ (address) requires a 3/ (country and town) line", None);
+    }
+    if seen[4] != seen[5] {
+        parts.problem("4/ (date of birth) and 5/ (place of birth) go together", None);
+    }
+    parts
+}
+
+fn date(date: NaiveDate) -> (FieldValue, &'static str) {
+    (FieldValue::Date(date), "date")
+}
+
+/// The `MMDD` date nearest to `base`, which may fall in the previous or next year
+fn month_day(base: NaiveDate, text: &str) -> Option<NaiveDate> {
+    let month = text.get(..2)?.parse().ok()?;
+    let day = text.get(2..4)?.parse().ok()?;
+    [base.year() - 1, base.year(), base.year() + 1]
+        .into_iter()
+        .filter_map(|year| NaiveDate::from_ymd_opt(year, month, day))
+        .min_by_key(|candidate| (*candidate - base).num_days().abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(subfield: &SubField) -> String {
+        serde_json::to_value(&subfield.value).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_amount_fields() {
+        let parts = decompose("32A", &["240115EUR1234,56"]).parts;
+        let named: Vec<(&str, String)> = parts.iter().map(|p| (p.name, text(p))).collect();
+        assert_eq!(
+            named,
+            [
+                ("Value Date", "\"2024-01-15\"".to_string()),
+                ("Currency", "\"EUR\"".to_string()),
+                ("Amount", "\"1234.56\"".to_string()),
+            ]
+        );
+        assert_eq!(parts[2].range, 9..16);
+
+        let balance = decompose("62F", &["D240131EUR100,"]).parts;
+        assert_eq!(balance[0].field_type, "string");
+        assert_eq!(balance[1].field_type, "date");
+        assert!(decompose("32A", &["24011EUR1,00"]).parts.is_empty());
+        assert!(decompose("20", &["REF"]).parts.is_empty());
+    }
+
+    #[test]
+    fn test_statement_line() {
+        let lines = ["2312290102RCR1234,56NTRFINV 42//BANKREF1", "SUPPLEMENT"];
+        let parts = decompose("61", &lines).parts;
+        let named: Vec<(&str, String)> = parts.iter().map(|p| (p.name, text(p))).collect();
+        assert_eq!(
+            named,
+            [
+                ("Value Date", "\"2023-12-29\"".to_string()),
+                // The entry date crosses into the next year
+                ("Entry Date", "\"2024-01-02\"".to_string()),
+                ("D/C Mark", "\"RC\"".to_string()),
+                ("Funds Code", "\"R\"".to_string()),
+                ("Amount", "\"1234.56\"".to_string()),
+                ("Transaction Type", "\"NTRF\"".to_string()),
+                ("Customer Reference", "\"INV 42\"".to_string()),
+                ("Bank Reference", "\"BANKREF1\"".to_string()),
+                ("Supplementary Details", "\"SUPPLEMENT\"".to_string()),
+            ]
+        );
+        assert_eq!((parts[8].line, parts[8].range.clone()), (1, 0..10));
+
+        // MT942/MT950 expected entries are marked EC/ED
+        let expected = decompose("61", &["2401160116ED250,NTRFNONREF"]).parts;
+        assert_eq!((expected[2].name, text(&expected[2])), ("D/C Mark", "\"ED\"".to_string()));
+
+        let unknown = decompose("61", &["240115XD500,00NMSCNONREF"]);
+        assert!(unknown.parts.is_empty());
+        assert_eq!(unknown.problems[0].0, "D/C mark \"XD\" is not C, D, RC, RD, EC or ED");
+        // Other mistakes are left to the format check
+        assert!(decompose("61", &["240115C500,00NMSC"]).problems.is_empty());
+
+        let short = decompose("61", &["240115C500,00NMSCNONREF"]).parts;
+        let names: Vec<&str> = short.iter().map(|p| p.name).collect();
+        assert_eq!(
+            names,
+            ["Value Date", "D/C Mark", "Amount", "Transaction Type", "Customer Reference"]
+        );
+    }
+
+    fn parts<'a>(decomposed: &'a Decomposed, lines: &[&'a str]) -> Vec<(&'static str, &'a str)> {
+        decomposed.parts.iter().map(|p| (p.name, &lines[p.line][p.range.clone()])).collect()
+    }
+
+    #[test]
+    fn test_party_options() {
+        let lines = ["/C/12345678", "DEUTDEFF"];
+        let party = decompose("52A", &lines);
+        assert_eq!(
+            parts(&party, &lines),
+            [("D/C Mark", "C"), ("Account", "12345678"), ("BIC", "DEUTDEFF")]
+        );
+
+        let lines = ["//FW021000021", "FIRST BANK", "NEW YORK"];
+        let party = decompose("57D", &lines);
+        assert_eq!(
+            parts(&party, &lines),
+            [("Clearing Code", "FW021000021"), ("Name", "FIRST BANK"), ("Address", "NEW YORK")]
+        );
+
+        let lines = ["/DE89370400440532013000", "JOHN DOE", "BERLIN"];
+        let party = decompose("59", &lines);
+        assert_eq!(
+            parts(&party, &lines),
+            [("Account", "DE89370400440532013000"), ("Name", "JOHN DOE"), ("Address", "BERLIN")]
+        );
+        assert!(party.problems.is_empty());
+        assert!(decompose("53B", &["/C/1234"]).parts.is_empty());
+    }
+
+    #[test]
+    fn test_structured_party() {
+        let lines = ["NIDN/DE/121231234342", "1/JOHN DOE", "2/HAUPTSTRASSE 1", "3/DE/BERLIN"];
+        let party = decompose("50F", &lines);
+        assert_eq!(
+            parts(&party, &lines),
+            [
+                ("Identifier Code", "NIDN"),
+                ("Identifier Country", "DE"),
+                ("Identifier", "121231234342"),
+                ("Name", "JOHN DOE"),
+                ("Address", "HAUPTSTRASSE 1"),
+                ("Country", "DE"),
+                ("Town", "BERLIN"),
+            ]
+        );
+        assert!(party.problems.is_empty());
+
+        let lines = ["/12345", "1/JOHN DOE", "4/19800230", "2/MAIN ST", "9/X"];
+        let party = decompose("50F", &lines);
+        let problems: Vec<(&str, Option<usize>)> =
+            party.problems.iter().map(|(message, line)| (message.as_str(), *line)).collect();
+        assert_eq!(
+            problems,
+            [
+                ("4/ must be a date of birth (YYYYMMDD)", Some(2)),
+                ("line numbers must be in ascending order", Some(3)),
+                ("line number 9 is not allowed", Some(4)),
+                ("2/ (address) requires a 3/ (country and town) line", None),
+                ("4/ (date of birth) and 5/ (place of birth) go together", None),
+            ]
+        );
+
+        // Option F accounts have no D/C mark
+        let lines = ["/D/12345", "1/JANE DOE"];
+        let party = decompose("59F", &lines);
+        assert_eq!(parts(&party, &lines), [("Account", "D/12345"), ("Name", "JANE DOE")]);
+
+        let party = decompose("59F", &["1/JANE DOE", "4/19800101"]);
+        assert_eq!(party.problems[0].0, "line number 4 is not allowed");
+        assert_eq!(decompose("50F", &["XXXX/DE/1", "1/A"]).problems.len(), 1);
+    }
+}
@@ -0,0 +1,1052 @@
+//! Fixed-Width Parser
+//!
+//! Slices each line into fields according to `ParserConfig.field_definitions`.
+//! Field `start`/`length` are 0-based character offsets within the line. Lines end
+//! at LF or, in EBCDIC input, NEL.
+//!
+//! Fixed-length files (RECFM=F) have no line breaks: with `ParserConfig.record_length`
+//! every record is that many characters, and a record's "line" is its 1-based number.
+//! Layouts with packed or binary fields always use this mode, since their bytes may
+//! be anything, including 0x0A, 0x0D or NEL.
+//! Files mixing several layouts select one per line through `ParserConfig.record_layouts`
+//! and the code found at `ParserConfig.discriminator`.
+//!
+//! Numeric fields may use COBOL storage (`usage` zoned/packed/binary, implied `scale`)
+//! or be numeric-edited (`usage` edited, read against the PIC in `format`);
+//! packed and binary fields need a single-byte decoding so each character is one byte;
+//! the original bytes are recovered through `ParserConfig.encoding` (e.g. CP037).
+//! Display numbers may be localized (`1.234,56`, `1 234,56-`) per
+//! `ParserConfig.decimal_separator`, or detected from each value when unset.
+
+use crate::clock;
+use crate::copybook;
+use crate::dates;
+use crate::decimal::Decimal;
+use crate::encoding::Encoding;
+use crate::numbers::NumberFormat;
+use crate::totals;
+use crate::types::*;
+use crate::utils::generate_id;
+
+/// Parse fixed-width data
+pub fn parse_fixed_width(data: &str, config: &ParserConfig) -> Result<ParsedData, ParseError> {
+    let start_time = clock::now();
+    let total_bytes = data.len();
+
+    let layouts = resolve_layouts(config)?;
+    let options = FieldOptions::from_config(config);
+    let record_length = layouts.record_length(config)?;
+
+    let mut records = Vec::new();
+
+    for (line_number, (line_offset, line)) in split_records(data, record_length).enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record = parse_line(
+            records.len(),
+            line,
+            line_number + 1,
+            line_offset,
+            &layouts,
+            &options,
+        );
+        records.push(record);
+    }
+
+    let diagnostics = totals::check_records(&mut records, config)?;
+    let invalid_count = records.iter().filter(|r| !r.is_valid).count();
+    let valid_count = records.len() - invalid_count;
+    let end_time = clock::now();
+
+    Ok(ParsedData {
+        id: generate_id("parsed"),
+        config: config.clone(),
+        records,
+        headers: Some(layouts.headers()),
+        columns: None,
+        metadata: ParseMetadata {
+            total_records: valid_count + invalid_count,
+            valid_records: valid_count,
+            invalid_records: invalid_count,
+            parse_time: end_time - start_time,
+            file_size: Some(total_bytes),
+            parser_engine: "wasm".to_string(),
+            diagnostics,
+            ..Default::default()
+        },
+    })
+}
+
+/// Parse one non-blank line with the layout it selects
+pub(crate) fn parse_line(
+    index: usize,
+    line: &str,
+    line_number: usize,
+    line_offset: usize,
+    layouts: &Layouts,
+    options: &FieldOptions,
+) -> ParsedRecord {
+    match layouts {
+        Layouts::Single(definitions) => {
+            let location = (line_number, line_offset);
+            create_record(index, line, location, definitions, "data", options)
+        }
+        Layouts::Multi {
+            discriminator,
+            layouts,
+        } => {
+            let (start, end) = slice_bounds(line, discriminator.start, discriminator.length);
+            let code = line[start..end].trim();
+            match layouts
+                .iter()
+                .find(|l| l.discriminator_value.trim() == code)
+            {
+                Some(layout) => create_record(
+                    index,
+                    line,
+                    (line_number, line_offset),
+                    &layout.field_definitions,
+                    &layout.record_type,
+                    options,
+                ),
+                None => unknown_layout_record(
+                    index,
+                    line,
+                    (line_number, line_offset),
+                    code,
+                    discriminator,
+                ),
+            }
+        }
+    }
+}
+
+/// Field layouts to apply to each line
+pub(crate) enum Layouts<'a> {
+    /// Every line uses `field_definitions`
+    Single(&'a [FieldDefinition]),
+    /// Lines are matched to a `RecordLayout` by their discriminator code
+    Multi {
+        discriminator: &'a Discriminator,
+        layouts: &'a [RecordLayout],
+    },
+}
+
+impl Layouts<'_> {
+    /// Field definitions of all layouts
+    fn definitions(&self) -> Box<dyn Iterator<Item = &FieldDefinition> + '_> {
+        match self {
+            Layouts::Single(definitions) => Box::new(definitions.iter()),
+            Layouts::Multi { layouts, .. } => {
+                Box::new(layouts.iter().flat_map(|l| l.field_definitions.iter()))
+            }
+        }
+    }
+
+    /// Field names across all layouts, in declaration order without duplicates
+    pub(crate) fn headers(&self) -> Vec<String> {
+        let mut headers: Vec<String> = Vec::new();
+        for def in self.definitions() {
+            if !headers.contains(&def.name) {
+                headers.push(def.name.clone());
+            }
+        }
+        headers
+    }
+
+    /// Characters per record in fixed-length mode, or `None` to split lines
+    ///
+    /// Without `record_length`, layouts with packed or binary fields are read as
+    /// records of their full length.
+    pub(crate) fn record_length(&self, config: &ParserConfig) -> Result<Option<usize>, ParseError> {
+        match config.record_length {
+            Some(0) => Err(ParseError::ConfigError(
+                "recordLength must be greater than 0".to_string(),
+            )),
+            Some(length) => Ok(Some(length)),
+            None => {
+                let binary = self
+                    .definitions()
+                    .any(|def| matches!(def.usage.as_deref(), Some("packed" | "binary")));
+                let length = self.definitions().map(|def| def.start + def.length).max();
+                Ok(length.filter(|_| binary))
+            }
+        }
+    }
+}
+
+/// Pick multi-layout mode when `record_layouts` is set, else plain `field_definitions`
+pub(crate) fn resolve_layouts(config: &ParserConfig) -> Result<Layouts<'_>, ParseError> {
+    if let Some(layouts) = config.record_layouts.as_deref().filter(|l| !l.is_empty()) {
+        let discriminator = config.discriminator.as_ref().ok_or_else(|| {
+            ParseError::ConfigError("recordLayouts require a discriminator".to_string())
+        })?;
+        return Ok(Layouts::Multi {
+            discriminator,
+            layouts,
+        });
+    }
+
+    config
+        .field_definitions
+        .as_deref()
+        .filter(|defs| !defs.is_empty())
+        .map(Layouts::Single)
+        .ok_or_else(|| {
+            ParseError::ConfigError(
+                "fixed-width parsing requires fieldDefinitions or recordLayouts".to_string(),
+            )
+        })
+}
+
+/// File-wide settings for converting field values
+pub(crate) struct FieldOptions {
+    /// Single-byte encoding the data was decoded from, used to recover binary field bytes
+    encoding: Option<Encoding>,
+    /// Configured number separators; detected per value when `None`
+    numbers: Option<NumberFormat>,
+}
+
+impl FieldOptions {
+    pub(crate) fn from_config(config: &ParserConfig) -> Self {
+        Self {
+            encoding: config
+                .encoding
+                .as_deref()
+                .and_then(Encoding::from_label)
+                .filter(|e| !matches!(e, Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be)),
+            numbers: NumberFormat::from_config(config),
+        }
+    }
+}
+
+/// Invalid record for a line whose discriminator matches no layout
+fn unknown_layout_record(
+    index: usize,
+    line: &str,
+    (line_number, line_offset): (usize, usize),
+    code: &str,
+    discriminator: &Discriminator,
+) -> ParsedRecord {
+    let (start, end) = slice_bounds(line, discriminator.start, discriminator.length);
+    let diagnostic = Diagnostic::error(
+        DiagnosticCode::UnknownRecordLayout,
+        format!(
+            "Unknown record type \"{}\" at columns {}-{}: no matching record layout",
+            code,
+            discriminator.start + 1,
+            discriminator.start + discriminator.length
+        ),
+    )
+    .at(line_number, discriminator.start + 1)
+    .with_span(line_offset + start, line_offset + end);
+
+    let mut record = ParsedRecord {
+        id: format!("record-{}", index),
+        index,
+        fields: vec![],
+        raw: line.to_string(),
+        record_type: "data".to_string(),
+        is_valid: false,
+        errors: None,
+        position: Some(line_position(line, line_number, line_offset)),
+        diagnostics: None,
+    };
+    record.add_diagnostics(vec![diagnostic]);
+    record
+}
+
+/// Characters ending a line: LF, and NEL from EBCDIC input
+pub(crate) const LINE_ENDS: [char; 2] = ['\n', '\u{85}'];
+
+/// Split data into records with their byte offsets: lines (line endings stripped),
+/// or `record_length` characters each in fixed-length mode
+pub(crate) fn split_records(
+    data: &str,
+    record_length: Option<usize>,
+) -> Box<dyn Iterator<Item = (usize, &str)> + '_> {
+    let mut offset = 0usize;
+    match record_length {
+        Some(length) => {
+            let mut rest = data;
+            Box::new(std::iter::from_fn(move || {
+                if rest.is_empty() {
+                    return None;
+                }
+                let end = rest.char_indices().nth(length).map_or(rest.len(), |(i, _)| i);
+                let (record, tail) = rest.split_at(end);
+                let record_offset = offset;
+                offset += end;
+                rest = tail;
+                Some((record_offset, record))
+            }))
+        }
+        None => Box::new(data.split_inclusive(LINE_ENDS).map(move |line| {
+            let line_offset = offset;
+            offset += line.len();
+            let line = line.strip_suffix(LINE_ENDS).unwrap_or(line);
+            (line_offset, line.strip_suffix('\r').unwrap_or(line))
+        })),
+    }
+}
+
+/// Create a parsed record from one line
+///
+/// `location` is the line's 1-based line number and byte offset in the input.
+fn create_record(
+    index: usize,
+    line: &str,
+    (line_number, line_offset): (usize, usize),
+    definitions: &[FieldDefinition],
+    record_type: &str,
+    options: &FieldOptions,
+) -> ParsedRecord {
+    let mut fields = Vec::with_capacity(definitions.len());
+    let mut diagnostics = Vec::new();
+
+    for def in definitions {
+        let (start, end) = slice_bounds(line, def.start, def.length);
+        let raw_value = &line[start..end];
+        let trimmed = raw_value.trim();
+        let field_id = format!("field-{}-{}", index, def.id);
+        let locate = |diagnostic: Diagnostic| {
+            diagnostic
+                .at(line_number, def.start + 1)
+                .with_span(line_offset + start, line_offset + end)
+                .with_field(field_id.clone())
+        };
+
+        if def.required && trimmed.is_empty() {
+            diagnostics.push(locate(Diagnostic::error(
+                DiagnosticCode::FieldRequired,
+                format!("Required field \"{}\" is empty", def.name),
+            )));
+        }
+
+        let value = match convert_value(raw_value, def, options) {
+            Ok(value) => value,
+            Err(e) => {
+                diagnostics.push(locate(Diagnostic::error(DiagnosticCode::FieldConversion, e)));
+                FieldValue::String(trimmed.to_string())
+            }
+        };
+
+        fields.push(ParsedField {
+            id: field_id,
+            name: def.name.clone(),
+            value,
+            field_type: def.field_type.clone(),
+            original_value: raw_value.to_string(),
+            position: Some(Position {
+                start: line_offset + start,
+                end: line_offset + end,
+                line: Some(line_number),
+                column: Some(line[..start].chars().count() + 1),
+            }),
+        });
+    }
+
+    // Missing columns were read as blank; say so rather than padding silently
+    let layout_length = definitions.iter().map(|def| def.start + def.length).max();
+    let length = line.chars().count();
+    if let Some(layout_length) = layout_length.filter(|&l| length < l) {
+        let end = line_offset + line.len();
+        diagnostics.push(
+            Diagnostic::warning(
+                DiagnosticCode::ShortRecord,
+                format!(
+                    "Record has {} characters, the layout needs {}; missing columns read as blank",
+                    length, layout_length
+                ),
+            )
+            .at(line_number, length + 1)
+            .with_span(end, end),
+        );
+    }
+
+    let mut record = ParsedRecord {
+        id: format!("record-{}", index),
+        index,
+        fields,
+        raw: line.to_string(),
+        record_type: record_type.to_string(),
+        is_valid: true,
+        errors: None,
+        position: Some(line_position(line, line_number, line_offset)),
+        diagnostics: None,
+    };
+    record.add_diagnostics(diagnostics);
+    record
+}
+
+/// Span of a whole line
+fn line_position(line: &str, line_number: usize, line_offset: usize) -> Position {
+    Position {
+        start: line_offset,
+        end: line_offset + line.len(),
+        line: Some(line_number),
+        column: Some(1),
+    }
+}
+
+/// Byte range of `length` characters starting at character `start`, clamped to the line
+fn slice_bounds(line: &str, start: usize, length: usize) -> (usize, usize) {
+    if line.is_ascii() {
+        let begin = start.min(line.len());
+        return (begin, (start + length).min(line.len()));
+    }
+
+    let byte_at = |char_idx: usize| {
+        line.char_indices()
+            .nth(char_idx)
+            .map(|(i, _)| i)
+            .unwrap_or(line.len())
+    };
+    (byte_at(start), byte_at(start + length))
+}
+
+/// Convert a raw field slice according to the field definition's type and usage
+fn convert_value(
+    raw_value: &str,
+    def: &FieldDefinition,
+    options: &FieldOptions,
+) -> Result<FieldValue, String> {
+    match def.usage.as_deref() {
+        Some("packed") => return decode_packed(raw_value, def, options.encoding),
+        Some("binary") => return decode_binary(raw_value, def, options.encoding),
+        Some("edited") => return decode_edited(raw_value, def),
+        _ => {}
+    }
+
+    let value = raw_value.trim();
+    if value.is_empty() {
+        return Ok(FieldValue::Null);
+    }
+
+    match def.field_type.as_str() {
+        "number" if def.usage.as_deref() == Some("zoned") || def.signed || def.scale.is_some() => {
+            decode_zoned(value, def)
+        }
+        "number" => {
+            let numbers = options
+                .numbers
+                .unwrap_or_else(|| NumberFormat::detect([value]));
+            if let Ok(n) = value.parse::<i64>() {
+                Ok(FieldValue::Integer(n))
+            } else if let Some(d) = numbers.parse(value) {
+                Ok(d.to_i64().map_or(FieldValue::Decimal(d), FieldValue::Integer))
+            } else if let Ok(n) = value.parse::<f64>() {
+                Ok(FieldValue::Number(n))
+            } else {
+                Err(format!(
+                    "Field \"{}\" value \"{}\" is not a valid number",
+                    def.name, value
+                ))
+            }
+        }
+        // Without a format, ISO 8601 or the mainframe-style YYYYMMDD
+        "date" => match def.format.as_deref() {
+            Some(format) => dates::parse_with_format(value, format),
+            None => dates::parse_iso(value).or_else(|| dates::parse_with_format(value, "%Y%m%d")),
+        }
+        .ok_or_else(|| {
+            format!(
+                "Field \"{}\" value \"{}\" is not a valid date{}",
+                def.name,
+                value,
+                def.format
+                    .as_ref()
+                    .map(|f| format!(" (expected {})", f))
+                    .unwrap_or_default()
+            )
+        }),
+        "boolean" => match value.to_lowercase().as_str() {
+            "true" | "yes" | "y" | "1" => Ok(FieldValue::Boolean(true)),
+            "false" | "no" | "n" | "0" => Ok(FieldValue::Boolean(false)),
+            _ => Err(format!(
+                "Field \"{}\" value \"{}\" is not a valid boolean",
+                def.name, value
+            )),
+        },
+        _ => Ok(FieldValue::String(value.to_string())),
+    }
+}
+
+/// Decode zoned decimal digits with an overpunched or separate sign and implied decimals
+fn decode_zoned(value: &str, def: &FieldDefinition) -> Result<FieldValue, String> {
+    let invalid = || {
+        format!(
+            "Field \"{}\" value \"{}\" is not a valid zoned decimal",
+            def.name, value
+        )
+    };
+
+    let mut negative = false;
+    let mut body = value;
+    if let Some(rest) = body.strip_prefix('-').or_else(|| body.strip_suffix('-')) {
+        negative = true;
+        body = rest;
+    } else if let Some(rest) = body.strip_prefix('+').or_else(|| body.strip_suffix('+')) {
+        body = rest;
+    }
+
+    let mut digits: Vec<char> = body.trim().chars().collect();
+    for idx in [digits.len().wrapping_sub(1), 0] {
+        if let Some((digit, is_negative)) = digits.get(idx).and_then(|&c| overpunch(c)) {
+            digits[idx] = digit;
+            negative |= is_negative;
+            break;
+        }
+    }
+
+    if digits.is_empty() || !digits.iter().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    scaled_value(
+        &digits.into_iter().collect::<String>(),
+        negative,
+        def.scale.unwrap_or(0),
+    )
+    .ok_or_else(invalid)
+}
+
+/// Map an overpunched sign character to its digit and sign
+fn overpunch(c: char) -> Option<(char, bool)> {
+    match c {
+        '{' => Some(('0', false)),
+        'A'..='I' => Some(((b'1' + (c as u8 - b'A')) as char, false)),
+        '}' => Some(('0', true)),
+        'J'..='R' => Some(((b'1' + (c as u8 - b'J')) as char, true)),
+        _ => None,
+    }
+}
+
+/// Decode a numeric-edited field (`ZZ,ZZ9.99-`, `$$$9.99`, `9(5)CR`) by reading each
+/// character against its PIC symbol; a blank field is null (`BLANK WHEN ZERO`)
+fn decode_edited(raw_value: &str, def: &FieldDefinition) -> Result<FieldValue, String> {
+    let invalid = || {
+        format!(
+            "Field \"{}\" value \"{}\" does not match PIC {}",
+            def.name,
+            raw_value.trim(),
+            def.format.as_deref().unwrap_or("")
+        )
+    };
+    if raw_value.trim().is_empty() {
+        return Ok(FieldValue::Null);
+    }
+    let picture: Vec<char> = def
+        .format
+        .as_deref()
+        .and_then(copybook::expand_picture)
+        .ok_or_else(invalid)?
+        .chars()
+        .collect();
+
+    let mut chars = raw_value.chars();
+    let mut digits = String::new();
+    let mut negative = false;
+    let mut scale = None;
+    let mut i = 0;
+    while i < picture.len() {
+        let symbol = picture[i];
+        i += 1;
+        match (symbol, picture.get(i)) {
+            ('V', _) => scale = Some(0),
+            ('S' | 'P', _) => {}
+            // CR and DB show the sign in two characters, blank when positive
+            ('C', Some('R')) | ('D', Some('B')) => {
+                i += 1;
+                let shown: String = chars.by_ref().take(2).collect();
+                match shown.to_uppercase().as_str() {
+                    "CR" | "DB" => negative = true,
+                    "  " => {}
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => {
+                let c = chars.next().ok_or_else(invalid)?;
+                match symbol {
+                    '9' | 'Z' | '*' | '+' | '-' | '$' if c.is_ascii_digit() => {
+                        digits.push(c);
+                        scale = scale.map(|s| s + 1);
+                    }
+                    '+' | '-' if c == '-' => negative = true,
+                    // Suppressed zeros are only blank before the first digit
+                    'Z' | '*' if matches!(c, ' ' | '*') => {
+                        if scale.is_some() {
+                            digits.push('0');
+                            scale = scale.map(|s| s + 1);
+                        }
+                    }
+                    '+' | '-' | '$' if matches!(c, ' ' | '+' | '$') => {}
+                    '.' if c == '.' => scale = Some(0),
+                    // Insertion characters, blanked or starred while zeros are suppressed
+                    ',' | '0' | '/' if c == symbol => {}
+                    'B' if c == ' ' => {}
+                    ',' | 'B' | '0' | '/' if digits.is_empty() && matches!(c, ' ' | '*') => {}
+                    _ => return Err(invalid()),
+                }
+            }
+        }
+    }
+    if digits.is_empty() || chars.any(|c| c != ' ') {
+        return Err(invalid());
+    }
+
+    scaled_value(&digits, negative, scale.unwrap_or(0)).ok_or_else(invalid)
+}
+
+/// Raw bytes of a binary field; each character must map back to one byte
+fn field_bytes(
+    raw_value: &str,
+    def: &FieldDefinition,
+    encoding: Option<Encoding>,
+) -> Result<Vec<u8>, String> {
+    raw_value
+        .chars()
+        .map(|c| match encoding {
+            Some(encoding) => encoding.encode_char(c),
+            None => u8::try_from(c as u32).ok(),
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| {
+            format!(
+                "Field \"{}\" contains characters outside a single-byte encoding",
+                def.name
+            )
+        })
+}
+
+/// Decode a packed decimal (COMP-3) field
+fn decode_packed(
+    raw_value: &str,
+    def: &FieldDefinition,
+    encoding: Option<Encoding>,
+) -> Result<FieldValue, String> {
+    let bytes = field_bytes(raw_value, def, encoding)?;
+    if bytes.is_empty() {
+        return Ok(FieldValue::Null);
+    }
+
+    let mut digits = String::with_capacity(bytes.len() * 2);
+    for byte in &bytes {
+        digits.push(char::from(b'0' + (byte >> 4)));
+        digits.push(char::from(b'0' + (byte & 0x0F)));
+    }
+    let sign = digits.pop().map(|c| c as u8 - b'0').unwrap_or(0x0F);
+
+    let negative = match sign {
+        0x0D | 0x0B => true,
+        0x0C | 0x0F | 0x0A | 0x0E => false,
+        _ => {
+            return Err(format!(
+                "Field \"{}\" has an invalid packed sign nibble",
+                def.name
+            ))
+        }
+    };
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!(
+            "Field \"{}\" is not a valid packed decimal",
+            def.name
+        ));
+    }
+
+    scaled_value(&digits, negative, def.scale.unwrap_or(0))
+        .ok_or_else(|| format!("Field \"{}\" is not a valid packed decimal", def.name))
+}
+
+/// Decode a big-endian binary (COMP) field
+fn decode_binary(
+    raw_value: &str,
+    def: &FieldDefinition,
+    encoding: Option<Encoding>,
+) -> Result<FieldValue, String> {
+    let bytes = field_bytes(raw_value, def, encoding)?;
+    if bytes.is_empty() || bytes.len() > 8 {
+        return Err(format!(
+            "Field \"{}\" has an unsupported binary length of {} bytes",
+            def.name,
+            bytes.len()
+        ));
+    }
+
+    let unsigned = bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+    let (negative, magnitude) = if def.signed {
+        // Sign-extend the two's complement value from the field's width
+        let shift = 64 - bytes.len() * 8;
+        let value = ((unsigned << shift) as i64) >> shift;
+        (value < 0, value.unsigned_abs())
+    } else {
+        (false, unsigned)
+    };
+
+    scaled_value(&magnitude.to_string(), negative, def.scale.unwrap_or(0))
+        .ok_or_else(|| format!("Field \"{}\" is not a valid binary number", def.name))
+}
+
+/// Build a numeric value from unsigned digits, a sign and implied decimal places
+///
+/// Whole numbers that fit become integers; anything with a scale stays an exact decimal.
+fn scaled_value(digits: &str, negative: bool, scale: u32) -> Option<FieldValue> {
+    if scale == 0 {
+        let sign = if negative { "-" } else { "" };
+        if let Ok(n) = format!("{}{}", sign, digits).parse::<i64>() {
+            return Some(FieldValue::Integer(n));
+        }
+    }
+    Decimal::from_scaled(digits, negative, scale).map(FieldValue::Decimal)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn definition(
+        id: &str,
+        start: usize,
+        length: usize,
+        field_type: &str,
+        required: bool,
+    ) -> FieldDefinition {
+        FieldDefinition {
+            id: id.to_string(),
+            name: id.to_uppercase(),
+            start,
+            length,
+            field_type: field_type.to_string(),
+            required,
+            ..Default::default()
+        }
+    }
+
+    fn options() -> FieldOptions {
+        FieldOptions::from_config(&ParserConfig::default())
+    }
+
+    fn config() -> ParserConfig {
+        ParserConfig {
+            parser_type: "fixed-width".to_string(),
+            field_definitions: Some(vec![
+                definition("id", 0, 5, "string", true),
+                definition("name", 5, 20, "string", false),
+                definition("amount", 25, 10, "number", false),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_fixed_width() {
+        let data = "00001John Doe            0000150000\r\n00002Jane Smith          0000230050\n";
+        let result = parse_fixed_width(data, &config()).unwrap();
+
+        assert_eq!(result.records.len(), 2);
+        let fields = &result.records[1].fields;
+        assert!(matches!(&fields[1].value, FieldValue::String(s) if s == "Jane Smith"));
+        assert!(matches!(fields[2].value, FieldValue::Integer(230050)));
+        let position = fields[0].position.as_ref().unwrap();
+        assert_eq!(&data[position.start..position.end], "00002");
+    }
+
+    #[test]
+    fn test_required_and_type_errors() {
+        let data = "     John Doe            12AB";
+        let result = parse_fixed_width(data, &config()).unwrap();
+
+        let record = &result.records[0];
+        assert!(!record.is_valid);
+        assert_eq!(record.errors.as_ref().unwrap().len(), 2);
+        assert_eq!(result.metadata.invalid_records, 1);
+
+        let diagnostics = record.diagnostics.as_ref().unwrap();
+        assert_eq!(diagnostics[0].code, DiagnosticCode::FieldRequired);
+        assert_eq!(diagnostics[0].field_id.as_deref(), Some("field-0-id"));
+        assert_eq!((diagnostics[1].line, diagnostics[1].column), (Some(1), Some(26)));
+    }
+
+    #[test]
+    fn test_short_line_warning() {
+        let data = "00001John Doe            0000000042\n00002Jane";
+        let result = parse_fixed_width(data, &config()).unwrap();
+
+        assert!(result.records[0].diagnostics.is_none());
+        let record = &result.records[1];
+        assert!(record.is_valid, "{:?}", record.errors);
+        let diagnostics = record.diagnostics.as_ref().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, DiagnosticCode::ShortRecord);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (Some(2), Some(10)));
+        assert!(diagnostics[0].message.contains("9 characters, the layout needs 35"));
+    }
+
+    #[test]
+    fn test_missing_definitions_is_config_error() {
+        let config = ParserConfig {
+            parser_type: "fixed-width".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            parse_fixed_width("abc", &config),
+            Err(ParseError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_multi_layout_discriminator() {
+        let layout = |id: &str, code: &str, record_type: &str, defs| RecordLayout {
+            id: id.to_string(),
+            name: id.to_string(),
+            discriminator_value: code.to_string(),
+            record_type: record_type.to_string(),
+            field_definitions: defs,
+        };
+        let config = ParserConfig {
+            parser_type: "fixed-width".to_string(),
+            discriminator: Some(Discriminator {
+                start: 0,
+                length: 2,
+            }),
+            record_layouts: Some(vec![
+                layout(
+                    "hdr",
+                    "01",
+                    "header",
+                    vec![definition("date", 2, 8, "date", true)],
+                ),
+                layout(
+                    "dtl",
+                    "04",
+                    "transaction",
+                    vec![definition("amount", 2, 6, "number", true)],
+                ),
+                layout(
+                    "trl",
+                    "07",
+                    "footer",
+                    vec![definition("count", 2, 3, "number", true)],
+                ),
+            ]),
+            ..Default::default()
+        };
+        let data = "0120240115\n04001250\n99garbage\n07002";
+        let result = parse_fixed_width(data, &config).unwrap();
+
+        let types: Vec<&str> = result
+            .records
+            .iter()
+            .map(|r| r.record_type.as_str())
+            .collect();
+        assert_eq!(types, ["header", "transaction", "data", "footer"]);
+        assert!(matches!(
+            result.records[0].fields[0].value,
+            FieldValue::Date(d) if d.to_string() == "2024-01-15"
+        ));
+        assert!(matches!(
+            result.records[1].fields[0].value,
+            FieldValue::Integer(1250)
+        ));
+        assert!(!result.records[2].is_valid);
+        let errors = result.records[2].errors.as_ref().unwrap();
+        assert!(errors[0].contains("\"99\" at columns 1-2"), "{}", errors[0]);
+        assert_eq!(result.records[2].diagnostics.as_ref().unwrap()[0].column, Some(1));
+        assert_eq!(result.headers.unwrap(), ["DATE", "AMOUNT", "COUNT"]);
+    }
+
+    #[test]
+    fn test_packed_and_zoned_decoding() {
+        let packed = FieldDefinition {
+            usage: Some("packed".to_string()),
+            scale: Some(2),
+            signed: true,
+            ..definition("balance", 0, 3, "number", false)
+        };
+        let zoned = FieldDefinition {
+            usage: Some("zoned".to_string()),
+            scale: Some(2),
+            signed: true,
+            ..definition("rate", 3, 5, "number", false)
+        };
+
+        // 0x12 0x34 0x5D = -123.45 packed, "0012J" = -1.21 zoned (J = -1)
+        let line: String = [0x12u8, 0x34, 0x5D].iter().map(|&b| b as char).collect();
+        let line = format!("{}0012J", line);
+        let record = create_record(0, &line, (1, 0), &[packed, zoned], "data", &options());
+
+        assert!(record.is_valid, "{:?}", record.errors);
+        assert!(matches!(&record.fields[0].value, FieldValue::Decimal(d) if d.to_string() == "-123.45"));
+        assert!(matches!(&record.fields[1].value, FieldValue::Decimal(d) if d.to_string() == "-1.21"));
+    }
+
+    #[test]
+    fn test_binary_widths() {
+        let value = |bytes: &[u8], signed: bool| {
+            let def = FieldDefinition {
+                usage: Some("binary".to_string()),
+                signed,
+                ..definition("count", 0, bytes.len(), "number", false)
+            };
+            let line: String = bytes.iter().map(|&b| b as char).collect();
+            let record = create_record(0, &line, (1, 0), &[def], "data", &options());
+            assert!(record.is_valid, "{:?}", record.errors);
+            serde_json::to_value(&record.fields[0].value).unwrap().to_string()
+        };
+
+        assert_eq!(value(&[0xFF, 0xFE], true), "-2");
+        assert_eq!(value(&[0xFF, 0xFE], false), "65534");
+        assert_eq!(value(&[0xFF; 8], true), "-1");
+        // Unsigned doublewords above i64::MAX stay positive
+        assert_eq!(value(&[0xFF; 8], false), "\"18446744073709551615\"");
+        assert_eq!(value(&[0x80, 0, 0, 0, 0, 0, 0, 0], true), "-9223372036854775808");
+    }
+
+    /// Packed S9(3), binary 9(4) and a 2-character code: 6 bytes per record
+    pub(crate) fn binary_config() -> ParserConfig {
+        ParserConfig {
+            parser_type: "fixed-width".to_string(),
+            field_definitions: Some(vec![
+                FieldDefinition {
+                    usage: Some("packed".to_string()),
+                    signed: true,
+                    ..definition("amount", 0, 2, "number", false)
+                },
+                FieldDefinition {
+                    usage: Some("binary".to_string()),
+                    ..definition("count", 2, 2, "number", false)
+                },
+                definition("code", 4, 2, "string", false),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fixed_length_records() {
+        let values = |result: &ParsedData| -> Vec<String> {
+            let json = serde_json::to_value(&result.records).unwrap();
+            let records = json.as_array().unwrap();
+            let fields = records.iter().flat_map(|r| r["fields"].as_array().unwrap().clone());
+            fields.map(|f| f["value"].to_string()).collect()
+        };
+
+        // -10 packed ends in 0x0D, and binary 10 is 0x0A: neither may end a record
+        let bytes = [0x01, 0x0D, 0x00, 0x0A, b'A', b'B', 0x12, 0x3C, 0x0D, 0x0A, b'C', b'D'];
+        let data: String = bytes.iter().map(|&b| b as char).collect();
+        let result = parse_fixed_width(&data, &binary_config()).unwrap();
+        assert_eq!(values(&result), ["-10", "10", "\"AB\"", "123", "3338", "\"CD\""]);
+        let position = result.records[1].position.as_ref().unwrap();
+        assert_eq!((position.start, position.line), (6, Some(2)));
+
+        // In CP037, 0x15 (NEL) and 0x25 (LF) are binary bytes too
+        let config = ParserConfig {
+            encoding: Some("cp037".to_string()),
+            ..binary_config()
+        };
+        let bytes = [0x02, 0x5D, 0x00, 0x15, 0xD6, 0xD2, 0x00, 0x1C, 0x00, 0x25, 0xD6, 0xD2];
+        let result = crate::parse_bytes(&bytes, &config).unwrap();
+        assert_eq!(values(&result), ["-25", "21", "\"OK\"", "1", "37", "\"OK\""]);
+
+        // Text layouts still split lines, at NEL as well as LF
+        let config = ParserConfig {
+            encoding: Some("cp037".to_string()),
+            field_definitions: Some(vec![definition("code", 0, 2, "string", false)]),
+            ..binary_config()
+        };
+        let result = crate::parse_bytes(&[0xC1, 0xC2, 0x15, 0xC3, 0xC4, 0x25], &config).unwrap();
+        assert_eq!(values(&result), ["\"AB\"", "\"CD\""]);
+
+        let config = ParserConfig {
+            record_length: Some(0),
+            ..binary_config()
+        };
+        assert!(matches!(parse_fixed_width("", &config), Err(ParseError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_edited_numbers() {
+        let value = |picture: &str, text: &str| {
+            let def = FieldDefinition {
+                usage: Some("edited".to_string()),
+                format: Some(picture.to_string()),
+                ..definition("amount", 0, text.chars().count(), "number", false)
+            };
+            let record = create_record(0, text, (1, 0), &[def], "data", &options());
+            match record.errors {
+                Some(errors) => errors[0].clone(),
+                None => serde_json::to_value(&record.fields[0].value).unwrap().to_string(),
+            }
+        };
+
+        assert_eq!(value("ZZ,ZZ9.99", " 1,234.50"), "\"1234.50\"");
+        assert_eq!(value("ZZ9.99", "  0.05"), "\"0.05\"");
+        assert_eq!(value("-9(5)", "-00042"), "-42");
+        assert_eq!(value("$$,$$9.99CR", "   $12.00CR"), "\"-12.00\"");
+        assert_eq!(value("+++9", "  -7"), "-7");
+        assert_eq!(value("9(3)DB", "015  "), "15");
+        assert_eq!(value("**9.99", "**1.25"), "\"1.25\"");
+        assert_eq!(value("99/99/99", "24/01/15"), "240115");
+        assert_eq!(value("ZZ9.99", "      "), "null");
+        assert_eq!(value("ZZ,ZZ9", "    12"), "12");
+        assert_eq!(value("99B99", "12 34"), "1234");
+        assert_eq!(
+            value("ZZ9.99", " 1x.00"),
+            "Field \"AMOUNT\" value \"1x.00\" does not match PIC ZZ9.99"
+        );
+
+        // Insertion positions must hold their own character
+        for (picture, text) in [
+            ("9.99", "1x23"),
+            ("99,999", "12X345"),
+            ("99/99", "12-34"),
+            ("99B99", "12X34"),
+            ("9909", "1219"),
+        ] {
+            assert!(value(picture, text).contains("does not match PIC"), "{} {}", picture, text);
+        }
+    }
+
+    #[test]
+    fn test_localized_numbers() {
+        let def = definition("amount", 0, 12, "number", false);
+        let value = |line: &str, options: &FieldOptions| {
+            let defs = std::slice::from_ref(&def);
+            let record = create_record(0, line, (1, 0), defs, "data", options);
+            serde_json::to_value(&record.fields[0].value).unwrap()
+        };
+
+        // Detected per value: a comma followed by two digits is a decimal mark
+        assert_eq!(value("  1.234,56- ", &options()), "-1234.56");
+        assert_eq!(value("   1,234,567", &options()), 1234567);
+
+        let config = ParserConfig {
+            decimal_separator: Some(",".to_string()),
+            thousands_separator: Some(" ".to_string()),
+            ..Default::default()
+        };
+        let european = FieldOptions::from_config(&config);
+        assert_eq!(value("(1 234,500)", &european), "-1234.500");
+        assert_eq!(value("  12,50 EUR", &european), "12.50");
+    }
+
+    #[test]
+    fn test_date_format() {
+        let def = FieldDefinition {
+            format: Some("DD.MM.YY".to_string()),
+            ..definition("booked", 0, 8, "date", false)
+        };
+        let defs = std::slice::from_ref(&def);
+        let record = create_record(0, "15.01.24", (1, 0), defs, "data", &options());
+        assert!(matches!(record.fields[0].value, FieldValue::Date(d) if d.to_string() == "2024-01-15"));
+
+        let record = create_record(0, "2024-01-", (1, 0), &[def], "data", &options());
+        assert_eq!(
+            record.errors.unwrap()[0],
+            "Field \"BOOKED\" value \"2024-01-\" is not a valid date (expected DD.MM.YY)"
+        );
+    }
+}
@@ -1,139 +1,77 @@
 //! High-Performance WASM Parser Library
 //!
-//! Provides 10-100x faster parsing for CSV, XML (ISO 20022), and SWIFT FIN formats.
-
-use wasm_bindgen::prelude::*;
-use serde::{Deserialize, Serialize};
+//! Provides 10-100x faster parsing for CSV, fixed-width, XML (ISO 20022), and SWIFT FIN formats.
+//!
+//! The parsing core is plain Rust and builds as a native `rlib`; the wasm-bindgen
+//! exports live in [`wasm`] behind the `wasm` feature (enabled by default).
 
+pub mod clock;
+pub mod control;
+pub mod copybook;
 pub mod csv_parser;
+pub mod dates;
+pub mod decimal;
+mod delimited;
+pub mod encoding;
+pub mod fixed_width_parser;
+pub mod numbers;
+pub mod progress;
 pub mod xml_parser;
 pub mod fin_parser;
+mod fin_subfields;
+pub mod streaming;
+pub mod swift_format;
+pub mod totals;
 pub mod types;
 pub mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-use types::{ParsedData, ParserConfig, ParseProgress};
+#[cfg(feature = "wasm")]
+pub use wasm::*;
 
-/// Initialize the WASM module
-#[wasm_bindgen(start)]
-pub fn init() {
-    // Set up panic hook for better error messages
-    #[cfg(feature = "console_error_panic_hook")]
-    console_error_panic_hook::set_once();
-}
+use types::{ParseError, ParsedData, ParserConfig};
 
-/// Check if WASM module is ready
-#[wasm_bindgen]
+/// Check if the parser module is ready
 pub fn is_ready() -> bool {
     true
 }
 
 /// Get parser version
-#[wasm_bindgen]
 pub fn get_version() -> String {
     env!("CARGO_PKG_VERSION").to_string()
 }
 
-/// Main parse function - routes to appropriate parser
-#[wasm_bindgen]
-pub fn parse(data: &str, config_js: JsValue) -> Result<JsValue, JsError> {
-    let config: ParserConfig = serde_wasm_bindgen::from_value(config_js)
-        .map_err(|e| JsError::new(&format!("Invalid config: {}", e)))?;
-
-    let result = match config.parser_type.as_str() {
-        "csv" => csv_parser::parse_csv(data, &config),
-        "iso20022" => xml_parser::parse_xml(data, &config),
-        "fin" => fin_parser::parse_fin(data, &config),
-        _ => csv_parser::parse_csv(data, &config), // Default to CSV
-    };
-
-    match result {
-        Ok(parsed) => serde_wasm_bindgen::to_value(&parsed)
-            .map_err(|e| JsError::new(&format!("Serialization error: {}", e))),
-        Err(e) => Err(JsError::new(&e.to_string())),
+/// Route data to the parser selected by `config.parser_type`
+pub fn parse_with_config(data: &str, config: &ParserConfig) -> Result<ParsedData, ParseError> {
+    match config.parser_type.as_str() {
+        "csv" => csv_parser::parse_csv(data, config),
+        "fixed-width" => fixed_width_parser::parse_fixed_width(data, config),
+        "iso20022" => xml_parser::parse_xml(data, config),
+        "fin" => fin_parser::parse_fin(data, config),
+        _ => csv_parser::parse_csv(data, config), // Default to CSV
     }
 }
 
-/// Parse CSV with streaming and progress callback
-#[wasm_bindgen]
-pub fn parse_csv_streaming(
-    data: &str,
-    config_js: JsValue,
-    progress_callback: &js_sys::Function,
-) -> Result<JsValue, JsError> {
-    let config: ParserConfig = serde_wasm_bindgen::from_value(config_js)
-        .map_err(|e| JsError::new(&format!("Invalid config: {}", e)))?;
-
-    let progress_fn = |progress: ParseProgress| {
-        if let Ok(progress_js) = serde_wasm_bindgen::to_value(&progress) {
-            let _ = progress_callback.call1(&JsValue::NULL, &progress_js);
-        }
+/// Decode raw bytes with `config.encoding` (detected when unset) and parse them
+///
+/// The encoding used is recorded in `metadata.encoding`; `file_size` is the byte length.
+pub fn parse_bytes(data: &[u8], config: &ParserConfig) -> Result<ParsedData, ParseError> {
+    let (text, encoding) = encoding::decode(data, config.encoding.as_deref())?;
+    // Fixed-width keeps NEL so binary fields can recover their bytes
+    let text = match config.parser_type.as_str() {
+        "fixed-width" => text,
+        _ => encoding::nel_as_newline(text),
     };
-
-    let result = csv_parser::parse_csv_with_progress(data, &config, progress_fn);
-
-    match result {
-        Ok(parsed) => serde_wasm_bindgen::to_value(&parsed)
-            .map_err(|e| JsError::new(&format!("Serialization error: {}", e))),
-        Err(e) => Err(JsError::new(&e.to_string())),
-    }
-}
-
-/// Parse XML (ISO 20022) with streaming
-#[wasm_bindgen]
-pub fn parse_xml_streaming(
-    data: &str,
-    config_js: JsValue,
-    progress_callback: &js_sys::Function,
-) -> Result<JsValue, JsError> {
-    let config: ParserConfig = serde_wasm_bindgen::from_value(config_js)
-        .map_err(|e| JsError::new(&format!("Invalid config: {}", e)))?;
-
-    let progress_fn = |progress: ParseProgress| {
-        if let Ok(progress_js) = serde_wasm_bindgen::to_value(&progress) {
-            let _ = progress_callback.call1(&JsValue::NULL, &progress_js);
-        }
+    let config = ParserConfig {
+        encoding: Some(encoding.label().to_string()),
+        ..config.clone()
     };
 
-    let result = xml_parser::parse_xml_with_progress(data, &config, progress_fn);
-
-    match result {
-        Ok(parsed) => serde_wasm_bindgen::to_value(&parsed)
-            .map_err(|e| JsError::new(&format!("Serialization error: {}", e))),
-        Err(e) => Err(JsError::new(&e.to_string())),
-    }
-}
-
-/// Detect parser type from data
-#[wasm_bindgen]
-pub fn detect_parser_type(data: &str) -> String {
-    utils::detect_type(data).to_string()
-}
-
-/// Suggest CSV delimiter
-#[wasm_bindgen]
-pub fn suggest_delimiter(data: &str) -> String {
-    utils::suggest_csv_delimiter(data).to_string()
-}
-
-/// Benchmark parsing speed (for testing)
-#[wasm_bindgen]
-pub fn benchmark_csv(data: &str, iterations: u32) -> f64 {
-    let config = ParserConfig::default();
-    let start = web_sys::window()
-        .and_then(|w| w.performance())
-        .map(|p| p.now())
-        .unwrap_or(0.0);
-
-    for _ in 0..iterations {
-        let _ = csv_parser::parse_csv(data, &config);
-    }
-
-    let end = web_sys::window()
-        .and_then(|w| w.performance())
-        .map(|p| p.now())
-        .unwrap_or(0.0);
-
-    (end - start) / iterations as f64
+    let mut result = parse_with_config(&text, &config)?;
+    result.metadata.encoding = Some(encoding.label().to_string());
+    result.metadata.file_size = Some(data.len());
+    Ok(result)
 }
 
 #[cfg(test)]
@@ -149,4 +87,43 @@ mod tests {
     fn test_version() {
         assert!(!get_version().is_empty());
     }
+
+    #[test]
+    fn test_parse_with_config_routes_to_csv() {
+        let config = ParserConfig::default();
+        let result = parse_with_config("a,b\n1,2", &config).unwrap();
+        assert_eq!(result.records.len(), 1);
+        assert!(result.id.starts_with("parsed-"));
+    }
+
+    #[test]
+    fn test_parse_bytes_windows_1252() {
+        let data = b"nom;montant\nSoci\xE9t\xE9 G\xE9n\xE9rale;100\n";
+        let config = ParserConfig {
+            delimiter: ";".to_string(),
+            ..Default::default()
+        };
+        let result = parse_bytes(data, &config).unwrap();
+        assert_eq!(result.metadata.encoding.as_deref(), Some("windows-1252"));
+        assert_eq!(result.records[0].fields[0].original_value, "Société Générale");
+    }
+
+    #[test]
+    fn test_parse_bytes_ebcdic_csv_lines() {
+        // "a,b", "1,2" and "3,4" in CP037, ending in NEL
+        let data = [0x81, 0x6B, 0x82, 0x15, 0xF1, 0x6B, 0xF2, 0x15, 0xF3, 0x6B, 0xF4, 0x15];
+        let config = ParserConfig {
+            encoding: Some("cp037".to_string()),
+            ..Default::default()
+        };
+        let result = parse_bytes(&data, &config).unwrap();
+        assert_eq!(result.headers.unwrap(), ["a", "b"]);
+        let values: Vec<&str> = result
+            .records
+            .iter()
+            .flat_map(|r| &r.fields)
+            .map(|f| f.original_value.as_str())
+            .collect();
+        assert_eq!(values, ["1", "2", "3", "4"]);
+    }
 }
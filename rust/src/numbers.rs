@@ -0,0 +1,275 @@
+//! Locale-Aware Numbers
+//!
+//! Bank exports write amounts the local way: `1.234,56`, `-1 234,56`, `1234,56-`,
+//! `(1,234.56)`, `€ 12,50` or `12.50 EUR`. [`NumberFormat`] strips the sign, currency
+//! and digit grouping and yields an exact [`Decimal`]; the source text is left to the
+//! caller's `original_value`.
+
+use crate::decimal::Decimal;
+use crate::types::ParserConfig;
+
+/// Decimal mark and digit grouping of localized numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    /// Grouping character; `None` accepts `,` or `.` (whichever is not the decimal
+    /// mark), spaces and `'`
+    pub thousands_separator: Option<char>,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            decimal_separator: '.',
+            thousands_separator: None,
+        }
+    }
+}
+
+impl NumberFormat {
+    /// Separators set on `ParserConfig`, or `None` to detect them from the data
+    pub fn from_config(config: &ParserConfig) -> Option<Self> {
+        let first = |s: &Option<String>| s.as_deref().and_then(|s| s.chars().next());
+        let decimal = first(&config.decimal_separator);
+        let thousands = first(&config.thousands_separator);
+        if decimal.is_none() && thousands.is_none() {
+            return None;
+        }
+        Some(Self {
+            decimal_separator: decimal.unwrap_or(if thousands == Some('.') { ',' } else { '.' }),
+            thousands_separator: thousands,
+        })
+    }
+
+    /// Guess the decimal mark from sample values
+    ///
+    /// `12,5` and `1.234,56` vote for `,`; `1,234` is ambiguous and does not vote.
+    /// Ties and columns without evidence use `.`.
+    pub fn detect<'a>(values: impl IntoIterator<Item = &'a str>) -> Self {
+        let (mut comma, mut dot) = (0usize, 0usize);
+        for value in values {
+            match decimal_mark(value) {
+                Some(',') => comma += 1,
+                Some('.') => dot += 1,
+                _ => {}
+            }
+        }
+        Self {
+            decimal_separator: if comma > dot { ',' } else { '.' },
+            thousands_separator: None,
+        }
+    }
+
+    /// Parse a localized number, `None` when `text` is not one
+    pub fn parse(&self, text: &str) -> Option<Decimal> {
+        let (negative, body) = strip_affixes(text)?;
+        let (int_part, frac_part) = match body.split_once(self.decimal_separator) {
+            Some((int_part, frac_part)) => (int_part, frac_part),
+            None => (body, ""),
+        };
+        let digits = ungroup(int_part, |c| self.is_grouping(c))?;
+        if !frac_part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        Decimal::from_scaled(
+            &format!("{}{}", digits, frac_part),
+            negative,
+            frac_part.len() as u32,
+        )
+    }
+
+    fn is_grouping(&self, c: char) -> bool {
+        match self.thousands_separator {
+            Some(' ') => is_space(c),
+            Some(separator) => c == separator,
+            None => c != self.decimal_separator && (matches!(c, ',' | '.' | '\'') || is_space(c)),
+        }
+    }
+}
+
+/// Spaces used for digit grouping, including no-break and narrow no-break space
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\u{a0}' | '\u{202f}')
+}
+
+fn is_currency_symbol(c: char) -> bool {
+    matches!(c, '€' | '$' | '£' | '¥' | '₹' | '¤')
+}
+
+/// Remove signs, parentheses and currency around the digits
+///
+/// Returns whether the number is negative and the remaining digits and separators.
+fn strip_affixes(text: &str) -> Option<(bool, &str)> {
+    let mut body = text.trim();
+    let mut negative = false;
+    let mut signs = 0;
+
+    loop {
+        let before = body.len();
+        if let Some(inner) = body.strip_prefix('(').and_then(|b| b.strip_suffix(')')) {
+            body = inner;
+            negative = true;
+            signs += 1;
+        }
+        let minus = ['-', '\u{2212}'];
+        if let Some(rest) = body
+            .strip_prefix(minus)
+            .or_else(|| body.strip_suffix(minus))
+        {
+            body = rest;
+            negative = true;
+            signs += 1;
+        }
+        if let Some(rest) = body.strip_prefix('+').or_else(|| body.strip_suffix('+')) {
+            body = rest;
+            signs += 1;
+        }
+        body = strip_currency(body).trim();
+        if body.len() == before {
+            break;
+        }
+    }
+
+    let starts_with_digit = body.starts_with(|c: char| c.is_ascii_digit());
+    (signs <= 1 && starts_with_digit).then_some((negative, body))
+}
+
+/// Active ISO 4217 currency codes, sorted
+const CURRENCY_CODES: [&str; 155] = [
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD",
+    "CDF", "CHF", "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD",
+    "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ",
+    "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IQD", "IRR", "ISK", "JMD", "JOD",
+    "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR",
+    "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR",
+    "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN",
+    "PGK", "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR",
+    "SDG", "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB",
+    "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS",
+    "VES", "VND", "VUV", "WST", "XAF", "XCD", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+/// Strip a currency symbol or ISO 4217 code (`EUR`) from either end
+///
+/// A code must be set apart from the number by a space or a symbol (`EUR 12,50`,
+/// `12.50 USD`), so identifiers such as `TXN123` stay text.
+fn strip_currency(body: &str) -> &str {
+    let body = body
+        .trim_start_matches(is_currency_symbol)
+        .trim_end_matches(is_currency_symbol);
+    let is_code =
+        |code: Option<&str>| code.is_some_and(|c| CURRENCY_CODES.binary_search(&c).is_ok());
+    let is_separator = |c: char| c.is_whitespace() || is_currency_symbol(c);
+
+    if is_code(body.get(..3)) && body[3..].starts_with(is_separator) {
+        return &body[3..];
+    }
+    let split = body.len().saturating_sub(3);
+    if is_code(body.get(split..)) && body[..split].ends_with(is_separator) {
+        return &body[..split];
+    }
+    body
+}
+
+/// Digits of an integer part, checking that groups after the first have 3 digits
+fn ungroup(int_part: &str, is_grouping: impl Fn(char) -> bool) -> Option<String> {
+    let groups: Vec<&str> = int_part.split(is_grouping).collect();
+    if groups
+        .iter()
+        .any(|group| !group.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return None;
+    }
+    if groups.len() > 1
+        && (groups[0].is_empty()
+            || groups[0].len() > 3
+            || groups[1..].iter().any(|group| group.len() != 3))
+    {
+        return None;
+    }
+    Some(groups.concat())
+}
+
+/// Decimal mark a single value reveals, if it is unambiguous
+fn decimal_mark(value: &str) -> Option<char> {
+    let (_, body) = strip_affixes(value)?;
+    let last = body.rfind(['.', ','])?;
+    let mark = body[last..].chars().next()?;
+    let other = if mark == '.' { ',' } else { '.' };
+
+    if body.contains(other) {
+        // `1.234,56`: the last mark is the decimal one
+        return Some(mark);
+    }
+    if body.matches(mark).count() > 1 {
+        // `1.234.567`: a repeated mark is grouping
+        return Some(other);
+    }
+    let decimals = body.len() - last - 1;
+    (decimals != 3).then_some(mark)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(format: NumberFormat, text: &str) -> Option<String> {
+        format.parse(text).map(|d| d.to_string())
+    }
+
+    #[test]
+    fn test_localized_amounts() {
+        let european = NumberFormat {
+            decimal_separator: ',',
+            thousands_separator: None,
+        };
+        assert_eq!(parse(european, "1.234,56").as_deref(), Some("1234.56"));
+        assert_eq!(parse(european, "-1 234,56").as_deref(), Some("-1234.56"));
+        assert_eq!(
+            parse(european, "1\u{a0}234,56 €").as_deref(),
+            Some("1234.56")
+        );
+        assert_eq!(parse(european, "1234,56-").as_deref(), Some("-1234.56"));
+        assert_eq!(parse(european, "EUR 12,5").as_deref(), Some("12.5"));
+        assert_eq!(parse(european, "1.5"), None);
+
+        let english = NumberFormat::default();
+        assert_eq!(parse(english, "(1,234.56)").as_deref(), Some("-1234.56"));
+        assert_eq!(parse(english, "$1,234,567").as_deref(), Some("1234567"));
+        assert_eq!(parse(english, "12.50 USD").as_deref(), Some("12.50"));
+        assert_eq!(parse(english, "1,23"), None);
+        assert_eq!(parse(english, "--5"), None);
+        assert_eq!(parse(english, "2024-01-15"), None);
+        assert_eq!(parse(english, "ABC"), None);
+    }
+
+    #[test]
+    fn test_identifiers_are_not_amounts() {
+        let english = NumberFormat::default();
+        for id in ["TXN123", "ABC456", "ID-999", "EUR12", "12EUR", "REF 12", "INV-2024"] {
+            assert_eq!(parse(english, id), None, "{}", id);
+        }
+        assert_eq!(parse(english, "CHF 1'234.50").as_deref(), Some("1234.50"));
+        assert_eq!(parse(english, "USD $12").as_deref(), Some("12"));
+    }
+
+    #[test]
+    fn test_detect_and_config() {
+        assert_eq!(
+            NumberFormat::detect(["1.234", "12,50", "3"]).decimal_separator,
+            ','
+        );
+        assert_eq!(NumberFormat::detect(["1,234", "7"]).decimal_separator, '.');
+        assert_eq!(NumberFormat::detect(["1.234.567"]).decimal_separator, ',');
+
+        let config = ParserConfig {
+            thousands_separator: Some(".".to_string()),
+            ..Default::default()
+        };
+        let format = NumberFormat::from_config(&config).unwrap();
+        assert_eq!(format.decimal_separator, ',');
+        assert_eq!(parse(format, "1 234,56"), None);
+        assert!(NumberFormat::from_config(&ParserConfig::default()).is_none());
+    }
+}
@@ -0,0 +1,126 @@
+//! Progress Estimation
+//!
+//! [`ProgressTracker`] turns the byte position of a parse into `ParseProgress`
+//! updates: callbacks are throttled to one per [`REPORT_INTERVAL_MS`], the remaining
+//! time is estimated from the throughput so far, and the input is counted in chunks
+//! of [`CHUNK_SIZE`] bytes, so one-shot parses report the same counters as the
+//! chunked `StreamingParser`.
+
+use crate::clock::{self, Clock};
+use crate::types::ParseProgress;
+use std::sync::Arc;
+
+/// Minimum time between two `parsing` updates, in milliseconds
+pub const REPORT_INTERVAL_MS: f64 = 100.0;
+
+/// Bytes per chunk reported by one-shot parses
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+/// Throttles progress updates and estimates the time remaining
+#[derive(Clone)]
+pub struct ProgressTracker {
+    total_bytes: usize,
+    start_time: f64,
+    last_report: Option<f64>,
+    /// Time source; the active clock (see [`clock::now`]) when `None`
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl ProgressTracker {
+    pub fn new(total_bytes: usize) -> Self {
+        Self::started_at(clock::now(), total_bytes)
+    }
+
+    /// Tracker for a parse that began at `start_time` (see [`clock::now`])
+    pub fn started_at(start_time: f64, total_bytes: usize) -> Self {
+        Self {
+            total_bytes,
+            start_time,
+            last_report: None,
+            clock: None,
+        }
+    }
+
+    /// Tracker timed by `clock` instead of the active clock, starting now
+    pub fn with_clock(clock: Arc<dyn Clock>, total_bytes: usize) -> Self {
+        Self {
+            clock: Some(clock.clone()),
+            ..Self::started_at(clock.now(), total_bytes)
+        }
+    }
+
+    fn now(&self) -> f64 {
+        self.clock.as_ref().map_or_else(clock::now, |clock| clock.now())
+    }
+
+    /// Whether the next update is due, starting its interval if so
+    pub fn due(&mut self) -> bool {
+        let now = self.now();
+        match self.last_report {
+            Some(last) if now - last < REPORT_INTERVAL_MS => false,
+            _ => {
+                self.last_report = Some(now);
+                true
+            }
+        }
+    }
+
+    /// Progress at byte `bytes_processed`, with chunk counters and time remaining
+    pub fn progress(&self, phase: &str, bytes_processed: usize, records: usize) -> ParseProgress {
+        let bytes_processed = bytes_processed.min(self.total_bytes);
+        let mut progress = ParseProgress::new(phase, bytes_processed, self.total_bytes, records);
+        progress.total_chunks = Some(self.total_bytes.div_ceil(CHUNK_SIZE).max(1));
+        progress.current_chunk =
+            Some((bytes_processed.div_ceil(CHUNK_SIZE)).clamp(1, progress.total_chunks.unwrap()));
+        progress.estimated_time_remaining = self.time_remaining(bytes_processed);
+        progress
+    }
+
+    /// Seconds left at the throughput so far, once there is any
+    pub fn time_remaining(&self, bytes_processed: usize) -> Option<f64> {
+        let elapsed = (self.now() - self.start_time) / 1000.0;
+        if bytes_processed == 0 || elapsed <= 0.0 {
+            return None;
+        }
+        let bytes_per_second = bytes_processed as f64 / elapsed;
+        Some(self.total_bytes.saturating_sub(bytes_processed) as f64 / bytes_per_second)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Clock advanced by hand, in whole milliseconds
+    struct ManualClock(AtomicU64);
+
+    impl Clock for ManualClock {
+        fn now(&self) -> f64 {
+            self.0.load(Ordering::SeqCst) as f64
+        }
+
+        fn timestamp(&self) -> u64 {
+            0
+        }
+    }
+
+    #[test]
+    fn test_throttling_chunks_and_eta() {
+        let time = Arc::new(ManualClock(AtomicU64::new(1_000)));
+        let total = 3 * CHUNK_SIZE;
+        let mut tracker = ProgressTracker::with_clock(time.clone(), total);
+        assert!(tracker.due());
+        assert!(!tracker.due());
+
+        time.0.store(1_500, Ordering::SeqCst);
+        assert!(tracker.due());
+        let progress = tracker.progress("parsing", CHUNK_SIZE + 1, 10);
+
+        assert_eq!(progress.percentage, 33);
+        assert_eq!((progress.current_chunk, progress.total_chunks), (Some(2), Some(3)));
+        // A third of the input took 0.5s, so the rest takes about 1s
+        let remaining = progress.estimated_time_remaining.unwrap();
+        assert!((remaining - 1.0).abs() < 0.01, "{}", remaining);
+    }
+}
@@ -0,0 +1,934 @@
+//! Incremental Push-Based Parser
+//!
+//! `StreamingParser` accepts input in arbitrary byte chunks (e.g. from `File.stream()`)
+//! and hands out records as soon as they are complete, so a large file never has to be
+//! held in memory as one string. Partial lines, quoted CSV fields and XML markup that
+//! straddle a chunk boundary are carried over to the next `push`.
+//!
+//! Chunks are decoded with `ParserConfig.encoding` (detected from the first chunk when
+//! unset), so multi-byte characters may also be split between chunks.
+
+use crate::clock;
+use crate::csv_parser::{self, Dialect, Row, RowRules, RowTypes};
+use crate::delimited::{self, Delimiter};
+use crate::encoding::{self, StreamDecoder};
+use crate::fixed_width_parser;
+use crate::progress::ProgressTracker;
+use crate::totals::ControlTotals;
+use crate::types::*;
+use crate::utils::generate_id;
+use crate::xml_parser::{event_span, XmlRecordBuilder};
+use csv::StringRecord;
+use csv_core::{ReadRecordResult, Reader as CsvCoreReader, ReaderBuilder as CsvCoreBuilder};
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+
+/// Stateful parser fed with successive chunks of one document
+pub struct StreamingParser {
+    config: ParserConfig,
+    state: StreamState,
+    decoder: StreamDecoder,
+    records: Vec<ParsedRecord>,
+    totals: ControlTotals,
+    valid_count: usize,
+    invalid_count: usize,
+    bytes_processed: usize,
+    chunks_processed: usize,
+    start_time: f64,
+    finished: bool,
+}
+
+/// Format-specific carry-over state
+enum StreamState {
+    Csv(Box<CsvStream>),
+    FixedWidth(LineStream),
+    Xml(Box<XmlStream>),
+}
+
+impl StreamingParser {
+    /// Create a streaming parser for `csv`, `fixed-width` or `iso20022` configs
+    pub fn new(config: ParserConfig) -> Result<Self, ParseError> {
+        let state = match config.parser_type.as_str() {
+            "csv" => StreamState::Csv(Box::new(CsvStream::new(&config)?)),
+            "fixed-width" => {
+                fixed_width_parser::resolve_layouts(&config)?;
+                StreamState::FixedWidth(LineStream::default())
+            }
+            "iso20022" => StreamState::Xml(Box::new(XmlStream::new())),
+            other => {
+                return Err(ParseError::ConfigError(format!(
+                    "streaming is not supported for parser type \"{}\"",
+                    other
+                )))
+            }
+        };
+        let decoder = StreamDecoder::new(config.encoding.as_deref())?;
+        let totals = ControlTotals::from_config(&config)?;
+
+        Ok(Self {
+            config,
+            state,
+            decoder,
+            records: Vec::new(),
+            totals,
+            valid_count: 0,
+            invalid_count: 0,
+            bytes_processed: 0,
+            chunks_processed: 0,
+            start_time: clock::now(),
+            finished: false,
+        })
+    }
+
+    /// Feed the next chunk, returning the number of records ready to drain
+    pub fn push(&mut self, chunk: &[u8]) -> Result<usize, ParseError> {
+        if self.finished {
+            return Err(ParseError::ConfigError(
+                "push called after finish".to_string(),
+            ));
+        }
+        if chunk.is_empty() {
+            return Ok(self.records.len());
+        }
+
+        self.bytes_processed += chunk.len();
+        self.chunks_processed += 1;
+
+        let text = self.decoder.decode_chunk(chunk)?;
+        self.feed_text(text)?;
+
+        Ok(self.records.len())
+    }
+
+    /// Run decoded text through the format-specific state
+    fn feed_text(&mut self, text: String) -> Result<(), ParseError> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        // Fixed-width keeps NEL so binary fields can recover their bytes
+        let text = match self.state {
+            StreamState::FixedWidth(_) => text,
+            _ => encoding::nel_as_newline(text),
+        };
+        let text = text.as_bytes();
+        if let Some(encoding) = self.decoder.encoding() {
+            // Fixed-width binary fields need the detected encoding, which may change
+            // from UTF-8 to Windows-1252 mid-stream
+            self.config.encoding = Some(encoding.label().to_string());
+        }
+
+        let before = self.records.len();
+        match &mut self.state {
+            StreamState::Csv(csv) => csv.feed(text, &self.config, &mut self.records)?,
+            StreamState::FixedWidth(lines) => {
+                lines.feed(text, false, &self.config, &mut self.records)?
+            }
+            StreamState::Xml(xml) => xml.feed(text, false, &mut self.records)?,
+        }
+        self.count_new_records(before);
+        Ok(())
+    }
+
+    /// Take the records completed so far
+    pub fn drain(&mut self) -> Vec<ParsedRecord> {
+        std::mem::take(&mut self.records)
+    }
+
+    /// Number of records produced so far, drained or not
+    pub fn records_processed(&self) -> usize {
+        self.valid_count + self.invalid_count
+    }
+
+    /// Progress so far, given the size of the whole input when it is known
+    ///
+    /// Chunk counters follow the pushed chunks; the total is extrapolated from
+    /// their average size. Without `total_bytes`, `total_bytes` and `percentage`
+    /// stay 0 until the parse is finished.
+    pub fn progress(&self, total_bytes: Option<usize>) -> ParseProgress {
+        let total = total_bytes.unwrap_or(self.bytes_processed);
+        let tracker = ProgressTracker::started_at(self.start_time, total);
+        let phase = if self.finished { "complete" } else { "parsing" };
+        let mut progress = tracker.progress(phase, self.bytes_processed, self.records_processed());
+        progress.current_chunk = Some(self.chunks_processed);
+        progress.total_chunks = match total_bytes {
+            Some(total) if self.bytes_processed > 0 => {
+                let chunks = (total * self.chunks_processed).div_ceil(self.bytes_processed);
+                Some(chunks.max(self.chunks_processed))
+            }
+            _ => None,
+        };
+        if total_bytes.is_none() {
+            progress.estimated_time_remaining = None;
+            if !self.finished {
+                progress.total_bytes = 0;
+                progress.percentage = 0;
+            }
+        }
+        progress
+    }
+
+    /// Flush buffered input and return the undrained records with full metadata
+    pub fn finish(&mut self) -> Result<ParsedData, ParseError> {
+        if self.finished {
+            return Err(ParseError::ConfigError("finish called twice".to_string()));
+        }
+        self.finished = true;
+
+        let text = self.decoder.finish()?;
+        self.feed_text(text)?;
+        let encoding = self.decoder.encoding().map(|e| e.label().to_string());
+
+        let before = self.records.len();
+        let mut columns = None;
+        let mut diagnostics = Vec::new();
+        let headers = match &mut self.state {
+            StreamState::Csv(csv) => {
+                csv.finish(&self.config, &mut self.records)?;
+                columns = csv.types.as_ref().map(RowTypes::columns);
+                csv.headers.clone().unwrap_or_default()
+            }
+            StreamState::FixedWidth(lines) => {
+                lines.feed(&[], true, &self.config, &mut self.records)?;
+                fixed_width_parser::resolve_layouts(&self.config)?.headers()
+            }
+            StreamState::Xml(xml) => {
+                xml.feed(&[], true, &mut self.records)?;
+                diagnostics = std::mem::take(&mut xml.builder.diagnostics);
+                xml.builder.headers()
+            }
+        };
+        self.count_new_records(before);
+
+        // Failed control totals invalidate the total record if it is still undrained
+        let valid_before = self.records.iter().filter(|r| r.is_valid).count();
+        diagnostics.extend(std::mem::take(&mut self.totals).finish(&mut self.records));
+        let invalidated = valid_before - self.records.iter().filter(|r| r.is_valid).count();
+        self.valid_count -= invalidated;
+        self.invalid_count += invalidated;
+
+        Ok(ParsedData {
+            id: generate_id("parsed"),
+            config: self.config.clone(),
+            records: self.drain(),
+            headers: Some(headers),
+            columns,
+            metadata: ParseMetadata {
+                total_records: self.records_processed(),
+                valid_records: self.valid_count,
+                invalid_records: self.invalid_count,
+                parse_time: clock::now() - self.start_time,
+                file_size: Some(self.bytes_processed),
+                parser_engine: "wasm".to_string(),
+                chunks_processed: Some(self.chunks_processed),
+                encoding,
+                diagnostics: (!diagnostics.is_empty()).then_some(diagnostics),
+                ..Default::default()
+            },
+        })
+    }
+
+    fn count_new_records(&mut self, before: usize) {
+        for record in &self.records[before..] {
+            self.totals.observe(record);
+            if record.is_valid {
+                self.valid_count += 1;
+            } else {
+                self.invalid_count += 1;
+            }
+        }
+    }
+}
+
+/// Rows buffered to infer CSV column types before records are released
+const SCHEMA_SAMPLE_ROWS: usize = 1000;
+
+/// CSV state: `csv_core` keeps quoting state between calls, we keep the partial record
+///
+/// Column types are inferred from the first [`SCHEMA_SAMPLE_ROWS`] rows, which are
+/// held back until then.
+struct CsvStream {
+    reader: CsvCoreReader,
+    output: Vec<u8>,
+    output_len: usize,
+    ends: Vec<usize>,
+    ends_len: usize,
+    /// Source bytes of the record being read, for field spans
+    raw: Vec<u8>,
+    raw_offset: usize,
+    dialect: Dialect,
+    /// Multi-character or pattern delimiter; records are then split from `text`
+    split: Option<Delimiter>,
+    /// Input not yet split into records (`split` only)
+    text: String,
+    /// Preamble lines still to skip (`skip_rows`)
+    skip_lines: usize,
+    headers: Option<Vec<String>>,
+    record_index: usize,
+    pending: Vec<(usize, Row)>,
+    rules: RowRules,
+    types: Option<RowTypes>,
+}
+
+impl CsvStream {
+    fn new(config: &ParserConfig) -> Result<Self, ParseError> {
+        let dialect = Dialect::from_config(config);
+
+        Ok(Self {
+            reader: CsvCoreBuilder::new()
+                .delimiter(dialect.delimiter)
+                .quote(dialect.quote)
+                .escape(dialect.escape)
+                .comment(dialect.comment)
+                .build(),
+            output: vec![0; 4096],
+            output_len: 0,
+            ends: vec![0; 64],
+            ends_len: 0,
+            raw: Vec::new(),
+            raw_offset: 0,
+            dialect,
+            split: Delimiter::from_config(config)?,
+            text: String::new(),
+            skip_lines: config.skip_rows,
+            headers: None,
+            record_index: 0,
+            pending: Vec::new(),
+            rules: RowRules::from_config(config)?,
+            types: None,
+        })
+    }
+
+    /// Consume input; an empty slice signals end of input
+    fn feed(
+        &mut self,
+        mut input: &[u8],
+        config: &ParserConfig,
+        records: &mut Vec<ParsedRecord>,
+    ) -> Result<(), ParseError> {
+        while self.skip_lines > 0 && !input.is_empty() {
+            let skipped = match memchr::memchr(b'\n', input) {
+                Some(newline) => {
+                    self.skip_lines -= 1;
+                    newline + 1
+                }
+                None => input.len(),
+            };
+            self.raw_offset += skipped;
+            input = &input[skipped..];
+        }
+        if self.split.is_some() {
+            // Input is decoded text, so it is valid UTF-8
+            self.text.push_str(std::str::from_utf8(input).unwrap_or_default());
+            return self.split_records(input.is_empty(), config, records);
+        }
+        loop {
+            let (result, read, written, ends_written) = self.reader.read_record(
+                input,
+                &mut self.output[self.output_len..],
+                &mut self.ends[self.ends_len..],
+            );
+            self.raw.extend_from_slice(&input[..read]);
+            input = &input[read..];
+            self.output_len += written;
+            self.ends_len += ends_written;
+
+            match result {
+                ReadRecordResult::InputEmpty | ReadRecordResult::End => return Ok(()),
+                ReadRecordResult::OutputFull => {
+                    let len = self.output.len() * 2;
+                    self.output.resize(len, 0);
+                }
+                ReadRecordResult::OutputEndsFull => {
+                    let len = self.ends.len() * 2;
+                    self.ends.resize(len, 0);
+                }
+                ReadRecordResult::Record => {
+                    if let Some((index, row)) = self.take_row(config)? {
+                        self.emit(index, row, config, records);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Emit the complete records of `text`, or all of it at the end of input
+    fn split_records(
+        &mut self,
+        eof: bool,
+        config: &ParserConfig,
+        records: &mut Vec<ParsedRecord>,
+    ) -> Result<(), ParseError> {
+        let Some(delimiter) = self.split.clone() else {
+            return Ok(());
+        };
+        let mut pos = 0;
+        while let Some(record) =
+            delimited::split_record(&self.text, pos, eof, &delimiter, &self.dialect)
+        {
+            pos = record.end;
+            let raw = csv_parser::source_text(&self.text, &record.spans).to_string();
+            let spans = record
+                .spans
+                .iter()
+                .map(|&(start, end)| (self.raw_offset + start, self.raw_offset + end))
+                .collect();
+            if let Some((index, row)) = self.next_row(record.fields, spans, raw, None, config)? {
+                self.emit(index, row, config, records);
+            }
+        }
+        self.text.drain(..pos);
+        self.raw_offset += pos;
+        Ok(())
+    }
+
+    /// Flush the last record and any rows still waiting for the column types
+    fn finish(
+        &mut self,
+        config: &ParserConfig,
+        records: &mut Vec<ParsedRecord>,
+    ) -> Result<(), ParseError> {
+        self.feed(&[], config, records)?;
+        if self.types.is_none() {
+            self.resolve_types(config, records);
+        }
+        Ok(())
+    }
+
+    /// Build the record for `row`, or hold it back until the column types are known
+    fn emit(
+        &mut self,
+        index: usize,
+        row: Row,
+        config: &ParserConfig,
+        records: &mut Vec<ParsedRecord>,
+    ) {
+        match &self.types {
+            Some(types) => records.push(types.record(index, row, config)),
+            None => {
+                self.pending.push((index, row));
+                if self.pending.len() >= SCHEMA_SAMPLE_ROWS {
+                    self.resolve_types(config, records);
+                }
+            }
+        }
+    }
+
+    /// Infer the column types from the buffered rows and release their records
+    fn resolve_types(&mut self, config: &ParserConfig, records: &mut Vec<ParsedRecord>) {
+        let headers = self.headers.as_deref().unwrap_or_default();
+        let types = self.rules.clone().infer(
+            self.pending
+                .iter()
+                .filter_map(|(_, row)| row.as_ref().ok())
+                .map(|(record, _, raw)| (record, raw.as_str())),
+            headers,
+            config,
+        );
+        for (index, row) in self.pending.drain(..) {
+            records.push(types.record(index, row, config));
+        }
+        self.types = Some(types);
+    }
+
+    /// Turn the buffered fields into a row (`None` for the header row)
+    fn take_row(&mut self, config: &ParserConfig) -> Result<Option<(usize, Row)>, ParseError> {
+        let output = &self.output[..self.output_len];
+        let mut fields = Vec::with_capacity(self.ends_len);
+        let mut start = 0;
+        let mut utf8_error = None;
+        for &end in &self.ends[..self.ends_len] {
+            match std::str::from_utf8(&output[start..end]) {
+                Ok(field) => fields.push(field.to_string()),
+                Err(e) => utf8_error = Some(e),
+            }
+            start = end;
+        }
+        let spans = csv_parser::field_spans(&self.raw, 0, &self.dialect, fields.len());
+        let raw = match (spans.first(), spans.last()) {
+            (Some(&(start, _)), Some(&(_, end))) => {
+                String::from_utf8_lossy(&self.raw[start..end]).into_owned()
+            }
+            _ => String::new(),
+        };
+        let spans = spans
+            .into_iter()
+            .map(|(start, end)| (self.raw_offset + start, self.raw_offset + end))
+            .collect();
+        self.raw_offset += self.raw.len();
+        self.raw.clear();
+        self.output_len = 0;
+        self.ends_len = 0;
+        self.next_row(fields, spans, raw, utf8_error, config)
+    }
+
+    /// Row for the fields of a record and its source text (`None` for the header row)
+    fn next_row(
+        &mut self,
+        fields: Vec<String>,
+        spans: Vec<(usize, usize)>,
+        raw: String,
+        utf8_error: Option<std::str::Utf8Error>,
+        config: &ParserConfig,
+    ) -> Result<Option<(usize, Row)>, ParseError> {
+        if self.headers.is_none() {
+            if config.has_header {
+                self.headers = Some(csv_parser::header_names(
+                    fields.iter().map(String::as_str),
+                    config,
+                )?);
+                return Ok(None);
+            }
+            self.headers = Some(csv_parser::numbered_headers(fields.len()));
+        }
+
+        let index = self.record_index;
+        self.record_index += 1;
+
+        if let Some(e) = utf8_error {
+            let mut record = ParsedRecord {
+                id: format!("record-{}", index),
+                index,
+                fields: vec![],
+                raw: String::new(),
+                record_type: "data".to_string(),
+                is_valid: false,
+                errors: None,
+                position: None,
+                diagnostics: None,
+            };
+            record.add_diagnostics(vec![Diagnostic::error(
+                DiagnosticCode::CsvUtf8,
+                format!("invalid UTF-8 in record: {}", e),
+            )]);
+            return Ok(Some((index, Err(Box::new(record)))));
+        }
+
+        Ok(Some((index, Ok((StringRecord::from(fields), spans, raw)))))
+    }
+}
+
+/// Line-oriented state: the trailing partial line (or fixed-length record) is carried over
+#[derive(Default)]
+struct LineStream {
+    pending: Vec<u8>,
+    pending_offset: usize,
+    line_number: usize,
+    record_index: usize,
+}
+
+impl LineStream {
+    fn feed(
+        &mut self,
+        chunk: &[u8],
+        is_last: bool,
+        config: &ParserConfig,
+        records: &mut Vec<ParsedRecord>,
+    ) -> Result<(), ParseError> {
+        self.pending.extend_from_slice(chunk);
+
+        let layouts = fixed_width_parser::resolve_layouts(config)?;
+        let record_length = layouts.record_length(config)?;
+        let text = String::from_utf8_lossy(&self.pending);
+        let complete = if is_last {
+            text.len()
+        } else if let Some(length) = record_length {
+            let whole = text.chars().count() / length * length;
+            text.char_indices().nth(whole).map_or(text.len(), |(i, _)| i)
+        } else {
+            match text.rfind(fixed_width_parser::LINE_ENDS) {
+                Some(pos) => pos + text[pos..].chars().next().map_or(1, char::len_utf8),
+                None => return Ok(()),
+            }
+        };
+
+        let options = fixed_width_parser::FieldOptions::from_config(config);
+        for (offset, line) in fixed_width_parser::split_records(&text[..complete], record_length) {
+            self.line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            records.push(fixed_width_parser::parse_line(
+                self.record_index,
+                line,
+                self.line_number,
+                self.pending_offset + offset,
+                &layouts,
+                &options,
+            ));
+            self.record_index += 1;
+        }
+
+        self.pending.drain(..complete);
+        self.pending_offset += complete;
+        Ok(())
+    }
+}
+
+/// XML state: input is cut after the last complete markup so no tag or text node is split
+struct XmlStream {
+    pending: Vec<u8>,
+    pending_offset: usize,
+    scan_pos: usize,
+    markup: Markup,
+    builder: XmlRecordBuilder,
+}
+
+/// Where the scanner is relative to XML markup
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Markup {
+    Text,
+    Tag,
+    Quoted(u8),
+    Comment,
+    CData,
+    Instruction,
+}
+
+impl XmlStream {
+    fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            pending_offset: 0,
+            scan_pos: 0,
+            markup: Markup::Text,
+            builder: XmlRecordBuilder::new(),
+        }
+    }
+
+    fn feed(
+        &mut self,
+        chunk: &[u8],
+        is_last: bool,
+        records: &mut Vec<ParsedRecord>,
+    ) -> Result<(), ParseError> {
+        self.pending.extend_from_slice(chunk);
+
+        let complete = if is_last {
+            self.pending.len()
+        } else {
+            match self.scan() {
+                Some(end) => end,
+                None => return Ok(()),
+            }
+        };
+
+        let mut reader = Reader::from_reader(&self.pending[..complete]);
+        reader.trim_text(true);
+        // Start tags of open elements were consumed with earlier chunks
+        reader.check_end_names(false);
+
+        let mut buf = Vec::new();
+        loop {
+            let position = reader.buffer_position();
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Eof) => break,
+                Ok(event) => {
+                    let window = &self.pending[position..reader.buffer_position()];
+                    let span = event_span(&event, window, self.pending_offset + position);
+                    self.builder.handle_event(&event, span);
+                }
+                Err(e) => {
+                    let offset = self.pending_offset + reader.buffer_position();
+                    return Err(ParseError::Located(Box::new(
+                        Diagnostic::error(DiagnosticCode::XmlSyntax, format!("Invalid XML: {}", e))
+                            .with_span(offset, offset),
+                    )));
+                }
+            }
+            buf.clear();
+        }
+        records.extend(self.builder.take_records());
+
+        self.pending.drain(..complete);
+        self.pending_offset += complete;
+        self.scan_pos = self.scan_pos.saturating_sub(complete);
+        Ok(())
+    }
+
+    /// Scan new input, returning the end of the last complete markup if any
+    fn scan(&mut self) -> Option<usize> {
+        let buf = &self.pending;
+        let mut safe_end = None;
+        let mut i = self.scan_pos;
+
+        while i < buf.len() {
+            let rest = &buf[i..];
+            match self.markup {
+                Markup::Text => {
+                    if rest[0] == b'<' {
+                        if rest.starts_with(b"<!--") {
+                            self.markup = Markup::Comment;
+                            i += 4;
+                            continue;
+                        }
+                        if rest.starts_with(b"<![CDATA[") {
+                            self.markup = Markup::CData;
+                            i += 9;
+                            continue;
+                        }
+                        if rest.starts_with(b"<?") {
+                            self.markup = Markup::Instruction;
+                            i += 2;
+                            continue;
+                        }
+                        // Not enough input yet to tell a comment or CDATA from a tag
+                        if b"<!--".starts_with(rest) || b"<![CDATA[".starts_with(rest) {
+                            break;
+                        }
+                        self.markup = Markup::Tag;
+                    }
+                }
+                Markup::Tag => match rest[0] {
+                    b'"' | b'\'' => self.markup = Markup::Quoted(rest[0]),
+                    b'>' => {
+                        self.markup = Markup::Text;
+                        safe_end = Some(i + 1);
+                    }
+                    _ => {}
+                },
+                Markup::Quoted(quote) => {
+                    if rest[0] == quote {
+                        self.markup = Markup::Tag;
+                    }
+                }
+                Markup::Comment | Markup::CData | Markup::Instruction => {
+                    let terminator: &[u8] = match self.markup {
+                        Markup::Comment => b"-->",
+                        Markup::CData => b"]]>",
+                        _ => b"?>",
+                    };
+                    if rest.starts_with(terminator) {
+                        self.markup = Markup::Text;
+                        i += terminator.len();
+                        safe_end = Some(i);
+                        continue;
+                    }
+                    if rest.len() < terminator.len() {
+                        break;
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        self.scan_pos = i;
+        safe_end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_in_chunks(parser: &mut StreamingParser, data: &[u8], size: usize) -> Vec<ParsedRecord> {
+        let mut records = Vec::new();
+        for chunk in data.chunks(size) {
+            parser.push(chunk).unwrap();
+            records.extend(parser.drain());
+        }
+        records.extend(parser.finish().unwrap().records);
+        records
+    }
+
+    #[test]
+    fn test_csv_quoted_field_across_chunks() {
+        let data = "name,note\nAlice,\"multi\nline, quoted\"\nBob,plain\n";
+        let mut parser = StreamingParser::new(ParserConfig::default()).unwrap();
+        let records = push_in_chunks(&mut parser, data.as_bytes(), 3);
+
+        let expected = csv_parser::parse_csv(data, &ParserConfig::default()).unwrap();
+        assert_eq!(records.len(), expected.records.len());
+        assert_eq!(records[0].fields[1].original_value, "multi\nline, quoted");
+
+        let spans = |records: &[ParsedRecord]| -> Vec<(usize, usize)> {
+            records
+                .iter()
+                .flat_map(|r| r.fields.iter().filter_map(|f| f.position.as_ref()))
+                .map(|p| (p.start, p.end))
+                .collect()
+        };
+        assert_eq!(spans(&records), spans(&expected.records));
+    }
+
+    #[test]
+    fn test_csv_column_types_from_sample() {
+        let data = "id,amount\n007,10\n012,2.50\n";
+        let mut parser = StreamingParser::new(ParserConfig::default()).unwrap();
+        parser.push(data.as_bytes()).unwrap();
+        // Rows are held back until the column types are known
+        assert!(parser.drain().is_empty());
+
+        let result = parser.finish().unwrap();
+        let expected = csv_parser::parse_csv(data, &ParserConfig::default()).unwrap();
+        let values = |records: &[ParsedRecord]| -> Vec<serde_json::Value> {
+            records
+                .iter()
+                .flat_map(|r| &r.fields)
+                .map(|f| serde_json::json!([f.value, f.field_type]))
+                .collect()
+        };
+        assert_eq!(values(&result.records), values(&expected.records));
+        assert_eq!(result.columns.unwrap()[1].field_type, "number");
+    }
+
+    #[test]
+    fn test_csv_dialect_across_chunks() {
+        let data = "Bank export\nSheet 1\nname,note\n# skipped\n\"A\\\"B\",x\nC,y\n";
+        let config = ParserConfig {
+            comment_char: Some("#".to_string()),
+            skip_rows: 2,
+            ..Default::default()
+        };
+        let expected = csv_parser::parse_csv(data, &config).unwrap();
+        for size in [1, 5, 64] {
+            let mut parser = StreamingParser::new(config.clone()).unwrap();
+            let records = push_in_chunks(&mut parser, data.as_bytes(), size);
+            assert_eq!(records.len(), expected.records.len());
+            for (record, expected) in records.iter().zip(&expected.records) {
+                assert_eq!(record.fields[0].original_value, expected.fields[0].original_value);
+                assert_eq!(record.raw, expected.raw);
+                let span = |r: &ParsedRecord| r.position.as_ref().map(|p| (p.start, p.end));
+                assert_eq!(span(record), span(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_csv_pattern_delimiter_across_chunks() {
+        let data = "id  name\n1   \"J  Smith\"\n2\tDoe\n";
+        let config = ParserConfig {
+            delimiter_pattern: Some(r"[ \t]+".to_string()),
+            ..Default::default()
+        };
+        let expected = csv_parser::parse_csv(data, &config).unwrap();
+        assert_eq!(expected.records.len(), 2);
+        for size in [1, 4, 64] {
+            let mut parser = StreamingParser::new(config.clone()).unwrap();
+            let records = push_in_chunks(&mut parser, data.as_bytes(), size);
+            let fields = |records: &[ParsedRecord]| -> Vec<(String, Option<usize>)> {
+                records
+                    .iter()
+                    .flat_map(|r| &r.fields)
+                    .map(|f| (f.original_value.clone(), f.position.as_ref().map(|p| p.start)))
+                    .collect()
+            };
+            assert_eq!(fields(&records), fields(&expected.records));
+            let raws: Vec<&str> = records.iter().map(|r| r.raw.as_str()).collect();
+            assert_eq!(raws, ["1   \"J  Smith\"", "2\tDoe"], "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_xml_elements_across_chunks() {
+        let data = r#"<?xml version="1.0"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.03"><CstmrCdtTrfInitn>
+<GrpHdr><MsgId>MSG-1</MsgId><NbOfTxs>2</NbOfTxs></GrpHdr>
+<!-- a > comment -->
+<PmtInf><CdtTrfTxInf><Amt><InstdAmt Ccy="EUR">10.00</InstdAmt></Amt></CdtTrfTxInf>
+<CdtTrfTxInf><Amt><InstdAmt Ccy="EUR">20.00</InstdAmt></Amt></CdtTrfTxInf></PmtInf>
+</CstmrCdtTrfInitn></Document>"#;
+        let config = ParserConfig {
+            parser_type: "iso20022".to_string(),
+            ..Default::default()
+        };
+
+        let expected = crate::xml_parser::parse_xml(data, &config).unwrap();
+        for size in [1, 7, 64] {
+            let mut parser = StreamingParser::new(config.clone()).unwrap();
+            let records = push_in_chunks(&mut parser, data.as_bytes(), size);
+            let raws: Vec<&str> = records.iter().map(|r| r.raw.as_str()).collect();
+            let expected_raws: Vec<&str> =
+                expected.records.iter().map(|r| r.raw.as_str()).collect();
+            assert_eq!(raws, expected_raws, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_detected_utf8_falls_back_to_windows_1252() {
+        let config = ParserConfig {
+            delimiter: ";".to_string(),
+            ..Default::default()
+        };
+        let mut parser = StreamingParser::new(config).unwrap();
+        let data = b"nom;ville\nDupont;Paris\nL\xE9a;Orl\xE9ans\n";
+        let records = push_in_chunks(&mut parser, data, 12);
+
+        let json = serde_json::to_value(&records).unwrap();
+        assert_eq!(json[1]["fields"][0]["value"], "Léa");
+        assert_eq!(json[1]["fields"][1]["value"], "Orléans");
+    }
+
+    #[test]
+    fn test_ebcdic_csv_lines_across_chunks() {
+        let config = ParserConfig {
+            encoding: Some("cp037".to_string()),
+            ..Default::default()
+        };
+        // "a,b", "1,2" and "3,4" in CP037, ending in NEL
+        let data = [0x81, 0x6B, 0x82, 0x15, 0xF1, 0x6B, 0xF2, 0x15, 0xF3, 0x6B, 0xF4, 0x15];
+        for size in [1, 4, 64] {
+            let mut parser = StreamingParser::new(config.clone()).unwrap();
+            let records = push_in_chunks(&mut parser, &data, size);
+            let raws: Vec<&str> = records.iter().map(|r| r.raw.as_str()).collect();
+            assert_eq!(raws, ["1,2", "3,4"], "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_fixed_length_records_across_chunks() {
+        let config = ParserConfig {
+            encoding: Some("latin1".to_string()),
+            ..fixed_width_parser::tests::binary_config()
+        };
+        let data = [0x01, 0x0D, 0x00, 0x0A, b'A', b'B', 0x12, 0x3C, 0x0D, 0x0A, 0xE9, b'D'];
+        let expected = crate::parse_bytes(&data, &config).unwrap();
+        assert_eq!(expected.records.len(), 2);
+
+        for size in 1..data.len() {
+            let mut parser = StreamingParser::new(config.clone()).unwrap();
+            let records = push_in_chunks(&mut parser, &data, size);
+            let raws: Vec<&str> = records.iter().map(|r| r.raw.as_str()).collect();
+            let expected_raws: Vec<&str> =
+                expected.records.iter().map(|r| r.raw.as_str()).collect();
+            assert_eq!(raws, expected_raws, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_fixed_width_partial_lines_and_metadata() {
+        let config = ParserConfig {
+            parser_type: "fixed-width".to_string(),
+            field_definitions: Some(vec![FieldDefinition {
+                id: "code".to_string(),
+                name: "Code".to_string(),
+                start: 0,
+                length: 3,
+                field_type: "string".to_string(),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let data = "AAA\nBBB\r\nCCC";
+        let mut parser = StreamingParser::new(config).unwrap();
+        parser.push(&data.as_bytes()[..5]).unwrap();
+        assert_eq!(parser.drain().len(), 1);
+        let progress = parser.progress(Some(data.len()));
+        assert_eq!((progress.current_chunk, progress.total_chunks), (Some(1), Some(3)));
+        assert_eq!((progress.bytes_processed, progress.percentage), (5, 41));
+
+        // Unknown size: no total, percentage or time remaining yet
+        let progress = parser.progress(None);
+        assert_eq!((progress.bytes_processed, progress.total_bytes), (5, 0));
+        assert_eq!((progress.percentage, progress.total_chunks), (0, None));
+        assert_eq!(progress.estimated_time_remaining, None);
+        parser.push(&data.as_bytes()[5..]).unwrap();
+
+        let result = parser.finish().unwrap();
+        assert_eq!(parser.progress(None).percentage, 100);
+        assert_eq!(result.records.len(), 2);
+        let position = result.records[1].fields[0].position.as_ref().unwrap();
+        assert_eq!(&data[position.start..position.end], "CCC");
+        assert_eq!(result.metadata.total_records, 3);
+        assert_eq!(result.metadata.chunks_processed, Some(2));
+    }
+}
@@ -0,0 +1,422 @@
+//! SWIFT Field Format Specifications
+//!
+//! Block 4 field formats are written in SWIFT notation, e.g. `6!n3!a15d` for 32A:
+//! a maximum length (`15`), fixed with `!`, followed by a character set: `n` digits,
+//! `a` upper-case letters, `c` upper-case letters and digits, `x` the SWIFT X
+//! character set, `d` a decimal amount with a comma. `4*35x` allows up to 4 lines of
+//! 35, `[...]` marks an optional part, `/` and other punctuation are literal, and
+//! `$` separates the lines of a field. [`FieldFormat`] compiles a specification and
+//! validates a value against it, naming the first part that does not match.
+
+use std::collections::HashMap;
+
+lazy_static::lazy_static! {
+    /// Formats shared by all message types, by tag and option
+    static ref FORMATS: HashMap<&'static str, FieldFormat> = compile(&[
+        ("13C", "/8c/4!n1!x4!n"),
+        ("13D", "6!n4!n1!x4!n"),
+        ("20", "16x"),
+        ("21", "16x"),
+        ("21R", "16x"),
+        ("23B", "4!c"),
+        ("23E", "4!c[/30x]"),
+        ("25", "35x"),
+        ("26T", "3!c"),
+        ("28C", "5n[/5n]"),
+        ("30", "6!n"),
+        ("32A", "6!n3!a15d"),
+        ("32B", "3!a15d"),
+        ("33B", "3!a15d"),
+        ("34F", "3!a[1!a]15d"),
+        ("36", "12d"),
+        ("50A", "[/34x$]4!a2!a2!c[3!c]"),
+        ("50F", "35x$4*35x"),
+        ("50K", "[/34x$]4*35x"),
+        ("52A", "[[/1!a][/34x]$]4!a2!a2!c[3!c]"),
+        ("52D", "[[/1!a][/34x]$]4*35x"),
+        ("53A", "[[/1!a][/34x]$]4!a2!a2!c[3!c]"),
+        ("53B", "[/1!a][/34x][$35x]"),
+        ("53D", "[[/1!a][/34x]$]4*35x"),
+        ("54A", "[[/1!a][/34x]$]4!a2!a2!c[3!c]"),
+        ("54D", "[[/1!a][/34x]$]4*35x"),
+        ("56A", "[[/1!a][/34x]$]4!a2!a2!c[3!c]"),
+        ("56D", "[[/1!a][/34x]$]4*35x"),
+        ("57A", "[[/1!a][/34x]$]4!a2!a2!c[3!c]"),
+        ("57D", "[[/1!a][/34x]$]4*35x"),
+        ("58A", "[[/1!a][/34x]$]4!a2!a2!c[3!c]"),
+        ("58D", "[[/1!a][/34x]$]4*35x"),
+        ("59", "[/34x$]4*35x"),
+        ("59A", "[/34x$]4!a2!a2!c[3!c]"),
+        ("59F", "[/34x$]4*35x"),
+        ("60F", "1!a6!n3!a15d"),
+        ("60M", "1!a6!n3!a15d"),
+        // 2a is the D/C mark (C, D, RC, RD, EC or ED), checked by `fin_subfields`
+        ("61", "6!n[4!n]2a[1!a]15d1!a3!c16x[//16x][$34x]"),
+        ("62F", "1!a6!n3!a15d"),
+        ("62M", "1!a6!n3!a15d"),
+        ("64", "1!a6!n3!a15d"),
+        ("65", "1!a6!n3!a15d"),
+        ("70", "4*35x"),
+        ("71A", "3!a"),
+        ("71F", "3!a15d"),
+        ("71G", "3!a15d"),
+        ("72", "6*35x"),
+        ("77B", "3*35x"),
+        ("90C", "5n3!a15d"),
+        ("90D", "5n3!a15d"),
+    ]);
+
+    /// Formats that only apply to certain message types, by type and tag
+    static ref MT_FORMATS: Vec<(&'static str, &'static str, FieldFormat)> = {
+        let narrative = ["192", "195", "196", "199", "292", "295", "296", "299", "999"];
+        let statement = ["940", "942", "950"];
+        narrative
+            .iter()
+            .map(|&mt| (mt, "79", "35*50x"))
+            .chain(statement.iter().map(|&mt| (mt, "86", "6*65x")))
+            .map(|(mt, tag, spec)| (mt, tag, FieldFormat::parse(spec).expect("valid format")))
+            .collect()
+    };
+}
+
+fn compile(specs: &[(&'static str, &str)]) -> HashMap<&'static str, FieldFormat> {
+    specs
+        .iter()
+        .map(|&(tag, spec)| (tag, FieldFormat::parse(spec).expect("valid format")))
+        .collect()
+}
+
+/// Format of field `tag` in messages of type `message_type` (`MT103` or `103`), if known
+pub fn field_format(message_type: Option<&str>, tag: &str) -> Option<&'static FieldFormat> {
+    let mt = message_type.map(|mt| mt.trim_start_matches("MT"));
+    MT_FORMATS
+        .iter()
+        .find(|(format_mt, format_tag, _)| Some(*format_mt) == mt && *format_tag == tag)
+        .map(|(_, _, format)| format)
+        .or_else(|| FORMATS.get(tag))
+}
+
+/// A compiled format specification
+#[derive(Debug, Clone)]
+pub struct FieldFormat {
+    spec: String,
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    /// Up to `lines` lines of `min..=max` characters from `charset`
+    Chars {
+        spec: String,
+        lines: usize,
+        min: usize,
+        max: usize,
+        charset: Charset,
+    },
+    Literal(char),
+    NewLine,
+    Optional(Vec<Item>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Charset {
+    Numeric,
+    Alpha,
+    Alphanumeric,
+    SwiftX,
+    Decimal,
+}
+
+impl Charset {
+    fn contains(self, c: char) -> bool {
+        match self {
+            Charset::Numeric => c.is_ascii_digit(),
+            Charset::Alpha => c.is_ascii_uppercase(),
+            Charset::Alphanumeric => c.is_ascii_uppercase() || c.is_ascii_digit(),
+            Charset::SwiftX => c.is_ascii_alphanumeric() || "/-?:().,'+ ".contains(c),
+            Charset::Decimal => c.is_ascii_digit() || c == ',',
+        }
+    }
+}
+
+/// The furthest point a value could be matched to, and what was expected there
+#[derive(Debug, Default)]
+struct Failure {
+    pos: usize,
+    expected: Option<String>,
+}
+
+impl Failure {
+    fn expect(&mut self, pos: usize, expected: impl Into<String>) {
+        if self.expected.is_none() || pos > self.pos {
+            self.pos = pos;
+            self.expected = Some(expected.into());
+        }
+    }
+}
+
+impl FieldFormat {
+    /// Compile a specification such as `6!n3!a15d` or `[/34x$]4*35x`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let chars: Vec<char> = spec.chars().collect();
+        let mut pos = 0;
+        let items = parse_items(&chars, &mut pos)?;
+        if pos < chars.len() {
+            return Err(format!("unbalanced ']' in format \"{}\"", spec));
+        }
+        Ok(Self {
+            spec: spec.to_string(),
+            items,
+        })
+    }
+
+    /// The specification this format was compiled from
+    pub fn spec(&self) -> &str {
+        &self.spec
+    }
+
+    /// Check `value` (lines separated by `\n`), describing the first mismatch
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        let text: Vec<char> = value.chars().collect();
+        let mut failure = Failure::default();
+        let ends = match_items(&self.items, &text, vec![0], &mut failure);
+        if ends.contains(&text.len()) {
+            return Ok(());
+        }
+
+        let furthest = ends.iter().copied().max();
+        match (failure.expected, furthest) {
+            (Some(expected), furthest) if furthest.is_none_or(|end| failure.pos >= end) => {
+                if failure.pos >= text.len() {
+                    Err(format!("value ends where {} is expected", expected))
+                } else {
+                    Err(format!("expected {} at character {}", expected, failure.pos + 1))
+                }
+            }
+            (_, Some(end)) => {
+                let rest: String = text[end..].iter().take(20).collect();
+                Err(format!("unexpected \"{}\" at character {}", rest, end + 1))
+            }
+            _ => Err("value does not match".to_string()),
+        }
+    }
+}
+
+/// Parse items up to the end of `chars` or a closing `]`
+fn parse_items(chars: &[char], pos: &mut usize) -> Result<Vec<Item>, String> {
+    let mut items = Vec::new();
+    while let Some(&c) = chars.get(*pos) {
+        match c {
+            ']' => break,
+            '[' => {
+                *pos += 1;
+                let group = parse_items(chars, pos)?;
+                if chars.get(*pos) != Some(&']') {
+                    return Err("unclosed '[' in format".to_string());
+                }
+                *pos += 1;
+                items.push(Item::Optional(group));
+            }
+            '$' => {
+                *pos += 1;
+                items.push(Item::NewLine);
+            }
+            '0'..='9' => items.push(parse_chars(chars, pos)?),
+            _ => {
+                *pos += 1;
+                items.push(Item::Literal(c));
+            }
+        }
+    }
+    Ok(items)
+}
+
+/// Parse `n`, `n!`, or `n*m` followed by a character set letter
+fn parse_chars(chars: &[char], pos: &mut usize) -> Result<Item, String> {
+    let start = *pos;
+    let mut lines = 1;
+    let mut max = parse_number(chars, pos)?;
+    if chars.get(*pos) == Some(&'*') {
+        *pos += 1;
+        lines = max;
+        max = parse_number(chars, pos)?;
+    }
+    let fixed = chars.get(*pos) == Some(&'!');
+    if fixed {
+        *pos += 1;
+    }
+    let charset = match chars.get(*pos) {
+        Some('n') => Charset::Numeric,
+        Some('a') => Charset::Alpha,
+        Some('c') => Charset::Alphanumeric,
+        Some('x') => Charset::SwiftX,
+        Some('d') => Charset::Decimal,
+        other => return Err(format!("unknown character set {:?} in format", other)),
+    };
+    *pos += 1;
+
+    Ok(Item::Chars {
+        spec: chars[start..*pos].iter().collect(),
+        lines,
+        min: if fixed { max } else { 1 },
+        max,
+        charset,
+    })
+}
+
+fn parse_number(chars: &[char], pos: &mut usize) -> Result<usize, String> {
+    let start = *pos;
+    while chars.get(*pos).is_some_and(char::is_ascii_digit) {
+        *pos += 1;
+    }
+    let digits: String = chars[start..*pos].iter().collect();
+    digits.parse().map_err(|_| "missing length in format".to_string())
+}
+
+/// Every position the items can end at, starting from any of `starts`
+fn match_items(
+    items: &[Item],
+    text: &[char],
+    mut starts: Vec<usize>,
+    failure: &mut Failure,
+) -> Vec<usize> {
+    for item in items {
+        let mut ends: Vec<usize> = starts
+            .iter()
+            .flat_map(|&start| match_item(item, text, start, failure))
+            .collect();
+        ends.sort_unstable();
+        ends.dedup();
+        if ends.is_empty() {
+            return ends;
+        }
+        starts = ends;
+    }
+    starts
+}
+
+fn match_item(item: &Item, text: &[char], start: usize, failure: &mut Failure) -> Vec<usize> {
+    match item {
+        Item::Literal(c) => {
+            if text.get(start) == Some(c) {
+                vec![start + 1]
+            } else {
+                failure.expect(start, format!("'{}'", c));
+                Vec::new()
+            }
+        }
+        Item::NewLine => {
+            if text.get(start) == Some(&'\n') {
+                vec![start + 1]
+            } else {
+                failure.expect(start, "a new line");
+                Vec::new()
+            }
+        }
+        Item::Optional(items) => {
+            let mut ends = match_items(items, text, vec![start], failure);
+            ends.push(start);
+            ends
+        }
+        Item::Chars {
+            spec,
+            lines,
+            min,
+            max,
+            charset,
+        } => {
+            let mut ends = Vec::new();
+            let mut line_start = start;
+            for line in 0..*lines {
+                if line > 0 {
+                    if text.get(line_start) != Some(&'\n') {
+                        break;
+                    }
+                    line_start += 1;
+                }
+                let run = text[line_start.min(text.len())..]
+                    .iter()
+                    .take_while(|&&c| charset.contains(c))
+                    .count();
+                let line_ends: Vec<usize> = (*min..=run.min(*max))
+                    .map(|len| line_start + len)
+                    .filter(|&end| {
+                        *charset != Charset::Decimal || is_decimal(&text[line_start..end])
+                    })
+                    .collect();
+                let Some(&longest) = line_ends.last() else {
+                    if line == 0 {
+                        let at = if run < *min { line_start + run } else { line_start };
+                        failure.expect(at, describe(spec, *charset));
+                    }
+                    break;
+                };
+                ends.extend(line_ends);
+                line_start = longest;
+            }
+            ends
+        }
+    }
+}
+
+/// Digits with exactly one decimal comma, starting with a digit
+fn is_decimal(chars: &[char]) -> bool {
+    chars.first().is_some_and(char::is_ascii_digit)
+        && chars.iter().filter(|&&c| c == ',').count() == 1
+}
+
+fn describe(spec: &str, charset: Charset) -> String {
+    let kind = match charset {
+        Charset::Numeric => "digits",
+        Charset::Alpha => "letters",
+        Charset::Alphanumeric => "letters or digits",
+        Charset::SwiftX => "text",
+        Charset::Decimal => "an amount with a decimal comma",
+    };
+    format!("{} ({})", spec, kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_formats_compile() {
+        assert!(FORMATS.len() > 40);
+        assert_eq!(field_format(Some("MT940"), "86").unwrap().spec(), "6*65x");
+        assert!(field_format(Some("MT103"), "86").is_none());
+        assert!(FieldFormat::parse("[3!a").is_err());
+        assert!(FieldFormat::parse("3!q").is_err());
+    }
+
+    #[test]
+    fn test_validate_values() {
+        let format = |tag| field_format(Some("MT940"), tag).unwrap();
+        assert!(format("32A").validate("240115EUR1234,56").is_ok());
+        assert!(format("32A").validate("240115EUR1234,").is_ok());
+        assert_eq!(
+            format("32A").validate("240115EU1234,56").unwrap_err(),
+            "expected 3!a (letters) at character 9"
+        );
+        assert_eq!(
+            format("32A").validate("240115EUR1234.56").unwrap_err(),
+            "expected 15d (an amount with a decimal comma) at character 10"
+        );
+        assert_eq!(
+            format("20").validate("REFERENCE-TOO-LONG").unwrap_err(),
+            "unexpected \"NG\" at character 17"
+        );
+        assert!(format("20").validate("").is_err());
+
+        // Optional account line, then name and address lines
+        assert!(format("59").validate("/DE89370400440532013000\nJOHN DOE\nBERLIN").is_ok());
+        assert!(format("59").validate("JOHN DOE").is_ok());
+        assert!(format("59").validate("/1\nA\nB\nC\nD\nE").is_err());
+
+        assert!(format("61").validate("2401150115D1234,56NTRFREF123//BANKREF\nDETAILS").is_ok());
+        assert!(format("61").validate("240115RC12,00NMSCNONREF").is_ok());
+        assert!(format("61").validate("2401160116ED250,NTRFNONREF").is_ok());
+        assert!(format("61").validate("240115C12.00NMSCNONREF").is_err());
+        assert!(format("28C").validate("123/1").is_ok());
+    }
+}
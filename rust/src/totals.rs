@@ -0,0 +1,329 @@
+//! Trailer Control Totals
+//!
+//! Batch files end with a trailer repeating the number of detail records and their
+//! amount and hash totals; ISO 20022 messages carry `NbOfTxs` and `CtrlSum` in the
+//! group header. [`ControlTotals`] accumulates the `control_checks` of `ParserConfig`
+//! over the parsed records and compares them with the expected values, flagging the
+//! total record and `ParseMetadata.diagnostics` on a mismatch.
+
+use crate::decimal::Decimal;
+use crate::numbers::NumberFormat;
+use crate::types::*;
+
+/// Running totals of the configured control checks
+#[derive(Debug, Default)]
+pub struct ControlTotals {
+    checks: Vec<(ControlCheck, Tally)>,
+    /// Values that could not be added up
+    problems: Vec<Diagnostic>,
+}
+
+/// Observed totals of one check and the expected value, once seen
+#[derive(Debug)]
+struct Tally {
+    count: usize,
+    sum: Decimal,
+    expected: Option<Expected>,
+}
+
+/// Expected value read from a total record
+#[derive(Debug)]
+struct Expected {
+    record_id: String,
+    field_id: String,
+    /// Source text, compared digit by digit for hash totals
+    text: String,
+    value: Option<Decimal>,
+}
+
+impl ControlTotals {
+    pub fn from_config(config: &ParserConfig) -> Result<Self, ParseError> {
+        let checks = config
+            .control_checks
+            .iter()
+            .flatten()
+            .map(|check| {
+                match (check.check.as_str(), &check.field) {
+                    ("count", _) | ("sum" | "hash", Some(_)) => {}
+                    ("sum" | "hash", None) => {
+                        return Err(ParseError::ConfigError(format!(
+                            "{} control check needs a field",
+                            check.check
+                        )))
+                    }
+                    (other, _) => {
+                        return Err(ParseError::ConfigError(format!(
+                            "unknown control check \"{}\"",
+                            other
+                        )))
+                    }
+                }
+                let tally = Tally {
+                    count: 0,
+                    sum: Decimal::zero(),
+                    expected: None,
+                };
+                Ok((check.clone(), tally))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            checks,
+            problems: Vec::new(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty()
+    }
+
+    /// Add `record` to the totals, or take the expected values it carries
+    pub fn observe(&mut self, record: &ParsedRecord) {
+        for (check, tally) in &mut self.checks {
+            if record.record_type == check.total_record_type {
+                if let Some(field) = find_field(record, &check.total_field) {
+                    tally.expected = Some(Expected {
+                        record_id: record.id.clone(),
+                        field_id: field.id.clone(),
+                        text: field.original_value.trim().to_string(),
+                        value: to_decimal(field),
+                    });
+                }
+                continue;
+            }
+            let checked = match check.record_type.as_deref() {
+                Some(record_type) => record.record_type == record_type,
+                None => !matches!(record.record_type.as_str(), "header" | "footer"),
+            };
+            if !checked {
+                continue;
+            }
+
+            tally.count += 1;
+            let Some(field) = check.field.as_deref().and_then(|name| find_field(record, name))
+            else {
+                continue;
+            };
+            let value = match check.check.as_str() {
+                "sum" => to_decimal(field),
+                "hash" => hash_value(&field.original_value),
+                _ => continue,
+            };
+            match value {
+                Some(value) => tally.sum = &tally.sum + &value,
+                None if matches!(field.value, FieldValue::Null) => {}
+                None => self.problems.push(
+                    Diagnostic::warning(
+                        DiagnosticCode::ControlTotal,
+                        format!(
+                            "Value \"{}\" of \"{}\" in {} is left out of the {} total",
+                            field.original_value.trim(),
+                            field.name,
+                            record.id,
+                            check.check
+                        ),
+                    )
+                    .with_field(field.id.clone()),
+                ),
+            }
+        }
+    }
+
+    /// Compare the totals with their expected values
+    ///
+    /// Mismatches are added as errors to the total record when it is in `records`
+    /// (streamed records may already be drained) and returned, with any values left
+    /// out of a total, for `ParseMetadata.diagnostics`.
+    pub fn finish(self, records: &mut [ParsedRecord]) -> Vec<Diagnostic> {
+        let mut diagnostics = self.problems;
+        for (check, tally) in self.checks {
+            let subject = match check.check.as_str() {
+                "count" => format!(
+                    "Count of {} records",
+                    check.record_type.as_deref().unwrap_or("detail")
+                ),
+                kind => format!(
+                    "{} of \"{}\"",
+                    if kind == "sum" { "Sum" } else { "Hash total" },
+                    check.field.as_deref().unwrap_or_default()
+                ),
+            };
+            let Some(expected) = tally.expected else {
+                diagnostics.push(Diagnostic::error(
+                    DiagnosticCode::ControlTotal,
+                    format!(
+                        "{}: no {} record with field \"{}\"",
+                        subject, check.total_record_type, check.total_field
+                    ),
+                ));
+                continue;
+            };
+
+            let (actual, matches) = match check.check.as_str() {
+                "count" => {
+                    let actual = tally.count.to_string();
+                    let matches = expected.value == actual.parse().ok();
+                    (actual, matches)
+                }
+                "sum" => (tally.sum.to_string(), expected.value.as_ref() == Some(&tally.sum)),
+                _ => {
+                    // Hash totals keep only as many rightmost digits as the trailer has room for
+                    let digits = tally.sum.to_string();
+                    let width = expected.text.len();
+                    let actual = format!("{:0>width$}", digits, width = width);
+                    let actual = actual[actual.len() - width..].to_string();
+                    let matches = actual == expected.text;
+                    (actual, matches)
+                }
+            };
+            if matches {
+                continue;
+            }
+
+            let diagnostic = Diagnostic::error(
+                DiagnosticCode::ControlTotal,
+                format!(
+                    "{} is {}, but {} field \"{}\" says {}",
+                    subject, actual, check.total_record_type, check.total_field, expected.text
+                ),
+            )
+            .with_field(expected.field_id);
+            if let Some(record) = records.iter_mut().find(|r| r.id == expected.record_id) {
+                record.add_diagnostics(vec![diagnostic.clone()]);
+            }
+            diagnostics.push(diagnostic);
+        }
+        diagnostics
+    }
+}
+
+/// Run the configured control checks over all `records`
+///
+/// Returns the diagnostics for `ParseMetadata.diagnostics`, `None` when all pass.
+pub fn check_records(
+    records: &mut [ParsedRecord],
+    config: &ParserConfig,
+) -> Result<Option<Vec<Diagnostic>>, ParseError> {
+    let mut totals = ControlTotals::from_config(config)?;
+    if totals.is_empty() {
+        return Ok(None);
+    }
+    for record in records.iter() {
+        totals.observe(record);
+    }
+    let diagnostics = totals.finish(records);
+    Ok((!diagnostics.is_empty()).then_some(diagnostics))
+}
+
+fn find_field<'a>(record: &'a ParsedRecord, name: &str) -> Option<&'a ParsedField> {
+    record.fields.iter().find(|field| field.name == name)
+}
+
+/// Exact value of a numeric field
+fn to_decimal(field: &ParsedField) -> Option<Decimal> {
+    match &field.value {
+        FieldValue::Decimal(d) => Some(d.clone()),
+        FieldValue::Integer(i) => i.to_string().parse().ok(),
+        FieldValue::Null => None,
+        // Floats and text are re-read from the source to stay exact
+        _ => NumberFormat::default().parse(&field.original_value),
+    }
+}
+
+/// Digits of an account or routing number as an integer
+fn hash_value(text: &str) -> Option<Decimal> {
+    let digits: String = text.chars().filter(char::is_ascii_digit).collect();
+    Decimal::from_scaled(&digits, false, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(index: usize, record_type: &str, fields: &[(&str, &str)]) -> ParsedRecord {
+        ParsedRecord {
+            id: format!("record-{}", index),
+            index,
+            fields: fields
+                .iter()
+                .enumerate()
+                .map(|(i, (name, value))| ParsedField {
+                    id: format!("field-{}-{}", index, i),
+                    name: name.to_string(),
+                    value: FieldValue::String(value.to_string()),
+                    field_type: "string".to_string(),
+                    original_value: value.to_string(),
+                    position: None,
+                })
+                .collect(),
+            raw: String::new(),
+            record_type: record_type.to_string(),
+            is_valid: true,
+            errors: None,
+            position: None,
+            diagnostics: None,
+        }
+    }
+
+    fn check(kind: &str, field: Option<&str>, total_field: &str) -> ControlCheck {
+        ControlCheck {
+            check: kind.to_string(),
+            field: field.map(str::to_string),
+            record_type: None,
+            total_field: total_field.to_string(),
+            total_record_type: "footer".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_count_sum_and_hash() {
+        let mut records = vec![
+            record(0, "data", &[("Account", "12-345"), ("Amount", "10.50")]),
+            record(1, "data", &[("Account", "99-999"), ("Amount", "7.25")]),
+            record(2, "footer", &[("Count", "2"), ("Total", "17.75"), ("Hash", "2344")]),
+        ];
+        let config = ParserConfig {
+            control_checks: Some(vec![
+                check("count", None, "Count"),
+                check("sum", Some("Amount"), "Total"),
+                check("hash", Some("Account"), "Hash"),
+            ]),
+            ..Default::default()
+        };
+        // 12345 + 99999 = 112344, kept to the 4 digits of the trailer
+        assert!(check_records(&mut records, &config).unwrap().is_none());
+        assert!(records[2].is_valid);
+    }
+
+    #[test]
+    fn test_mismatch_flags_trailer() {
+        let mut records = vec![
+            record(0, "data", &[("Amount", "10.50")]),
+            record(1, "footer", &[("Total", "10.05")]),
+        ];
+        let config = ParserConfig {
+            control_checks: Some(vec![
+                check("sum", Some("Amount"), "Total"),
+                check("count", None, "Missing"),
+            ]),
+            ..Default::default()
+        };
+        let diagnostics = check_records(&mut records, &config).unwrap().unwrap();
+
+        assert_eq!(
+            diagnostics[0].message,
+            "Sum of \"Amount\" is 10.50, but footer field \"Total\" says 10.05"
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "Count of detail records: no footer record with field \"Missing\""
+        );
+        assert!(!records[1].is_valid);
+        assert_eq!(records[1].errors.as_ref().unwrap().len(), 1);
+
+        let invalid = ParserConfig {
+            control_checks: Some(vec![check("sum", None, "Total")]),
+            ..Default::default()
+        };
+        assert!(check_records(&mut records, &invalid).is_err());
+    }
+}
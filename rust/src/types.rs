@@ -1,7 +1,11 @@
 //! Shared types for WASM parsers
 //! These mirror the TypeScript types for seamless interop
 
+use crate::dates::DateTime;
+use crate::decimal::Decimal;
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Parser configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,17 +17,61 @@ pub struct ParserConfig {
     #[serde(default)]
     pub description: Option<String>,
     // CSV specific
+    /// Field separator; may be several characters (`"|~|"`)
     #[serde(default = "default_delimiter")]
     pub delimiter: String,
+    /// Regex separating fields (`"\\s+"` for whitespace-aligned columns); overrides `delimiter`
+    #[serde(default)]
+    pub delimiter_pattern: Option<String>,
     #[serde(default = "default_true")]
     pub has_header: bool,
     #[serde(default = "default_quote_char")]
     pub quote_char: String,
+    /// Escapes the next character inside quoted fields; ignored when equal to `quote_char`
     #[serde(default = "default_escape_char")]
     pub escape_char: String,
+    /// Lines starting with this character are skipped (e.g. `"#"`)
+    #[serde(default)]
+    pub comment_char: Option<String>,
+    /// Preamble lines skipped before the header row
+    #[serde(default)]
+    pub skip_rows: usize,
+    /// Whitespace trimming: `none`, `headers`, `fields` or `all`
+    #[serde(default = "default_trim")]
+    pub trim: String,
+    /// Reject empty or duplicate headers and mark rows whose field count differs
+    /// from the header as invalid
+    #[serde(default)]
+    pub strict: bool,
+    /// Rules typing CSV rows as header, transaction or footer records
+    #[serde(default)]
+    pub row_rules: Option<Vec<CsvRowRule>>,
+    /// Record counts and totals checked against trailer fields after parsing
+    #[serde(default)]
+    pub control_checks: Option<Vec<ControlCheck>>,
+    /// Date format per column name (e.g. `{"Booking Date": "DD/MM/YYYY"}`)
+    #[serde(default)]
+    pub column_formats: Option<HashMap<String, String>>,
+    /// Type CSV columns from their values; `false` keeps every cell as text
+    #[serde(default = "default_true")]
+    pub infer_types: bool,
+    /// Decimal mark of numbers (`","` for `1.234,56`); detected per column when unset
+    #[serde(default)]
+    pub decimal_separator: Option<String>,
+    /// Digit grouping character of numbers (`"."`, `" "`, `"'"`)
+    #[serde(default)]
+    pub thousands_separator: Option<String>,
     // Fixed width specific
     #[serde(default)]
     pub field_definitions: Option<Vec<FieldDefinition>>,
+    #[serde(default)]
+    pub record_layouts: Option<Vec<RecordLayout>>,
+    #[serde(default)]
+    pub discriminator: Option<Discriminator>,
+    /// Characters per record (RECFM=F) instead of one record per line; defaults to the
+    /// layout's length when it has packed or binary fields
+    #[serde(default)]
+    pub record_length: Option<usize>,
     // FIN specific
     #[serde(default)]
     pub message_type: Option<String>,
@@ -46,6 +94,10 @@ fn default_escape_char() -> String {
     "\\".to_string()
 }
 
+fn default_trim() -> String {
+    "all".to_string()
+}
+
 fn default_true() -> bool {
     true
 }
@@ -57,10 +109,24 @@ impl Default for ParserConfig {
             name: "Parser".to_string(),
             description: None,
             delimiter: ",".to_string(),
+            delimiter_pattern: None,
             has_header: true,
             quote_char: "\"".to_string(),
             escape_char: "\\".to_string(),
+            comment_char: None,
+            skip_rows: 0,
+            trim: default_trim(),
+            strict: false,
+            row_rules: None,
+            control_checks: None,
+            column_formats: None,
+            infer_types: true,
+            decimal_separator: None,
+            thousands_separator: None,
             field_definitions: None,
+            record_layouts: None,
+            discriminator: None,
+            record_length: None,
             message_type: None,
             chunk_size: None,
             encoding: None,
@@ -69,7 +135,7 @@ impl Default for ParserConfig {
 }
 
 /// Field definition for fixed-width parsing
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FieldDefinition {
     pub id: String,
@@ -84,6 +150,88 @@ pub struct FieldDefinition {
     pub required: bool,
     #[serde(default)]
     pub description: Option<String>,
+    /// Numeric storage: "display" (default), "zoned", "packed" (COMP-3), "binary" (COMP)
+    /// or "edited" (numeric-edited text such as `ZZ,ZZ9.99-`, read against the PIC in `format`)
+    #[serde(default)]
+    pub usage: Option<String>,
+    /// Implied decimal places (COBOL `V`)
+    #[serde(default)]
+    pub scale: Option<u32>,
+    #[serde(default)]
+    pub signed: bool,
+}
+
+/// Location of the record-type code in multi-layout fixed-width files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Discriminator {
+    pub start: usize,
+    pub length: usize,
+}
+
+/// One record layout of a multi-layout fixed-width file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordLayout {
+    pub id: String,
+    pub name: String,
+    /// Discriminator value selecting this layout (compared after trimming)
+    pub discriminator_value: String,
+    #[serde(rename = "type", default = "default_record_type")]
+    pub record_type: String, // header, transaction, footer, data
+    pub field_definitions: Vec<FieldDefinition>,
+}
+
+/// Record type rule for CSV files mixing header, detail and trailer rows
+///
+/// A rule matches when column `column` equals `value` (both trimmed) and
+/// `pattern` matches the row's source text (`raw`, quotes included), whichever are
+/// set; the first matching rule wins and unmatched rows are `data`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvRowRule {
+    #[serde(default)]
+    pub column: usize,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(rename = "type", default = "default_record_type")]
+    pub record_type: String, // header, transaction, footer, data
+    /// Column names of matching rows; the file headers when unset
+    #[serde(default)]
+    pub columns: Option<Vec<String>>,
+}
+
+/// Control total compared with a field of a trailer (or group header) record
+///
+/// `count` counts the checked records, `sum` adds up `field`, and `hash` adds up
+/// the digits of `field` read as a number (e.g. account numbers), compared on as
+/// many rightmost digits as the expected total has.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlCheck {
+    /// `count`, `sum` or `hash`
+    pub check: String,
+    /// Field summed or hashed
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Type of the checked records; all but header and footer records when unset
+    #[serde(default)]
+    pub record_type: Option<String>,
+    /// Field holding the expected value
+    pub total_field: String,
+    /// Type of the record holding `total_field` (the last such record is used)
+    #[serde(default = "default_total_record_type")]
+    pub total_record_type: String,
+}
+
+fn default_total_record_type() -> String {
+    "footer".to_string()
+}
+
+fn default_record_type() -> String {
+    "data".to_string()
 }
 
 /// Parsed field
@@ -100,13 +248,20 @@ pub struct ParsedField {
     pub position: Option<Position>,
 }
 
-/// Field value - can be string, number, boolean, or null
+/// Field value - can be string, number, date, boolean, or null
+///
+/// Amounts use `Decimal`, which serializes as a string (e.g. `"1234.56"`) to stay exact.
+/// Dates and datetimes serialize as ISO 8601 strings (`"2024-01-15"`,
+/// `"2024-01-15T10:30:00+01:00"`).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum FieldValue {
     String(String),
     Number(f64),
     Integer(i64),
+    Decimal(Decimal),
+    Date(#[serde(with = "crate::dates::iso_date")] NaiveDate),
+    DateTime(DateTime),
     Boolean(bool),
     Null,
 }
@@ -135,17 +290,52 @@ impl From<i64> for FieldValue {
     }
 }
 
+impl From<Decimal> for FieldValue {
+    fn from(d: Decimal) -> Self {
+        FieldValue::Decimal(d)
+    }
+}
+
+impl From<NaiveDate> for FieldValue {
+    fn from(d: NaiveDate) -> Self {
+        FieldValue::Date(d)
+    }
+}
+
+impl From<DateTime> for FieldValue {
+    fn from(dt: DateTime) -> Self {
+        FieldValue::DateTime(dt)
+    }
+}
+
 impl From<bool> for FieldValue {
     fn from(b: bool) -> Self {
         FieldValue::Boolean(b)
     }
 }
 
-/// Position in source
+/// Position in source: byte range in the decoded input
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Position {
     pub start: usize,
     pub end: usize,
+    /// 1-based line of `start`, when known
+    #[serde(default)]
+    pub line: Option<usize>,
+    /// 1-based character column of `start`, when known
+    #[serde(default)]
+    pub column: Option<usize>,
+}
+
+impl Position {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end,
+            line: None,
+            column: None,
+        }
+    }
 }
 
 /// Parsed record
@@ -161,6 +351,130 @@ pub struct ParsedRecord {
     pub is_valid: bool,
     #[serde(default)]
     pub errors: Option<Vec<String>>,
+    /// Source span of the whole record
+    #[serde(default)]
+    pub position: Option<Position>,
+    /// Structured form of `errors`, plus any warnings
+    #[serde(default)]
+    pub diagnostics: Option<Vec<Diagnostic>>,
+}
+
+impl ParsedRecord {
+    /// Attach diagnostics; error messages are mirrored into `errors` and invalidate the record
+    pub fn add_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        for diagnostic in &diagnostics {
+            if diagnostic.severity == Severity::Error {
+                self.errors
+                    .get_or_insert_with(Vec::new)
+                    .push(diagnostic.message.clone());
+                self.is_valid = false;
+            }
+        }
+        if !diagnostics.is_empty() {
+            self.diagnostics
+                .get_or_insert_with(Vec::new)
+                .extend(diagnostics);
+        }
+    }
+}
+
+/// Diagnostic severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// Stable diagnostic codes, serialized as e.g. `"CSV_SYNTAX"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DiagnosticCode {
+    CsvSyntax,
+    CsvUtf8,
+    CsvFieldCount,
+    ControlTotal,
+    XmlSyntax,
+    FinBlock,
+    FinField,
+    FinFormat,
+    FieldRequired,
+    FieldConversion,
+    UnknownRecordLayout,
+    ShortRecord,
+    Encoding,
+    Config,
+    Copybook,
+    Cancelled,
+}
+
+/// Located, machine-readable parse problem
+///
+/// `line` and `column` are 1-based (column counts characters); `span` is a byte range
+/// in the decoded input.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub code: DiagnosticCode,
+    pub severity: Severity,
+    pub message: String,
+    #[serde(default)]
+    pub line: Option<usize>,
+    #[serde(default)]
+    pub column: Option<usize>,
+    #[serde(default)]
+    pub span: Option<Position>,
+    #[serde(default)]
+    pub field_id: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(code: DiagnosticCode, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            severity,
+            message: message.into(),
+            line: None,
+            column: None,
+            span: None,
+            field_id: None,
+        }
+    }
+
+    pub fn error(code: DiagnosticCode, message: impl Into<String>) -> Self {
+        Self::new(code, Severity::Error, message)
+    }
+
+    pub fn warning(code: DiagnosticCode, message: impl Into<String>) -> Self {
+        Self::new(code, Severity::Warning, message)
+    }
+
+    pub fn at(mut self, line: usize, column: usize) -> Self {
+        self.line = Some(line);
+        self.column = Some(column);
+        self
+    }
+
+    pub fn with_span(mut self, start: usize, end: usize) -> Self {
+        self.span = Some(Position::new(start, end));
+        self
+    }
+
+    pub fn with_field(mut self, field_id: impl Into<String>) -> Self {
+        self.field_id = Some(field_id.into());
+        self
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, " (line {}, column {})", line, column)?;
+        }
+        Ok(())
+    }
 }
 
 /// Full parsed data result
@@ -172,9 +486,28 @@ pub struct ParsedData {
     pub records: Vec<ParsedRecord>,
     #[serde(default)]
     pub headers: Option<Vec<String>>,
+    /// Inferred column types (CSV)
+    #[serde(default)]
+    pub columns: Option<Vec<ColumnSchema>>,
     pub metadata: ParseMetadata,
 }
 
+/// Type inferred for a CSV column and applied to all of its cells
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+    /// Share of non-empty cells whose value matches `field_type` (0.0 - 1.0)
+    pub confidence: f64,
+    /// Share of empty cells (0.0 - 1.0)
+    pub null_ratio: f64,
+    /// Decimal mark used by a number column
+    #[serde(default)]
+    pub decimal_separator: Option<String>,
+}
+
 /// Parse metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -193,6 +526,27 @@ pub struct ParseMetadata {
     pub parser_engine: String,
     #[serde(default)]
     pub chunks_processed: Option<usize>,
+    /// Problems with the file as a whole, e.g. failed control totals
+    #[serde(default)]
+    pub diagnostics: Option<Vec<Diagnostic>>,
+    /// Messages of a multi-message file (SWIFT FIN), in file order
+    #[serde(default)]
+    pub messages: Option<Vec<MessageSummary>>,
+}
+
+/// One message of a multi-message file and the records it produced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageSummary {
+    pub index: usize,
+    /// e.g. `MT103`, when the application header names it
+    #[serde(default)]
+    pub message_type: Option<String>,
+    /// Index of the message's first record in `ParsedData.records`
+    pub first_record: usize,
+    pub record_count: usize,
+    #[serde(default)]
+    pub position: Option<Position>,
 }
 
 fn default_wasm() -> String {
@@ -211,6 +565,8 @@ impl Default for ParseMetadata {
             encoding: None,
             parser_engine: "wasm".to_string(),
             chunks_processed: None,
+            diagnostics: None,
+            messages: None,
         }
     }
 }
@@ -279,6 +635,31 @@ pub enum ParseError {
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
-    #[error("Parse cancelled")]
-    Cancelled,
+    #[error("Invalid copybook: {0}")]
+    CopybookError(String),
+
+    /// Carries the metadata of the records parsed before cancellation
+    #[error("Parse cancelled after {} records", .0.total_records)]
+    Cancelled(Box<ParseMetadata>),
+
+    /// Fatal error with a source location
+    #[error("{0}")]
+    Located(Box<Diagnostic>),
+}
+
+impl ParseError {
+    /// Structured form of the error for JS callers and pipelines
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let code = match self {
+            ParseError::Located(diagnostic) => return (**diagnostic).clone(),
+            ParseError::CsvError(_) => DiagnosticCode::CsvSyntax,
+            ParseError::XmlError(_) => DiagnosticCode::XmlSyntax,
+            ParseError::FinError(_) => DiagnosticCode::FinBlock,
+            ParseError::EncodingError(_) => DiagnosticCode::Encoding,
+            ParseError::ConfigError(_) => DiagnosticCode::Config,
+            ParseError::CopybookError(_) => DiagnosticCode::Copybook,
+            ParseError::Cancelled(_) => DiagnosticCode::Cancelled,
+        };
+        Diagnostic::error(code, self.to_string())
+    }
 }
@@ -1,5 +1,7 @@
 //! Utility functions for parser detection and helpers
 
+use crate::types::ParsedRecord;
+
 /// Detect parser type from data content
 pub fn detect_type(data: &str) -> &'static str {
     let trimmed = data.trim();
@@ -14,8 +16,9 @@ pub fn detect_type(data: &str) -> &'static str {
         return "iso20022";
     }
 
-    // Check for SWIFT FIN message
-    if trimmed.starts_with("{1:") || trimmed.contains("{4:") {
+    // Check for SWIFT FIN message, bare or framed by an RJE (`$`) or DOS-PCC (0x01) envelope
+    let unframed = trimmed.trim_start_matches(['\u{1}', '$']).trim_start();
+    if unframed.starts_with("{1:") || trimmed.contains("{4:") {
         return "fin";
     }
 
@@ -71,7 +74,50 @@ pub fn calculate_size(data: &str) -> usize {
 
 /// Generate unique ID
 pub fn generate_id(prefix: &str) -> String {
-    format!("{}-{}", prefix, js_sys::Date::now() as u64)
+    format!("{}-{}", prefix, crate::clock::timestamp())
+}
+
+/// Maps byte offsets to 1-based line and column numbers
+pub struct LineIndex<'a> {
+    data: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(data: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(memchr::memchr_iter(b'\n', data.as_bytes()).map(|i| i + 1))
+            .collect();
+        Self { data, line_starts }
+    }
+
+    /// Line and character column of a byte offset
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.data.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = self
+            .data
+            .get(line_start..offset)
+            .map(|s| s.chars().count())
+            .unwrap_or(offset - line_start);
+        (line, column + 1)
+    }
+
+    /// Fill in line and column for every record and field position
+    pub fn locate(&self, records: &mut [ParsedRecord]) {
+        let positions = records.iter_mut().flat_map(|record| {
+            record
+                .position
+                .iter_mut()
+                .chain(record.fields.iter_mut().filter_map(|f| f.position.as_mut()))
+        });
+        for position in positions {
+            let (line, column) = self.line_column(position.start);
+            position.line = Some(line);
+            position.column = Some(column);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -94,6 +140,8 @@ mod tests {
     fn test_detect_fin() {
         let data = "{1:F01BANK}{2:I103}";
         assert_eq!(detect_type(data), "fin");
+        assert_eq!(detect_type("\u{1}{1:F01BANK}{2:I103}\u{3}   "), "fin");
+        assert_eq!(detect_type("$\r\n{1:F01BANK}{2:I940}$"), "fin");
     }
 
     #[test]
@@ -102,4 +150,13 @@ mod tests {
         assert_eq!(suggest_csv_delimiter("a;b;c\n1;2;3"), ';');
         assert_eq!(suggest_csv_delimiter("a\tb\tc\n1\t2\t3"), '\t');
     }
+
+    #[test]
+    fn test_line_index() {
+        let index = LineIndex::new("ab\ncé\r\nx");
+        assert_eq!(index.line_column(0), (1, 1));
+        assert_eq!(index.line_column(3), (2, 1));
+        assert_eq!(index.line_column(6), (2, 3));
+        assert_eq!(index.line_column(8), (3, 1));
+    }
 }
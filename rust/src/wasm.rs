@@ -0,0 +1,306 @@
+//! wasm-bindgen exports
+//!
+//! Thin JS-facing wrappers around the native parsing API.
+
+use wasm_bindgen::prelude::*;
+
+use crate::control::ParseController;
+use crate::streaming::StreamingParser;
+use crate::types::{ParseError, ParseProgress, ParsedRecord, ParserConfig};
+use crate::{clock, copybook, csv_parser, encoding, utils, xml_parser};
+
+/// Initialize the WASM module
+#[wasm_bindgen(start)]
+pub fn init() {
+    // Set up panic hook for better error messages
+    #[cfg(feature = "console_error_panic_hook")]
+    console_error_panic_hook::set_once();
+}
+
+/// Check if WASM module is ready
+#[wasm_bindgen(js_name = is_ready)]
+pub fn wasm_is_ready() -> bool {
+    crate::is_ready()
+}
+
+/// Get parser version
+#[wasm_bindgen(js_name = get_version)]
+pub fn wasm_get_version() -> String {
+    crate::get_version()
+}
+
+/// Main parse function - routes to appropriate parser
+#[wasm_bindgen]
+pub fn parse(data: &str, config_js: JsValue) -> Result<JsValue, JsValue> {
+    let config: ParserConfig = serde_wasm_bindgen::from_value(config_js)
+        .map_err(|e| JsError::new(&format!("Invalid config: {}", e)))?;
+
+    let result = crate::parse_with_config(data, &config);
+
+    match result {
+        Ok(parsed) => serde_wasm_bindgen::to_value(&parsed)
+            .map_err(|e| JsError::new(&format!("Serialization error: {}", e)).into()),
+        Err(e) => Err(parse_error_to_js(&e)),
+    }
+}
+
+/// Parse raw bytes (e.g. a `Uint8Array`), decoding them with `config.encoding`
+///
+/// The encoding is detected when not configured and reported in `metadata.encoding`.
+#[wasm_bindgen]
+pub fn parse_bytes(data: &[u8], config_js: JsValue) -> Result<JsValue, JsValue> {
+    let config: ParserConfig = serde_wasm_bindgen::from_value(config_js)
+        .map_err(|e| JsError::new(&format!("Invalid config: {}", e)))?;
+
+    match crate::parse_bytes(data, &config) {
+        Ok(parsed) => serde_wasm_bindgen::to_value(&parsed)
+            .map_err(|e| JsError::new(&format!("Serialization error: {}", e)).into()),
+        Err(e) => Err(parse_error_to_js(&e)),
+    }
+}
+
+/// Detect the character encoding of raw bytes
+#[wasm_bindgen]
+pub fn detect_encoding(data: &[u8]) -> String {
+    encoding::detect_encoding(data).label().to_string()
+}
+
+/// Cancellation token for `parse_csv_streaming` and `parse_xml_streaming`
+///
+/// A parse holds the JS thread until it returns, so `cancel()` takes effect when it is
+/// called from the progress callback (or before the parse starts). There is no pause:
+/// a parse cannot wait for a `resume()` that could only run after it has returned.
+#[wasm_bindgen(js_name = ParseController)]
+#[derive(Default)]
+pub struct WasmParseController {
+    inner: ParseController,
+}
+
+#[wasm_bindgen(js_class = ParseController)]
+impl WasmParseController {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmParseController {
+        Self::default()
+    }
+
+    /// Stop the parse at the next record; it then fails with code `CANCELLED`
+    pub fn cancel(&self) {
+        self.inner.cancel();
+    }
+
+    #[wasm_bindgen(getter, js_name = isCancelled)]
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.is_cancelled()
+    }
+}
+
+/// Parse CSV with streaming and progress callback
+///
+/// Cancelling `controller` or returning `false` from the callback cancels the parse.
+#[wasm_bindgen]
+pub fn parse_csv_streaming(
+    data: &str,
+    config_js: JsValue,
+    progress_callback: &js_sys::Function,
+    controller: &WasmParseController,
+) -> Result<JsValue, JsValue> {
+    let config: ParserConfig = serde_wasm_bindgen::from_value(config_js)
+        .map_err(|e| JsError::new(&format!("Invalid config: {}", e)))?;
+
+    let control = controller.inner.clone();
+    let progress_fn = progress_reporter(progress_callback, &control);
+
+    let result = csv_parser::parse_csv_with_control(data, &config, progress_fn, &control);
+
+    match result {
+        Ok(parsed) => serde_wasm_bindgen::to_value(&parsed)
+            .map_err(|e| JsError::new(&format!("Serialization error: {}", e)).into()),
+        Err(e) => Err(parse_error_to_js(&e)),
+    }
+}
+
+/// Parse XML (ISO 20022) with streaming
+///
+/// Cancelling `controller` or returning `false` from the callback cancels the parse.
+#[wasm_bindgen]
+pub fn parse_xml_streaming(
+    data: &str,
+    config_js: JsValue,
+    progress_callback: &js_sys::Function,
+    controller: &WasmParseController,
+) -> Result<JsValue, JsValue> {
+    let config: ParserConfig = serde_wasm_bindgen::from_value(config_js)
+        .map_err(|e| JsError::new(&format!("Invalid config: {}", e)))?;
+
+    let control = controller.inner.clone();
+    let progress_fn = progress_reporter(progress_callback, &control);
+
+    let result = xml_parser::parse_xml_with_control(data, &config, progress_fn, &control);
+
+    match result {
+        Ok(parsed) => serde_wasm_bindgen::to_value(&parsed)
+            .map_err(|e| JsError::new(&format!("Serialization error: {}", e)).into()),
+        Err(e) => Err(parse_error_to_js(&e)),
+    }
+}
+
+/// JS `Error` carrying the structured diagnostic as `error.code` and `error.diagnostic`,
+/// and for a cancelled parse the metadata gathered so far as `error.metadata`
+fn parse_error_to_js(error: &ParseError) -> JsValue {
+    let diagnostic = error.to_diagnostic();
+    let js_error = js_sys::Error::new(&error.to_string());
+    if let (Ok(code), Ok(diagnostic_js)) = (
+        serde_wasm_bindgen::to_value(&diagnostic.code),
+        serde_wasm_bindgen::to_value(&diagnostic),
+    ) {
+        let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str("code"), &code);
+        let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str("diagnostic"), &diagnostic_js);
+    }
+    if let ParseError::Cancelled(metadata) = error {
+        if let Ok(metadata_js) = serde_wasm_bindgen::to_value(metadata.as_ref()) {
+            let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str("metadata"), &metadata_js);
+        }
+    }
+    js_error.into()
+}
+
+/// Forward progress to JS, cancelling `control` when the callback returns `false`
+fn progress_reporter<'a>(
+    progress_callback: &'a js_sys::Function,
+    control: &'a ParseController,
+) -> impl Fn(ParseProgress) + 'a {
+    move |progress: ParseProgress| {
+        if let Ok(progress_js) = serde_wasm_bindgen::to_value(&progress) {
+            let returned = progress_callback.call1(&JsValue::NULL, &progress_js);
+            if returned.ok().and_then(|v| v.as_bool()) == Some(false) {
+                control.cancel();
+            }
+        }
+    }
+}
+
+/// Detect parser type from data
+#[wasm_bindgen]
+pub fn detect_parser_type(data: &str) -> String {
+    utils::detect_type(data).to_string()
+}
+
+/// Suggest CSV delimiter
+#[wasm_bindgen]
+pub fn suggest_delimiter(data: &str) -> String {
+    utils::suggest_csv_delimiter(data).to_string()
+}
+
+/// Benchmark parsing speed (for testing)
+#[wasm_bindgen]
+pub fn benchmark_csv(data: &str, iterations: u32) -> f64 {
+    let config = ParserConfig::default();
+    let start = clock::now();
+
+    for _ in 0..iterations {
+        let _ = csv_parser::parse_csv(data, &config);
+    }
+
+    let end = clock::now();
+
+    (end - start) / iterations as f64
+}
+
+/// Generate a fixed-width parser config from COBOL copybook text
+#[wasm_bindgen]
+pub fn import_copybook(copybook: &str, name: &str) -> Result<JsValue, JsValue> {
+    match copybook::copybook_to_config(copybook, name) {
+        Ok(config) => serde_wasm_bindgen::to_value(&config)
+            .map_err(|e| JsError::new(&format!("Serialization error: {}", e)).into()),
+        Err(e) => Err(parse_error_to_js(&e)),
+    }
+}
+
+/// Incremental parser fed with `Uint8Array` chunks (e.g. from `File.stream()`)
+#[wasm_bindgen(js_name = StreamingParser)]
+pub struct WasmStreamingParser {
+    inner: StreamingParser,
+    record_callback: Option<js_sys::Function>,
+}
+
+#[wasm_bindgen(js_class = StreamingParser)]
+impl WasmStreamingParser {
+    #[wasm_bindgen(constructor)]
+    pub fn new(config_js: JsValue) -> Result<WasmStreamingParser, JsValue> {
+        let config: ParserConfig = serde_wasm_bindgen::from_value(config_js)
+            .map_err(|e| JsError::new(&format!("Invalid config: {}", e)))?;
+
+        let inner = StreamingParser::new(config).map_err(|e| parse_error_to_js(&e))?;
+
+        Ok(Self {
+            inner,
+            record_callback: None,
+        })
+    }
+
+    /// Deliver each completed record to `callback` instead of buffering it for `drain`
+    pub fn set_record_callback(&mut self, callback: js_sys::Function) {
+        self.record_callback = Some(callback);
+    }
+
+    /// Feed the next chunk; returns the number of records waiting to be drained
+    pub fn push(&mut self, chunk: &[u8]) -> Result<usize, JsValue> {
+        let ready = self
+            .inner
+            .push(chunk)
+            .map_err(|e| parse_error_to_js(&e))?;
+        self.dispatch_records()?;
+        Ok(if self.record_callback.is_some() {
+            0
+        } else {
+            ready
+        })
+    }
+
+    /// Progress so far; pass the file size to get a total, percentage and time remaining
+    pub fn progress(&self, total_bytes: Option<usize>) -> Result<JsValue, JsError> {
+        serde_wasm_bindgen::to_value(&self.inner.progress(total_bytes))
+            .map_err(|e| JsError::new(&format!("Serialization error: {}", e)))
+    }
+
+    /// Take the records completed so far
+    pub fn drain(&mut self) -> Result<JsValue, JsError> {
+        serde_wasm_bindgen::to_value(&self.inner.drain())
+            .map_err(|e| JsError::new(&format!("Serialization error: {}", e)))
+    }
+
+    /// Flush buffered input and return the remaining records with metadata
+    pub fn finish(&mut self) -> Result<JsValue, JsValue> {
+        let mut parsed = self
+            .inner
+            .finish()
+            .map_err(|e| parse_error_to_js(&e))?;
+
+        if let Some(callback) = &self.record_callback {
+            for record in std::mem::take(&mut parsed.records) {
+                call_record_callback(callback, &record)?;
+            }
+        }
+
+        serde_wasm_bindgen::to_value(&parsed)
+            .map_err(|e| JsError::new(&format!("Serialization error: {}", e)).into())
+    }
+
+    fn dispatch_records(&mut self) -> Result<(), JsValue> {
+        if let Some(callback) = &self.record_callback {
+            for record in self.inner.drain() {
+                call_record_callback(callback, &record)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn call_record_callback(callback: &js_sys::Function, record: &ParsedRecord) -> Result<(), JsValue> {
+    let record_js = serde_wasm_bindgen::to_value(record)
+        .map_err(|e| JsError::new(&format!("Serialization error: {}", e)))?;
+    callback
+        .call1(&JsValue::NULL, &record_js)
+        .map_err(|_| JsError::new("Record callback threw an exception"))?;
+    Ok(())
+}
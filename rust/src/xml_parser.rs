@@ -2,14 +2,25 @@
 //!
 //! Uses quick-xml for SAX-style streaming XML parsing.
 //! 5-20x faster than JavaScript DOM-based parsers.
-
+//!
+//! The `NbOfTxs` and `CtrlSum` of the group header and of each `PmtInf` block are
+//! reconciled with the `CdtTrfTxInf`/`DrctDbtTxInf` transactions actually present;
+//! a mismatch is flagged as an error on the group header or block record.
+
+use crate::clock;
+use crate::control::ParseController;
+use crate::dates;
+use crate::decimal::Decimal;
+use crate::progress::ProgressTracker;
+use crate::totals;
 use crate::types::*;
+use crate::utils::{generate_id, LineIndex};
 use quick_xml::events::{BytesStart, Event};
 use quick_xml::reader::Reader;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-/// ISO 20022 field name mappings
 lazy_static::lazy_static! {
+    /// ISO 20022 field name mappings
     static ref ISO20022_FIELDS: HashMap<&'static str, &'static str> = {
         let mut m = HashMap::new();
         m.insert("MsgId", "Message ID");
@@ -66,336 +77,514 @@ lazy_static::lazy_static! {
 
 /// Parse XML (ISO 20022) data
 pub fn parse_xml(data: &str, config: &ParserConfig) -> Result<ParsedData, ParseError> {
-    let start_time = get_time();
+    parse_xml_with_progress(data, config, |_| {})
+}
+
+/// Parse XML with progress callback
+pub fn parse_xml_with_progress<F>(
+    data: &str,
+    config: &ParserConfig,
+    progress_fn: F,
+) -> Result<ParsedData, ParseError>
+where
+    F: Fn(ParseProgress),
+{
+    parse_xml_with_control(data, config, progress_fn, &ParseController::new())
+}
+
+/// Parse XML with progress callback, stopping early when `control` is cancelled
+pub fn parse_xml_with_control<F>(
+    data: &str,
+    config: &ParserConfig,
+    progress_fn: F,
+    control: &ParseController,
+) -> Result<ParsedData, ParseError>
+where
+    F: Fn(ParseProgress),
+{
+    let start_time = clock::now();
     let total_bytes = data.len();
 
+    progress_fn(
+        ParseProgress::new("initializing", 0, total_bytes, 0).with_message("Starting XML parse..."),
+    );
+
     let mut reader = Reader::from_str(data);
-    reader.config_mut().trim_text(true);
+    reader.trim_text(true);
 
-    let mut records = Vec::new();
-    let mut headers = std::collections::HashSet::new();
-    let mut path = Vec::new();
-    let mut current_text = String::new();
-    let mut record_index = 0usize;
-    let mut current_fields: Vec<ParsedField> = Vec::new();
-    let mut message_type = String::new();
+    let mut builder = XmlRecordBuilder::new();
+    let mut tracker = ProgressTracker::started_at(start_time, total_bytes);
 
     // Buffer for reading events
     let mut buf = Vec::new();
 
     loop {
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                path.push(name.clone());
-
-                // Detect message type from root or Document child
-                if path.len() <= 2 {
-                    if let Some(mt) = detect_message_type(&name, e) {
-                        message_type = mt;
-                    }
-                }
-
-                // Check for attributes (like currency)
-                for attr in e.attributes().flatten() {
-                    let attr_name = String::from_utf8_lossy(attr.key.as_ref()).to_string();
-                    let attr_value = String::from_utf8_lossy(&attr.value).to_string();
-
-                    let field_name = format!("{}[@{}]", path.join("."), attr_name);
-                    headers.insert(field_name.clone());
-
-                    current_fields.push(ParsedField {
-                        id: format!("attr-{}-{}", record_index, current_fields.len()),
-                        name: humanize_field(&attr_name),
-                        value: FieldValue::String(attr_value.clone()),
-                        field_type: "string".to_string(),
-                        original_value: attr_value,
-                        position: None,
-                    });
-                }
-            }
-
-            Ok(Event::Text(ref e)) => {
-                current_text = e.unescape().map(|s| s.to_string()).unwrap_or_default();
-            }
-
-            Ok(Event::End(ref e)) => {
-                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-
-                // Save field if we have text content
-                if !current_text.is_empty() {
-                    let field_path = path.join(".");
-                    headers.insert(field_path.clone());
-
-                    let (value, field_type) = infer_xml_type(&current_text);
+        let position = reader.buffer_position();
 
-                    current_fields.push(ParsedField {
-                        id: format!("field-{}-{}", record_index, current_fields.len()),
-                        name: humanize_field(&name),
-                        value,
-                        field_type,
-                        original_value: current_text.clone(),
-                        position: None,
-                    });
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Eof) => break,
 
-                    current_text.clear();
+            Ok(event) => {
+                let window = &data.as_bytes()[position..reader.buffer_position()];
+                let record_created = builder.handle_event(&event, event_span(&event, window, position));
+
+                if record_created && !control.checkpoint() {
+                    progress_fn(
+                        tracker
+                            .progress("cancelled", position, builder.record_index)
+                            .with_message("XML parsing cancelled"),
+                    );
+                    return Err(ParseError::Cancelled(Box::new(ParseMetadata {
+                        total_records: builder.record_index,
+                        valid_records: builder.record_index,
+                        invalid_records: 0,
+                        parse_time: clock::now() - start_time,
+                        file_size: Some(total_bytes),
+                        parser_engine: "wasm".to_string(),
+                        ..Default::default()
+                    })));
                 }
 
-                // Create record at certain depth levels or specific elements
-                let should_create_record = is_record_boundary(&name, &path);
-
-                if should_create_record && !current_fields.is_empty() {
-                    let record_type = determine_record_type(&path);
-
-                    records.push(ParsedRecord {
-                        id: format!("record-{}", record_index),
-                        index: record_index,
-                        fields: std::mem::take(&mut current_fields),
-                        raw: path.join("/"),
-                        record_type,
-                        is_valid: true,
-                        errors: None,
-                    });
-                    record_index += 1;
+                if record_created && tracker.due() {
+                    progress_fn(
+                        tracker
+                            .progress("parsing", reader.buffer_position(), builder.record_index)
+                            .with_message(&format!("Parsed {} records...", builder.record_index)),
+                    );
                 }
-
-                path.pop();
             }
 
-            Ok(Event::Eof) => break,
-
             Err(e) => {
-                return Err(ParseError::XmlError(format!(
-                    "Error at position {}: {:?}",
-                    reader.buffer_position(),
-                    e
+                let offset = reader.buffer_position();
+                let (line, column) = LineIndex::new(data).line_column(offset);
+                return Err(ParseError::Located(Box::new(
+                    Diagnostic::error(DiagnosticCode::XmlSyntax, format!("Invalid XML: {}", e))
+                        .at(line, column)
+                        .with_span(position, offset),
                 )));
             }
-
-            _ => {}
         }
 
         buf.clear();
     }
 
-    // Add document header record
-    if !message_type.is_empty() {
-        records.insert(
-            0,
-            ParsedRecord {
-                id: "document-header".to_string(),
-                index: 0,
-                fields: vec![ParsedField {
-                    id: "msg-type".to_string(),
-                    name: "Message Type".to_string(),
-                    value: FieldValue::String(message_type),
-                    field_type: "string".to_string(),
-                    original_value: String::new(),
-                    position: None,
-                }],
-                raw: "Document".to_string(),
-                record_type: "header".to_string(),
-                is_valid: true,
-                errors: None,
-            },
-        );
-    }
+    let record_count = builder.record_index;
+    let mut diagnostics = std::mem::take(&mut builder.diagnostics);
+    let (mut records, headers) = builder.finish();
+    LineIndex::new(data).locate(&mut records);
+    diagnostics.extend(totals::check_records(&mut records, config)?.into_iter().flatten());
+    let invalid_count = records.iter().filter(|r| !r.is_valid).count();
+
+    let end_time = clock::now();
 
-    let end_time = get_time();
+    progress_fn(
+        tracker
+            .progress("complete", total_bytes, record_count)
+            .with_message("XML parsing complete"),
+    );
 
     Ok(ParsedData {
-        id: format!("parsed-{}", js_sys::Date::now() as u64),
+        id: generate_id("parsed"),
         config: config.clone(),
         records,
-        headers: Some(headers.into_iter().collect()),
+        headers: Some(headers),
+        columns: None,
         metadata: ParseMetadata {
-            total_records: record_index,
-            valid_records: record_index,
-            invalid_records: 0,
+            total_records: record_count,
+            valid_records: record_count - invalid_count,
+            invalid_records: invalid_count,
             parse_time: end_time - start_time,
             file_size: Some(total_bytes),
             parser_engine: "wasm".to_string(),
+            diagnostics: (!diagnostics.is_empty()).then_some(diagnostics),
             ..Default::default()
         },
     })
 }
 
-/// Parse XML with progress callback
-pub fn parse_xml_with_progress<F>(
-    data: &str,
-    config: &ParserConfig,
-    progress_fn: F,
-) -> Result<ParsedData, ParseError>
-where
-    F: Fn(ParseProgress),
-{
-    let start_time = get_time();
-    let total_bytes = data.len();
+/// Builds records from XML events
+///
+/// Shared by the one-shot parsers and the incremental `StreamingParser`, which feeds
+/// it events from successive chunks.
+pub(crate) struct XmlRecordBuilder {
+    records: Vec<ParsedRecord>,
+    headers: HashSet<String>,
+    path: Vec<String>,
+    /// Byte offset of each open element's start tag, parallel to `path`
+    element_starts: Vec<usize>,
+    current_text: String,
+    current_fields: Vec<ParsedField>,
+    message_type: String,
+    header_emitted: bool,
+    pub(crate) record_index: usize,
+    /// Declared and actual totals of the message and of the open `PmtInf` block
+    group: TxTotals,
+    block: Option<TxTotals>,
+    /// Path length left once the element holding `GrpHdr` closes
+    group_depth: Option<usize>,
+    /// Amount of the open transaction, once seen
+    tx_amount: Option<Decimal>,
+    /// Mismatched totals, for `ParseMetadata.diagnostics`
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
 
-    progress_fn(
-        ParseProgress::new("initializing", 0, total_bytes, 0).with_message("Starting XML parse..."),
-    );
+/// `NbOfTxs`/`CtrlSum` of a group header or payment block and the transactions seen
+#[derive(Debug)]
+struct TxTotals {
+    /// Record carrying the declared totals
+    record_id: Option<String>,
+    declared_count: Option<Declared>,
+    declared_sum: Option<Declared>,
+    count: usize,
+    sum: Decimal,
+}
 
-    let mut reader = Reader::from_str(data);
-    reader.config_mut().trim_text(true);
-
-    let mut records = Vec::new();
-    let mut headers = std::collections::HashSet::new();
-    let mut path = Vec::new();
-    let mut current_text = String::new();
-    let mut record_index = 0usize;
-    let mut current_fields: Vec<ParsedField> = Vec::new();
-    let mut message_type = String::new();
-    let mut last_progress_update = 0usize;
-    let progress_interval = total_bytes / 100;
+impl Default for TxTotals {
+    fn default() -> Self {
+        Self {
+            record_id: None,
+            declared_count: None,
+            declared_sum: None,
+            count: 0,
+            sum: Decimal::zero(),
+        }
+    }
+}
 
-    let mut buf = Vec::new();
+/// A declared total and the field it was read from
+#[derive(Debug)]
+struct Declared {
+    text: String,
+    field_id: String,
+}
 
-    loop {
-        let position = reader.buffer_position();
+impl XmlRecordBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            records: Vec::new(),
+            headers: HashSet::new(),
+            path: Vec::new(),
+            element_starts: Vec::new(),
+            current_text: String::new(),
+            current_fields: Vec::new(),
+            message_type: String::new(),
+            header_emitted: false,
+            record_index: 0,
+            group: TxTotals::default(),
+            block: None,
+            group_depth: None,
+            tx_amount: None,
+            diagnostics: Vec::new(),
+        }
+    }
 
-        match reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) => {
+    /// Process one event and its source span, returning true when it completed a record
+    pub(crate) fn handle_event(&mut self, event: &Event, span: (usize, usize)) -> bool {
+        match event {
+            Event::Start(e) => {
                 let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-                path.push(name.clone());
 
-                if path.len() <= 2 {
+                match name.as_str() {
+                    "GrpHdr" => self.group_depth = Some(self.path.len().saturating_sub(1)),
+                    "PmtInf" => self.block = Some(TxTotals::default()),
+                    _ if is_transaction(&name) => {
+                        // Block details before the first transaction form the block's own record
+                        if !self.current_fields.is_empty() {
+                            let start = self.element_starts.last().copied().unwrap_or(span.0);
+                            let id = self.push_record("header".to_string(), (start, span.0));
+                            if let Some(block) = &mut self.block {
+                                block.record_id.get_or_insert(id);
+                            }
+                        }
+                        self.tx_amount = None;
+                    }
+                    _ => {}
+                }
+
+                self.path.push(name.clone());
+                self.element_starts.push(span.0);
+
+                // Detect message type from root or Document child
+                if self.path.len() <= 2 {
                     if let Some(mt) = detect_message_type(&name, e) {
-                        message_type = mt;
+                        self.message_type = mt;
                     }
                 }
 
-                for attr in e.attributes().flatten() {
+                // Check for attributes (like currency); the tag content follows the '<'
+                let attribute_spans = attribute_spans(e);
+                for (attr_index, attr) in e.attributes().flatten().enumerate() {
                     let attr_name = String::from_utf8_lossy(attr.key.as_ref()).to_string();
                     let attr_value = String::from_utf8_lossy(&attr.value).to_string();
 
-                    let field_name = format!("{}[@{}]", path.join("."), attr_name);
-                    headers.insert(field_name);
+                    let field_name = format!("{}[@{}]", self.path.join("."), attr_name);
+                    self.headers.insert(field_name);
 
-                    current_fields.push(ParsedField {
-                        id: format!("attr-{}-{}", record_index, current_fields.len()),
+                    self.current_fields.push(ParsedField {
+                        id: format!("attr-{}-{}", self.record_index, self.current_fields.len()),
                         name: humanize_field(&attr_name),
                         value: FieldValue::String(attr_value.clone()),
                         field_type: "string".to_string(),
                         original_value: attr_value,
-                        position: None,
+                        position: attribute_spans
+                            .get(attr_index)
+                            .map(|&(start, end)| Position::new(span.0 + 1 + start, span.0 + 1 + end)),
                     });
                 }
+                false
             }
 
-            Ok(Event::Text(ref e)) => {
-                current_text = e.unescape().map(|s| s.to_string()).unwrap_or_default();
+            Event::Text(e) => {
+                self.current_text = e.unescape().map(|s| s.to_string()).unwrap_or_default();
+                false
             }
 
-            Ok(Event::End(ref e)) => {
+            Event::End(e) => {
                 let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                let element_start = self.element_starts.last().copied().unwrap_or(span.0);
 
-                if !current_text.is_empty() {
-                    let field_path = path.join(".");
-                    headers.insert(field_path);
+                // Save field if we have text content
+                if !self.current_text.is_empty() {
+                    self.headers.insert(self.path.join("."));
 
-                    let (value, field_type) = infer_xml_type(&current_text);
+                    let (value, field_type) = infer_xml_type(&name, &self.current_text);
+                    let field_id =
+                        format!("field-{}-{}", self.record_index, self.current_fields.len());
+                    self.observe_total(&name, &field_id, &value);
 
-                    current_fields.push(ParsedField {
-                        id: format!("field-{}-{}", record_index, current_fields.len()),
+                    self.current_fields.push(ParsedField {
+                        id: field_id,
                         name: humanize_field(&name),
                         value,
                         field_type,
-                        original_value: current_text.clone(),
-                        position: None,
+                        original_value: std::mem::take(&mut self.current_text),
+                        position: Some(Position::new(element_start, span.1)),
                     });
-
-                    current_text.clear();
                 }
 
-                let should_create_record = is_record_boundary(&name, &path);
+                // Create record at certain depth levels or specific elements
+                let should_create_record = is_record_boundary(&name, &self.path);
+                let mut record_created = false;
 
-                if should_create_record && !current_fields.is_empty() {
-                    let record_type = determine_record_type(&path);
+                let mut record_id = None;
 
-                    records.push(ParsedRecord {
-                        id: format!("record-{}", record_index),
-                        index: record_index,
-                        fields: std::mem::take(&mut current_fields),
-                        raw: path.join("/"),
-                        record_type,
-                        is_valid: true,
-                        errors: None,
-                    });
-                    record_index += 1;
-
-                    // Update progress
-                    if position - last_progress_update > progress_interval {
-                        last_progress_update = position;
-                        progress_fn(
-                            ParseProgress::new("parsing", position, total_bytes, record_index)
-                                .with_message(&format!("Parsed {} records...", record_index)),
-                        );
+                if should_create_record && !self.current_fields.is_empty() {
+                    let record_type = determine_record_type(&self.path);
+                    record_id = Some(self.push_record(record_type, (element_start, span.1)));
+                    record_created = true;
+                }
+
+                match name.as_str() {
+                    "GrpHdr" => self.group.record_id = self.group.record_id.take().or(record_id),
+                    "PmtInf" => {
+                        if let Some(mut block) = self.block.take() {
+                            block.record_id = block.record_id.or(record_id);
+                            self.reconcile(block, "PmtInf", "the payment block");
+                        }
+                    }
+                    _ if is_transaction(&name) => {
+                        let amount = self.tx_amount.take().unwrap_or_else(Decimal::zero);
+                        for totals in std::iter::once(&mut self.group).chain(&mut self.block) {
+                            totals.count += 1;
+                            totals.sum = &totals.sum + &amount;
+                        }
                     }
+                    _ => {}
                 }
 
-                path.pop();
+                self.path.pop();
+                self.element_starts.pop();
+
+                if self.group_depth.is_some() && self.group_depth == Some(self.path.len()) {
+                    self.group_depth = None;
+                    let group = std::mem::take(&mut self.group);
+                    self.reconcile(group, "GrpHdr", "the message");
+                }
+                record_created
             }
 
-            Ok(Event::Eof) => break,
+            _ => false,
+        }
+    }
 
-            Err(e) => {
-                return Err(ParseError::XmlError(format!(
-                    "Error at position {}: {:?}",
-                    position, e
-                )));
+    /// Turn the pending fields into a record spanning `span`, returning its id
+    fn push_record(&mut self, record_type: String, span: (usize, usize)) -> String {
+        let id = format!("record-{}", self.record_index);
+        self.records.push(ParsedRecord {
+            id: id.clone(),
+            index: self.record_index,
+            fields: std::mem::take(&mut self.current_fields),
+            raw: self.path.join("/"),
+            record_type,
+            is_valid: true,
+            errors: None,
+            position: Some(Position::new(span.0, span.1)),
+            diagnostics: None,
+        });
+        self.record_index += 1;
+        id
+    }
+
+    /// Note a declared total or a transaction amount ending at element `name`
+    fn observe_total(&mut self, name: &str, field_id: &str, value: &FieldValue) {
+        let parent = self.path.iter().rev().nth(1).map(String::as_str);
+        let totals = match parent {
+            Some("GrpHdr") => Some(&mut self.group),
+            Some("PmtInf") => self.block.as_mut(),
+            _ => None,
+        };
+        if let (Some(totals), "NbOfTxs" | "CtrlSum") = (totals, name) {
+            let declared = Declared {
+                text: self.current_text.trim().to_string(),
+                field_id: field_id.to_string(),
+            };
+            if name == "NbOfTxs" {
+                totals.declared_count = Some(declared);
+            } else {
+                totals.declared_sum = Some(declared);
             }
+            return;
+        }
 
-            _ => {}
+        let is_amount = matches!(name, "InstdAmt" | "IntrBkSttlmAmt")
+            || (name == "Amt" && parent == Some("EqvtAmt"));
+        let in_transaction = self.path.iter().any(|segment| is_transaction(segment));
+        if is_amount && in_transaction && self.tx_amount.is_none() {
+            self.tx_amount = match value {
+                FieldValue::Decimal(d) => Some(d.clone()),
+                _ => self.current_text.trim().parse().ok(),
+            };
         }
+    }
 
-        buf.clear();
+    /// Compare declared totals with the transactions seen, flagging the totals' record
+    fn reconcile(&mut self, totals: TxTotals, element: &str, scope: &str) {
+        let mut mismatches = Vec::new();
+        if let Some(declared) = totals.declared_count {
+            if declared.text.parse::<usize>().ok() != Some(totals.count) {
+                let message = format!(
+                    "NbOfTxs in {} is {}, but {} has {} transactions",
+                    element, declared.text, scope, totals.count
+                );
+                mismatches.push((message, declared.field_id));
+            }
+        }
+        if let Some(declared) = totals.declared_sum {
+            if declared.text.parse::<Decimal>().ok().as_ref() != Some(&totals.sum) {
+                let message = format!(
+                    "CtrlSum in {} is {}, but the transactions in {} add up to {}",
+                    element, declared.text, scope, totals.sum
+                );
+                mismatches.push((message, declared.field_id));
+            }
+        }
+
+        for (message, field_id) in mismatches {
+            let diagnostic =
+                Diagnostic::error(DiagnosticCode::ControlTotal, message).with_field(field_id);
+            let record = totals
+                .record_id
+                .as_ref()
+                .and_then(|id| self.records.iter_mut().find(|r| &r.id == id));
+            if let Some(record) = record {
+                record.add_diagnostics(vec![diagnostic.clone()]);
+            }
+            self.diagnostics.push(diagnostic);
+        }
     }
 
-    if !message_type.is_empty() {
-        records.insert(
-            0,
-            ParsedRecord {
-                id: "document-header".to_string(),
-                index: 0,
-                fields: vec![ParsedField {
-                    id: "msg-type".to_string(),
-                    name: "Message Type".to_string(),
-                    value: FieldValue::String(message_type),
-                    field_type: "string".to_string(),
-                    original_value: String::new(),
-                    position: None,
-                }],
-                raw: "Document".to_string(),
-                record_type: "header".to_string(),
-                is_valid: true,
-                errors: None,
-            },
-        );
+    /// Take the records completed so far, preceded by the document header once known
+    pub(crate) fn take_records(&mut self) -> Vec<ParsedRecord> {
+        let mut records = std::mem::take(&mut self.records);
+        if !self.header_emitted && !self.message_type.is_empty() {
+            records.insert(0, self.document_header());
+            self.header_emitted = true;
+        }
+        records
     }
 
-    let end_time = get_time();
+    /// Field paths seen so far
+    pub(crate) fn headers(&self) -> Vec<String> {
+        self.headers.iter().cloned().collect()
+    }
 
-    progress_fn(
-        ParseProgress::new("complete", total_bytes, total_bytes, record_index)
-            .with_message("XML parsing complete"),
-    );
+    /// Remaining records and all field paths
+    pub(crate) fn finish(mut self) -> (Vec<ParsedRecord>, Vec<String>) {
+        let records = self.take_records();
+        (records, self.headers.into_iter().collect())
+    }
 
-    Ok(ParsedData {
-        id: format!("parsed-{}", js_sys::Date::now() as u64),
-        config: config.clone(),
-        records,
-        headers: Some(headers.into_iter().collect()),
-        metadata: ParseMetadata {
-            total_records: record_index,
-            valid_records: record_index,
-            invalid_records: 0,
-            parse_time: end_time - start_time,
-            file_size: Some(total_bytes),
-            parser_engine: "wasm".to_string(),
-            ..Default::default()
+    /// Document header record carrying the detected message type
+    fn document_header(&self) -> ParsedRecord {
+        ParsedRecord {
+            id: "document-header".to_string(),
+            index: 0,
+            fields: vec![ParsedField {
+                id: "msg-type".to_string(),
+                name: "Message Type".to_string(),
+                value: FieldValue::String(self.message_type.clone()),
+                field_type: "string".to_string(),
+                original_value: String::new(),
+                position: None,
+            }],
+            raw: "Document".to_string(),
+            record_type: "header".to_string(),
+            is_valid: true,
+            errors: None,
+            position: None,
+            diagnostics: None,
+        }
+    }
+}
+
+/// Source span of an event, given the bytes consumed while reading it and their offset
+///
+/// Markup spans start at the `<` (skipping trimmed whitespace); text spans cover the
+/// trimmed text only.
+pub(crate) fn event_span(event: &Event, window: &[u8], window_start: usize) -> (usize, usize) {
+    match event {
+        Event::Text(text) => match memchr::memmem::find(window, text) {
+            Some(offset) => (window_start + offset, window_start + offset + text.len()),
+            None => (window_start, window_start + window.len()),
         },
-    })
+        _ => {
+            let offset = memchr::memchr(b'<', window).unwrap_or(0);
+            (window_start + offset, window_start + window.len())
+        }
+    }
+}
+
+/// Byte ranges of each `name="value"` attribute within a start tag's content
+fn attribute_spans(tag: &[u8]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    // Skip the element name
+    let mut pos = tag
+        .iter()
+        .position(|b| b.is_ascii_whitespace())
+        .unwrap_or(tag.len());
+
+    loop {
+        while pos < tag.len() && tag[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos >= tag.len() || tag[pos] == b'/' {
+            break;
+        }
+
+        let start = pos;
+        while pos < tag.len() && tag[pos] != b'"' && tag[pos] != b'\'' {
+            pos += 1;
+        }
+        let Some(&quote) = tag.get(pos) else { break };
+        pos += 1;
+        while pos < tag.len() && tag[pos] != quote {
+            pos += 1;
+        }
+        pos = (pos + 1).min(tag.len());
+        spans.push((start, pos));
+    }
+
+    spans
 }
 
 /// Detect ISO 20022 message type
@@ -437,6 +626,7 @@ fn detect_message_type(name: &str, element: &BytesStart) -> Option<String> {
 /// Check if element marks a record boundary
 fn is_record_boundary(name: &str, path: &[String]) -> bool {
     let boundary_elements = [
+        "GrpHdr",
         "CdtTrfTxInf",
         "DrctDbtTxInf",
         "TxDtls",
@@ -450,6 +640,10 @@ fn is_record_boundary(name: &str, path: &[String]) -> bool {
     boundary_elements.contains(&name) || (path.len() == 2 && name != "Document")
 }
 
+fn is_transaction(name: &str) -> bool {
+    matches!(name, "CdtTrfTxInf" | "DrctDbtTxInf")
+}
+
 /// Determine record type from path
 fn determine_record_type(path: &[String]) -> String {
     let header_paths = ["GrpHdr", "MsgId", "CreDtTm", "NbOfTxs", "CtrlSum"];
@@ -489,8 +683,24 @@ fn humanize_field(name: &str) -> String {
         })
 }
 
-/// Infer type from XML text value
-fn infer_xml_type(value: &str) -> (FieldValue, String) {
+/// Elements holding monetary amounts
+const AMOUNT_ELEMENTS: &[&str] = &[
+    "Amt",
+    "InstdAmt",
+    "CtrlSum",
+    "IntrBkSttlmAmt",
+    "TtlIntrBkSttlmAmt",
+    "EqvtAmt",
+    "TxAmt",
+    "ChrgsAmt",
+    "RtrdInstdAmt",
+    "TtlNtries",
+    "Sum",
+    "TtlNetNtryAmt",
+];
+
+/// Infer type from the text value of element `name`
+fn infer_xml_type(name: &str, value: &str) -> (FieldValue, String) {
     let trimmed = value.trim();
 
     if trimmed.is_empty() {
@@ -506,11 +716,30 @@ fn infer_xml_type(value: &str) -> (FieldValue, String) {
         return (FieldValue::Boolean(false), "boolean".to_string());
     }
 
+    // Amounts and control sums stay exact, whole numbers included
+    if AMOUNT_ELEMENTS.contains(&name) {
+        if let Ok(d) = trimmed.parse::<Decimal>() {
+            return (FieldValue::Decimal(d), "number".to_string());
+        }
+    }
+
+    // ISODate / ISODateTime elements (`ReqdExctnDt`, `CreDtTm`, `Dt`, ...), offset kept
+    if name.ends_with("Dt") || name.ends_with("DtTm") {
+        if let Some(date) = dates::parse_iso(trimmed) {
+            return (date, "date".to_string());
+        }
+    }
+
     // Check integer
     if let Ok(n) = trimmed.parse::<i64>() {
         return (FieldValue::Integer(n), "number".to_string());
     }
 
+    // Check decimal
+    if let Ok(d) = trimmed.parse::<Decimal>() {
+        return (FieldValue::Decimal(d), "number".to_string());
+    }
+
     // Check float
     if let Ok(n) = trimmed.parse::<f64>() {
         return (FieldValue::Number(n), "number".to_string());
@@ -522,14 +751,6 @@ fn infer_xml_type(value: &str) -> (FieldValue, String) {
     )
 }
 
-/// Get current time in milliseconds
-fn get_time() -> f64 {
-    web_sys::window()
-        .and_then(|w| w.performance())
-        .map(|p| p.now())
-        .unwrap_or(0.0)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -540,4 +761,126 @@ mod tests {
         assert_eq!(humanize_field("CreDtTm"), "Creation Date/Time");
         assert_eq!(humanize_field("UnknownField"), "Unknown Field");
     }
+
+    #[test]
+    fn test_iso_dates() {
+        let json = |name, value| serde_json::to_value(infer_xml_type(name, value).0).unwrap();
+        assert_eq!(json("CreDtTm", "2024-01-15T10:30:00+01:00"), "2024-01-15T10:30:00+01:00");
+        assert_eq!(json("CreDtTm", "2024-01-15T10:30:00"), "2024-01-15T10:30:00");
+        assert_eq!(json("ReqdExctnDt", "2024-01-16"), "2024-01-16");
+        assert!(matches!(infer_xml_type("MsgId", "2024-01-15").0, FieldValue::String(_)));
+    }
+
+    #[test]
+    fn test_control_checks_against_group_header() {
+        let data = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.03"><CstmrCdtTrfInitn>
+<GrpHdr><MsgId>MSG-1</MsgId><NbOfTxs>3</NbOfTxs><CtrlSum>30.00</CtrlSum></GrpHdr>
+<PmtInf><CdtTrfTxInf><Amt><InstdAmt Ccy="EUR">10.00</InstdAmt></Amt></CdtTrfTxInf>
+<CdtTrfTxInf><Amt><InstdAmt Ccy="EUR">20.00</InstdAmt></Amt></CdtTrfTxInf></PmtInf>
+</CstmrCdtTrfInitn></Document>"#;
+        let check = |check: &str, field: Option<&str>, total_field: &str| ControlCheck {
+            check: check.to_string(),
+            field: field.map(str::to_string),
+            record_type: Some("transaction".to_string()),
+            total_field: total_field.to_string(),
+            total_record_type: "header".to_string(),
+        };
+        let config = ParserConfig {
+            parser_type: "iso20022".to_string(),
+            control_checks: Some(vec![
+                check("count", None, "Number of Transactions"),
+                check("sum", Some("Instructed Amount"), "Control Sum"),
+            ]),
+            ..Default::default()
+        };
+        let result = parse_xml(data, &config).unwrap();
+
+        let diagnostics = result.metadata.diagnostics.unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "NbOfTxs in GrpHdr is 3, but the message has 2 transactions"
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "Count of transaction records is 2, but header field \"Number of Transactions\" says 3"
+        );
+        assert_eq!(result.metadata.invalid_records, 1);
+        let group_header = result.records.iter().find(|r| !r.is_valid).unwrap();
+        assert!(group_header.raw.ends_with("GrpHdr"));
+    }
+
+    #[test]
+    fn test_reconciles_group_and_payment_block_totals() {
+        let data = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.03"><CstmrCdtTrfInitn>
+<GrpHdr><MsgId>MSG-1</MsgId><NbOfTxs>3</NbOfTxs><CtrlSum>35.50</CtrlSum></GrpHdr>
+<PmtInf><PmtInfId>P1</PmtInfId><NbOfTxs>2</NbOfTxs><CtrlSum>30.00</CtrlSum>
+<CdtTrfTxInf><Amt><InstdAmt Ccy="EUR">10.00</InstdAmt></Amt></CdtTrfTxInf>
+<CdtTrfTxInf><Amt><InstdAmt Ccy="EUR">20.50</InstdAmt></Amt></CdtTrfTxInf></PmtInf>
+<PmtInf><PmtInfId>P2</PmtInfId><NbOfTxs>1</NbOfTxs><CtrlSum>5.00</CtrlSum>
+<CdtTrfTxInf><Amt><EqvtAmt><Amt Ccy="EUR">5.00</Amt></EqvtAmt></Amt></CdtTrfTxInf></PmtInf>
+</CstmrCdtTrfInitn></Document>"#;
+        let config = ParserConfig {
+            parser_type: "iso20022".to_string(),
+            ..Default::default()
+        };
+        let result = parse_xml(data, &config).unwrap();
+
+        let messages: Vec<_> = result
+            .metadata
+            .diagnostics
+            .unwrap()
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        // The group totals agree; only the first block's sum is off
+        assert_eq!(
+            messages,
+            ["CtrlSum in PmtInf is 30.00, but the transactions in the payment block add up to 30.50"]
+        );
+        assert_eq!(result.metadata.invalid_records, 1);
+        let block = result.records.iter().find(|r| !r.is_valid).unwrap();
+        assert_eq!(block.record_type, "header");
+        assert!(block.raw.ends_with("PmtInf"));
+        assert!(matches!(&block.fields[0].value, FieldValue::String(s) if s == "P1"));
+        assert_eq!(result.records.iter().filter(|r| r.record_type == "transaction").count(), 3);
+    }
+
+    #[test]
+    fn test_syntax_error_is_located() {
+        let data = "<Document>\n  <GrpHdr><MsgId>1</MsgId></Wrong>\n</Document>";
+        let config = ParserConfig {
+            parser_type: "iso20022".to_string(),
+            ..Default::default()
+        };
+
+        match parse_xml(data, &config) {
+            Err(ParseError::Located(diagnostic)) => {
+                assert_eq!(diagnostic.code, DiagnosticCode::XmlSyntax);
+                assert_eq!(diagnostic.line, Some(2));
+            }
+            other => panic!("expected located error, got {:?}", other.map(|d| d.records.len())),
+        }
+    }
+
+    #[test]
+    fn test_element_and_attribute_positions() {
+        let data = "<Document>\n<CdtTrfTxInf><InstdAmt Ccy=\"EUR\">10.00</InstdAmt></CdtTrfTxInf>\n</Document>";
+        let config = ParserConfig {
+            parser_type: "iso20022".to_string(),
+            ..Default::default()
+        };
+        let result = parse_xml(data, &config).unwrap();
+        let record = &result.records[0];
+        let span = |position: &Option<Position>| {
+            let position = position.as_ref().unwrap();
+            &data[position.start..position.end]
+        };
+
+        assert_eq!(span(&record.fields[0].position), "Ccy=\"EUR\"");
+        assert_eq!(span(&record.fields[1].position), "<InstdAmt Ccy=\"EUR\">10.00</InstdAmt>");
+        assert!(span(&record.position).starts_with("<CdtTrfTxInf>"));
+        assert!(matches!(&record.fields[1].value, FieldValue::Decimal(d) if d.to_string() == "10.00"));
+        assert_eq!(record.position.as_ref().unwrap().line, Some(2));
+    }
 }
@@ -4,7 +4,7 @@
  */
 
 import { motion, AnimatePresence } from 'framer-motion'
-import { XMarkIcon, PauseIcon, PlayIcon } from '@heroicons/react/24/outline'
+import { XMarkIcon } from '@heroicons/react/24/outline'
 import type { ParseProgress, ParseController } from '../../types/parser'
 
 interface ProgressBarProps {
@@ -35,7 +35,6 @@ export function ProgressBar({ progress, controller, onCancel }: ProgressBarProps
 
   const isError = progress.phase === 'error'
   const isCancelled = progress.phase === 'cancelled'
-  const isPaused = controller?.isPaused ?? false
 
   return (
     <AnimatePresence>
@@ -57,21 +56,6 @@ export function ProgressBar({ progress, controller, onCancel }: ProgressBarProps
               {getPhaseLabel(progress.phase)}
             </span>
             <div className="flex items-center gap-2">
-              {/* Pause/Resume button */}
-              {controller && !isError && !isCancelled && progress.phase === 'parsing' && (
-                <button
-                  onClick={() => (isPaused ? controller.resume() : controller.pause())}
-                  className="p-1 rounded hover:bg-gray-100 text-gray-500"
-                  title={isPaused ? 'Resume' : 'Pause'}
-                >
-                  {isPaused ? (
-                    <PlayIcon className="w-4 h-4" />
-                  ) : (
-                    <PauseIcon className="w-4 h-4" />
-                  )}
-                </button>
-              )}
-
               {/* Cancel button */}
               {(onCancel || controller) && !isError && !isCancelled && (
                 <button
@@ -124,14 +108,6 @@ export function ProgressBar({ progress, controller, onCancel }: ProgressBarProps
               {progress.message}
             </p>
           )}
-
-          {/* Paused indicator */}
-          {isPaused && (
-            <div className="mt-2 text-xs text-yellow-600 flex items-center gap-1">
-              <PauseIcon className="w-3 h-3" />
-              Paused - Click play to resume
-            </div>
-          )}
         </div>
       </motion.div>
     </AnimatePresence>
@@ -246,7 +246,6 @@ function handleWorkerMessage(event: MessageEvent): void {
  * Create parse controller for cancellation
  */
 function createController(requestId: string): ParseController {
-  let isPaused = false
   let isCancelled = false
 
   return {
@@ -257,21 +256,6 @@ function createController(requestId: string): ParseController {
       }
       pendingRequests.delete(requestId)
     },
-    pause: () => {
-      isPaused = true
-      if (workerInstance) {
-        workerInstance.postMessage({ type: 'pause', id: requestId })
-      }
-    },
-    resume: () => {
-      isPaused = false
-      if (workerInstance) {
-        workerInstance.postMessage({ type: 'resume', id: requestId })
-      }
-    },
-    get isPaused() {
-      return isPaused
-    },
     get isCancelled() {
       return isCancelled
     },
@@ -1,5 +1,5 @@
 import { describe, it, expect } from 'vitest'
-import { parseCSV } from '../csvParser'
+import { parseCSV, csvSourceRows } from '../csvParser'
 import type { ParserConfig } from '../../types/parser'
 
 describe('CSV Parser', () => {
@@ -90,4 +90,32 @@ John,30`
     expect(result.metadata.parseTime).toBeGreaterThan(0)
     expect(result.metadata.fileSize).toBeGreaterThan(0)
   })
+
+  it('should match row rule patterns against the source text', () => {
+    const data = `D,"Doe, J"
+T,1
+"T","2"`
+
+    const config: ParserConfig = {
+      ...defaultConfig,
+      hasHeader: false,
+      rowRules: [{ pattern: '^"T",', type: 'footer' }],
+    }
+    const result = parseCSV(data, config)
+
+    expect(result.records.map((r) => r.type)).toEqual(['data', 'data', 'footer'])
+    expect(result.records[0].raw).toBe('D,"Doe, J"')
+    expect(result.records[2].raw).toBe('"T","2"')
+  })
+
+  it('should split source rows the way Papa does', () => {
+    const data = 'Export\nname,note\nAlice,"multi\nline, ""quoted"""\r\n\n# note\nBob, plain '
+
+    const config: ParserConfig = { ...defaultConfig, skipRows: 1, commentChar: '#' }
+
+    expect(csvSourceRows(data, config)).toEqual([
+      'Alice,"multi\nline, ""quoted"""',
+      'Bob, plain ',
+    ])
+  })
 })
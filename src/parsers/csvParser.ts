@@ -1,6 +1,85 @@
 import Papa from 'papaparse'
 import type { ParserConfig, ParsedData, ParsedRecord, ParsedField } from '../types/parser'
 
+// Record type of a CSV row from config.rowRules; unmatched rows are 'data'. Patterns
+// are matched against `raw`, the row's source text from csvSourceRows, as in the WASM
+// parser
+export function csvRecordType(
+  values: string[],
+  raw: string,
+  config: ParserConfig
+): ParsedRecord['type'] {
+  const rule = (config.rowRules || []).find((r) =>
+    (r.value === undefined || (values[r.column ?? 0] ?? '').trim() === r.value.trim()) &&
+    (r.pattern === undefined || new RegExp(r.pattern).test(raw))
+  )
+  return rule?.type ?? 'data'
+}
+
+// Source text of each data row: quotes and whitespace kept, line break dropped. Rows
+// are split the way the Papa options below do (skipRows, blank and comment lines, the
+// header row), so the result lines up with Papa's rows.
+export function csvSourceRows(data: string, config: ParserConfig): string[] {
+  const delimiter = config.delimiter || ','
+  const quote = config.quoteChar || '"'
+  const escape = config.escapeChar || '\\'
+  const skipRows = config.skipRows ?? 0
+  const text = skipRows > 0 ? data.split('\n').slice(skipRows).join('\n') : data
+
+  const rows: string[] = []
+  const pushRow = (end: number) => {
+    const row = text.slice(rowStart, end)
+    if (row !== '' && !(config.commentChar && row.startsWith(config.commentChar))) {
+      rows.push(row)
+    }
+  }
+  let rowStart = 0
+  let inQuotes = false
+  let atFieldStart = true
+  let i = 0
+  while (i < text.length) {
+    const c = text[i]
+    if (inQuotes) {
+      if ((c === escape || c === quote) && text[i + 1] === quote) {
+        i += 2
+      } else {
+        inQuotes = c !== quote
+        i++
+      }
+    } else if (c === '\n' || c === '\r') {
+      pushRow(i)
+      i += c === '\r' && text[i + 1] === '\n' ? 2 : 1
+      rowStart = i
+      atFieldStart = true
+    } else if (text.startsWith(delimiter, i)) {
+      i += delimiter.length
+      atFieldStart = true
+    } else {
+      inQuotes = atFieldStart && c === quote
+      atFieldStart = false
+      i++
+    }
+  }
+  pushRow(text.length)
+
+  return (config.hasHeader ?? true) ? rows.slice(1) : rows
+}
+
+// Papa options for the comment, skipRows and trim settings of the CSV dialect
+export function csvDialectOptions(config: ParserConfig) {
+  const trim = config.trim ?? 'all'
+  const skipRows = config.skipRows ?? 0
+  return {
+    comments: config.commentChar || false,
+    beforeFirstChunk: (chunk: string) =>
+      skipRows > 0 ? chunk.split('\n').slice(skipRows).join('\n') : undefined,
+    transformHeader: (header: string) =>
+      trim === 'headers' || trim === 'all' ? header.trim() : header,
+    transform: (value: string) =>
+      trim === 'fields' || trim === 'all' ? value.trim() : value,
+  }
+}
+
 export function parseCSV(data: string, config: ParserConfig): ParsedData {
   const startTime = performance.now()
 
@@ -10,9 +89,10 @@ export function parseCSV(data: string, config: ParserConfig): ParsedData {
     quoteChar: config.quoteChar || '"',
     escapeChar: config.escapeChar || '\\',
     skipEmptyLines: true,
-    transformHeader: (header) => header.trim(),
+    ...csvDialectOptions(config),
   })
 
+  const sources = csvSourceRows(data, config)
   const firstRow = result.data[0] as string[] | Record<string, string> | undefined
   const headers: string[] = config.hasHeader
     ? (result.meta.fields || [])
@@ -38,12 +118,14 @@ export function parseCSV(data: string, config: ParserConfig): ParsedData {
       .filter((err) => err.row === index)
       .map((err) => err.message)
 
+    const values = Array.isArray(row) ? row : Object.values(row)
+    const raw = sources[index] ?? values.join(config.delimiter || ',')
     return {
       id: `record-${index}`,
       index,
       fields,
-      raw: Array.isArray(row) ? row.join(config.delimiter || ',') : Object.values(row).join(config.delimiter || ','),
-      type: index === 0 && config.hasHeader ? 'header' : 'data',
+      raw,
+      type: csvRecordType(values, raw, config),
       isValid,
       errors: errors.length > 0 ? errors : undefined,
     }
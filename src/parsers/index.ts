@@ -33,8 +33,9 @@ export function detectParserType(data: string): ParserConfig['type'] {
     return 'iso20022'
   }
 
-  // Check for SWIFT FIN message
-  if (trimmedData.startsWith('{1:') || /\{4:\s*\n?:20:/.test(trimmedData)) {
+  // Check for SWIFT FIN message, bare or framed by an RJE ($) or DOS-PCC (0x01) envelope
+  const unframed = trimmedData.replace(/^[\x01$]+\s*/, '')
+  if (unframed.startsWith('{1:') || /\{4:\s*\n?:20:/.test(trimmedData)) {
     return 'fin'
   }
 
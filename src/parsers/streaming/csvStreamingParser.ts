@@ -11,6 +11,7 @@ import type {
   ParsedField,
   ParseProgress,
 } from '../../types/parser'
+import { csvDialectOptions, csvRecordType, csvSourceRows } from '../csvParser'
 
 const DEFAULT_CHUNK_SIZE = 1024 * 64 // 64KB chunks
 
@@ -23,6 +24,7 @@ export async function parseCSVStreaming(
   return new Promise((resolve, reject) => {
     const startTime = performance.now()
     const totalBytes = new Blob([data]).size
+    const sources = csvSourceRows(data, config)
     const records: ParsedRecord[] = []
     const headerSet = new Set<string>()
     let headers: string[] = []
@@ -40,7 +42,7 @@ export async function parseCSVStreaming(
       quoteChar: config.quoteChar || '"',
       escapeChar: config.escapeChar || '\\',
       skipEmptyLines: true,
-      transformHeader: (header: string) => header.trim(),
+      ...csvDialectOptions(config),
       chunkSize,
       chunk: (results: Papa.ParseResult<Record<string, string> | string[]>, parser: Papa.Parser) => {
         // Check for cancellation
@@ -74,15 +76,15 @@ export async function parseCSVStreaming(
           })
 
           const hasErrors = results.errors.some((e: Papa.ParseError) => e.row === recordIndex)
+          const values = Array.isArray(row) ? row : Object.values(row)
+          const raw = sources[recordIndex] ?? values.join(config.delimiter || ',')
 
           records.push({
             id: `record-${recordIndex}`,
             index: recordIndex,
             fields,
-            raw: Array.isArray(row)
-              ? row.join(config.delimiter || ',')
-              : Object.values(row).join(config.delimiter || ','),
-            type: recordIndex === 0 && config.hasHeader ? 'header' : 'data',
+            raw,
+            type: csvRecordType(values, raw, config),
             isValid: !hasErrors,
             errors: hasErrors
               ? results.errors.filter((e: Papa.ParseError) => e.row === recordIndex).map((e: Papa.ParseError) => e.message)
@@ -115,6 +117,8 @@ export async function parseCSVStreaming(
             recordsProcessed: recordIndex,
             percentage: Math.round((bytesProcessed / totalBytes) * 100),
             estimatedTimeRemaining: Math.round(estimatedTimeRemaining),
+            currentChunk: Math.max(1, Math.ceil(bytesProcessed / chunkSize)),
+            totalChunks: Math.max(1, Math.ceil(totalBytes / chunkSize)),
             message: `Parsed ${recordIndex.toLocaleString()} records...`,
           })
         }
@@ -202,14 +206,17 @@ export function parseCSVFile(
     let invalidCount = 0
     let lastProgressUpdate = 0
 
+    const chunkSize = config.chunkSize || DEFAULT_CHUNK_SIZE
+
     Papa.parse(file, {
       delimiter: config.delimiter || ',',
       header: config.hasHeader ?? true,
       quoteChar: config.quoteChar || '"',
       escapeChar: config.escapeChar || '\\',
       skipEmptyLines: true,
-      transformHeader: (header: string) => header.trim(),
+      ...csvDialectOptions(config),
       worker: false, // We're already in a worker
+      chunkSize,
       step: (results: Papa.ParseStepResult<Record<string, string> | string[]>, parser: Papa.Parser) => {
         if (isCancelled()) {
           parser.abort()
@@ -238,15 +245,17 @@ export function parseCSVFile(
         })
 
         const hasErrors = results.errors.length > 0
+        // The file is never held whole, so its source text is not available here: raw
+        // and row rule patterns see the values joined by the delimiter instead
+        const values = Array.isArray(row) ? row : Object.values(row)
+        const raw = values.join(config.delimiter || ',')
 
         records.push({
           id: `record-${recordIndex}`,
           index: recordIndex,
           fields,
-          raw: Array.isArray(row)
-            ? row.join(config.delimiter || ',')
-            : Object.values(row).join(config.delimiter || ','),
-          type: recordIndex === 0 && config.hasHeader ? 'header' : 'data',
+          raw,
+          type: csvRecordType(values, raw, config),
           isValid: !hasErrors,
           errors: hasErrors ? results.errors.map((e: Papa.ParseError) => e.message) : undefined,
         })
@@ -276,6 +285,8 @@ export function parseCSVFile(
             recordsProcessed: recordIndex,
             percentage: Math.round((bytesProcessed / totalBytes) * 100),
             estimatedTimeRemaining: Math.round(estimatedTimeRemaining),
+            currentChunk: Math.max(1, Math.ceil(bytesProcessed / chunkSize)),
+            totalChunks: Math.max(1, Math.ceil(totalBytes / chunkSize)),
             message: `Parsed ${recordIndex.toLocaleString()} records...`,
           })
         }
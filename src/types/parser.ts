@@ -6,12 +6,39 @@ export interface ParserConfig {
   name: string
   description?: string
   // CSV specific
+  // May be several characters, e.g. '|~|'
   delimiter?: string
+  // Regex separating fields, e.g. '\\s+'; overrides delimiter (WASM parser only)
+  delimiterPattern?: string
   hasHeader?: boolean
   quoteChar?: string
   escapeChar?: string
+  // Lines starting with this character are skipped, e.g. '#'
+  commentChar?: string
+  // Preamble lines skipped before the header row
+  skipRows?: number
+  // Whitespace trimming of header names and text values (default 'all')
+  trim?: 'none' | 'headers' | 'fields' | 'all'
+  // Reject empty/duplicate headers and mark rows with the wrong field count invalid
+  strict?: boolean
+  // Header/detail/trailer typing of CSV rows; the first matching rule wins
+  rowRules?: CsvRowRule[]
+  // Record counts and totals checked against trailer fields after parsing
+  controlChecks?: ControlCheck[]
+  // Date format per column name, e.g. { 'Booking Date': 'DD/MM/YYYY' }
+  columnFormats?: Record<string, string>
+  // Type CSV columns from their values; false keeps every cell as text
+  inferTypes?: boolean
+  // Number separators, e.g. ',' and '.' for 1.234,56; detected per column when unset
+  decimalSeparator?: string
+  thousandsSeparator?: string
   // Fixed width specific
   fieldDefinitions?: FieldDefinition[]
+  recordLayouts?: RecordLayout[]
+  discriminator?: { start: number; length: number }
+  // Characters per record (RECFM=F) instead of one record per line; defaults to the
+  // layout's length when it has packed or binary fields
+  recordLength?: number
   // FIN specific
   messageType?: string
   // Custom specific
@@ -23,6 +50,27 @@ export interface ParserConfig {
   encoding?: string
 }
 
+// Matches rows whose column `column` (default 0) equals `value` and/or whose source text
+// (`raw`, quotes and whitespace kept) matches `pattern`; `columns` names the fields of
+// matching rows
+export interface CsvRowRule {
+  column?: number
+  value?: string
+  pattern?: string
+  type?: 'header' | 'transaction' | 'footer' | 'data'
+  columns?: string[]
+}
+
+// Compares a count, sum or digit hash of the checked records (all but header/footer
+// when recordType is unset) with totalField of the last totalRecordType record
+export interface ControlCheck {
+  check: 'count' | 'sum' | 'hash'
+  field?: string
+  recordType?: string
+  totalField: string
+  totalRecordType?: string // default 'footer'
+}
+
 export interface FieldDefinition {
   id: string
   name: string
@@ -32,15 +80,37 @@ export interface FieldDefinition {
   format?: string
   required?: boolean
   description?: string
+  // 'edited' reads numeric-edited text (ZZ,ZZ9.99-) against the PIC in format
+  usage?: 'display' | 'zoned' | 'packed' | 'binary' | 'edited'
+  scale?: number
+  signed?: boolean
+}
+
+export interface RecordLayout {
+  id: string
+  name: string
+  discriminatorValue: string
+  type?: 'header' | 'transaction' | 'footer' | 'data'
+  fieldDefinitions: FieldDefinition[]
 }
 
 export interface ParsedField {
   id: string
   name: string
+  // Exact decimal amounts arrive from the WASM parsers as strings (e.g. "1234.56"),
+  // dates as ISO 8601 strings (e.g. "2024-01-15", "2024-01-15T10:30:00+01:00")
   value: string | number | boolean | null
   type: string
   originalValue: string
-  position?: { start: number; end: number }
+  position?: SourcePosition
+}
+
+// Byte range in the decoded input; line/column (1-based) of the start when known
+export interface SourcePosition {
+  start: number
+  end: number
+  line?: number
+  column?: number
 }
 
 export interface ParsedRecord {
@@ -51,6 +121,47 @@ export interface ParsedRecord {
   type: 'header' | 'transaction' | 'footer' | 'data'
   isValid: boolean
   errors?: string[]
+  position?: SourcePosition
+  diagnostics?: ParseDiagnostic[]
+}
+
+export type DiagnosticSeverity = 'error' | 'warning' | 'info'
+
+export type DiagnosticCode =
+  | 'CSV_SYNTAX'
+  | 'CSV_UTF8'
+  | 'CSV_FIELD_COUNT'
+  | 'CONTROL_TOTAL'
+  | 'XML_SYNTAX'
+  | 'FIN_BLOCK'
+  | 'FIN_FIELD'
+  | 'FIN_FORMAT'
+  | 'FIELD_REQUIRED'
+  | 'FIELD_CONVERSION'
+  | 'UNKNOWN_RECORD_LAYOUT'
+  | 'SHORT_RECORD'
+  | 'ENCODING'
+  | 'CONFIG'
+  | 'COPYBOOK'
+  | 'CANCELLED'
+
+// Structured parse problem; line/column are 1-based, span is a byte range
+export interface ParseDiagnostic {
+  code: DiagnosticCode
+  severity: DiagnosticSeverity
+  message: string
+  line?: number
+  column?: number
+  span?: SourcePosition
+  fieldId?: string
+}
+
+// Error thrown by the wasm parsers; a cancelled parse (code 'CANCELLED') carries the
+// metadata of the records parsed before it stopped
+export interface WasmParseError extends Error {
+  code?: DiagnosticCode
+  diagnostic?: ParseDiagnostic
+  metadata?: ParseMetadata
 }
 
 export interface ParsedData {
@@ -58,9 +169,19 @@ export interface ParsedData {
   config: ParserConfig
   records: ParsedRecord[]
   headers?: string[]
+  columns?: ColumnSchema[]
   metadata: ParseMetadata
 }
 
+// Type inferred for a CSV column; confidence and nullRatio are shares (0-1)
+export interface ColumnSchema {
+  name: string
+  type: 'string' | 'number' | 'date' | 'boolean' | 'null'
+  confidence: number
+  nullRatio: number
+  decimalSeparator?: string
+}
+
 export interface ParseMetadata {
   totalRecords: number
   validRecords: number
@@ -71,13 +192,26 @@ export interface ParseMetadata {
   encoding?: string
   parserEngine?: 'js' | 'wasm'
   chunksProcessed?: number
+  // Problems with the file as a whole, e.g. failed control totals
+  diagnostics?: ParseDiagnostic[]
+  // Messages of a multi-message file (SWIFT FIN), in file order
+  messages?: MessageSummary[]
+}
+
+// One message of a multi-message file and the records it produced
+export interface MessageSummary {
+  index: number
+  messageType?: string // e.g. 'MT103'
+  firstRecord: number // index of the message's first record in ParsedData.records
+  recordCount: number
+  position?: SourcePosition
 }
 
 // Progress reporting for streaming
 export interface ParseProgress {
   phase: 'initializing' | 'detecting' | 'parsing' | 'finalizing' | 'complete' | 'error' | 'cancelled'
   bytesProcessed: number
-  totalBytes: number
+  totalBytes: number // 0 while the input size is unknown
   recordsProcessed: number
   percentage: number
   currentChunk?: number
@@ -88,12 +222,10 @@ export interface ParseProgress {
 
 export type ProgressCallback = (progress: ParseProgress) => void
 
-// Cancelable parsing
+// Cancelable parsing; there is no pause, since a parse holds its worker's thread and
+// could never see a resume message
 export interface ParseController {
   cancel: () => void
-  pause: () => void
-  resume: () => void
-  isPaused: boolean
   isCancelled: boolean
 }
 
@@ -183,8 +315,6 @@ export type WorkerMessageType =
   | 'result'
   | 'error'
   | 'cancel'
-  | 'pause'
-  | 'resume'
   | 'init'
   | 'ready'
 
@@ -219,8 +349,11 @@ export type EncodingType =
   | 'utf-16be'
   | 'utf-16le'
   | 'iso-8859-1'
+  | 'iso-8859-15'
   | 'windows-1252'
   | 'ascii'
+  | 'cp037'
+  | 'cp1047'
 
 export interface EncodingInfo {
   encoding: EncodingType
@@ -16,7 +16,6 @@ import { detectEncoding, decodeBuffer } from '../parsers/utils/encodingDetector'
 
 // Worker state
 let isCancelled = false
-let isPaused = false
 let wasmModule: unknown = null
 
 // Message handler
@@ -41,14 +40,6 @@ self.onmessage = async (event: MessageEvent<WorkerMessage>) => {
         payload: createProgress('cancelled', 0, 0, 0, 'Parsing cancelled'),
       })
       break
-
-    case 'pause':
-      isPaused = true
-      break
-
-    case 'resume':
-      isPaused = false
-      break
   }
 }
 
@@ -70,7 +61,6 @@ async function initializeWorker(): Promise<void> {
 
 async function handleParse(id: string, request: ParseRequest): Promise<void> {
   isCancelled = false
-  isPaused = false
 
   const startTime = performance.now()
   let data: string
@@ -114,9 +104,6 @@ async function handleParse(id: string, request: ParseRequest): Promise<void> {
         request.config,
         (progress) => {
           if (isCancelled) throw new Error('Cancelled')
-          while (isPaused) {
-            // Busy wait for pause (in real impl, use atomics)
-          }
           postMessage({ type: 'progress', id, payload: progress })
         },
         () => isCancelled